@@ -14,6 +14,10 @@ pub struct AppConfig {
     pub cors_origins: Vec<String>,
     pub supabase_url: Option<String>,
     pub supabase_key: Option<String>,
+    /// Executions running at once across all users, 0 for no limit
+    pub max_concurrent_executions: usize,
+    /// Executions running at once for a single user, 0 for no limit
+    pub max_concurrent_executions_per_user: usize,
 }
 
 impl AppConfig {
@@ -53,6 +57,16 @@ impl AppConfig {
             "http://127.0.0.1:4173".to_string(),
         ];
 
+        let max_concurrent_executions = env::var("MAX_CONCURRENT_EXECUTIONS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+
+        let max_concurrent_executions_per_user = env::var("MAX_CONCURRENT_EXECUTIONS_PER_USER")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3);
+
         let supabase_url = env::var("SUPABASE_URL").ok();
         let supabase_key = env::var("SUPABASE_KEY").ok();
 
@@ -71,6 +85,8 @@ impl AppConfig {
             cors_origins,
             supabase_url,
             supabase_key,
+            max_concurrent_executions,
+            max_concurrent_executions_per_user,
         }
     }
 }
@@ -122,6 +138,8 @@ mod tests {
         env::remove_var("JWT_SECRET_KEY");
         env::remove_var("JWT_EXPIRE_MINUTES");
         env::remove_var("ENCRYPTION_KEY");
+        env::remove_var("MAX_CONCURRENT_EXECUTIONS");
+        env::remove_var("MAX_CONCURRENT_EXECUTIONS_PER_USER");
 
         let config = AppConfig::from_env();
 
@@ -129,6 +147,8 @@ mod tests {
         assert_eq!(config.jwt_secret_key, "hypernova_secret_key_2024_pods");
         assert_eq!(config.jwt_algorithm, "HS256");
         assert_eq!(config.jwt_expire_minutes, 10080); // 7 days
+        assert_eq!(config.max_concurrent_executions, 10);
+        assert_eq!(config.max_concurrent_executions_per_user, 3);
     }
 
     #[test]
@@ -5,6 +5,9 @@ pub const MCP_SERVER_CONNECTIONS: &str = "mcp_server_connections";
 pub const FLOWS: &str = "flows";
 pub const FLOW_EXECUTIONS: &str = "flow_executions";
 pub const FLOW_EVENTS: &str = "flow_events";
+pub const FLOW_SCHEDULES: &str = "flow_schedules";
+pub const FLOW_TRIGGERS: &str = "flow_triggers";
+pub const TRIGGER_DELIVERIES: &str = "flow_trigger_deliveries";
 pub const CHAT_SESSIONS: &str = "chat_sessions";
 #[allow(dead_code)]
 pub const CHAT_MESSAGES: &str = "chat_messages";
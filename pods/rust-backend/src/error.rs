@@ -3,11 +3,20 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
 
+/// A problem with one field of a request
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
 #[derive(Debug)]
 pub enum AppError {
     BadRequest(String),
+    InvalidInput(Vec<FieldError>),
     Unauthorized(String),
     Forbidden(String),
     NotFound(String),
@@ -20,6 +29,7 @@ impl std::fmt::Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AppError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
+            AppError::InvalidInput(errors) => write!(f, "Invalid Input: {}", field_errors_summary(errors)),
             AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             AppError::NotFound(msg) => write!(f, "Not Found: {}", msg),
@@ -30,23 +40,50 @@ impl std::fmt::Display for AppError {
     }
 }
 
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidInput(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Internal(_) | AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match &self {
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
-            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
-            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
-            AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
-            AppError::Database(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
+        let status = self.status_code();
+        let message = match &self {
+            AppError::InvalidInput(errors) => format!("Invalid input: {}", field_errors_summary(errors)),
+            AppError::BadRequest(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::Forbidden(msg)
+            | AppError::NotFound(msg)
+            | AppError::Conflict(msg)
+            | AppError::Internal(msg)
+            | AppError::Database(msg) => msg.clone(),
         };
 
         tracing::error!("{}", self);
-        (status, Json(json!({ "detail": message }))).into_response()
+        let mut body = json!({ "detail": message });
+        if let AppError::InvalidInput(errors) = &self {
+            body["errors"] = json!(errors);
+        }
+        (status, Json(body)).into_response()
     }
 }
 
+fn field_errors_summary(errors: &[FieldError]) -> String {
+    errors.iter()
+        .map(|e| format!("{}: {}", e.field, e.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 impl From<mongodb::error::Error> for AppError {
     fn from(e: mongodb::error::Error) -> Self {
         AppError::Database(e.to_string())
@@ -107,6 +144,10 @@ mod tests {
             (AppError::Conflict("test".into()), StatusCode::CONFLICT),
             (AppError::Internal("test".into()), StatusCode::INTERNAL_SERVER_ERROR),
             (AppError::Database("test".into()), StatusCode::INTERNAL_SERVER_ERROR),
+            (
+                AppError::InvalidInput(vec![FieldError { field: "x".into(), message: "test".into() }]),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
         ];
 
         for (error, expected_status) in test_cases {
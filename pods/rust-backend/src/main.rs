@@ -17,6 +17,7 @@ use std::sync::Arc;
 
 use config::AppConfig;
 use db::mongo;
+use services::execution_queue::QueueLimits;
 use services::flow_service::FlowService;
 use services::mcp_session_manager::McpSessionManager;
 use state::AppState;
@@ -52,6 +53,7 @@ async fn main() {
         mongo_client.clone(),
         config.fernet_key.clone(),
         Arc::clone(&mcp_manager),
+        QueueLimits::new(config.max_concurrent_executions, config.max_concurrent_executions_per_user),
     ));
 
     // Create app state
@@ -65,6 +67,17 @@ async fn main() {
     // Run startup initialization
     startup::startup_initialization(&mongo_client, &config).await;
 
+    // Resume or fail executions left running by a previous process
+    if let Err(e) = state.flow_service.recover_interrupted_executions().await {
+        tracing::error!("Failed to recover interrupted executions: {}", e);
+    }
+
+    // Start queued executions as concurrency limits allow
+    state.flow_service.start_queue_dispatcher();
+
+    // Start executions for cron schedules, catching up on runs missed while down
+    state.flow_service.start_scheduler();
+
     // CORS layer - allow all origins in dev (matches Python backend)
     let cors = CorsLayer::new()
         .allow_origin(Any)
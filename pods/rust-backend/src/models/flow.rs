@@ -13,6 +13,13 @@ pub enum FlowStepType {
     FeedbackLoop,
     QualityCheck,
     Approval,
+    Switch,
+    SubFlow,
+    #[serde(alias = "for_each")]
+    Map,
+    HumanInput,
+    Delay,
+    WaitForSignal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -23,13 +30,25 @@ pub enum FlowStepStatus {
     Completed,
     Failed,
     Skipped,
+    TimedOut,
+    /// Dropped before it finished: its branch lost the join, or the execution
+    /// was cancelled
+    Cancelled,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum FlowExecutionStatus {
     Pending,
+    /// Waiting for a slot under the concurrency limits
+    Queued,
     Running,
+    /// Paused on a human input step until its form is submitted
+    AwaitingInput,
+    /// Stopped before a step by a breakpoint, a debugger step or a pause request
+    Paused,
+    /// Parked on a delay step until `wake_at`, without a concurrency slot
+    Waiting,
     Completed,
     Failed,
     Cancelled,
@@ -102,6 +121,37 @@ pub struct FlowStep {
 fn default_step_type() -> FlowStepType { FlowStepType::Llm }
 fn default_timeout() -> Option<i32> { Some(300) }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowInputType {
+    #[default]
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
+    Object,
+}
+
+/// A declared flow input, validated when an execution starts
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct FlowInput {
+    #[serde(rename = "type", default)]
+    pub input_type: FlowInputType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub default: Option<serde_json::Value>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Allowed values
+    #[serde(rename = "enum", default)]
+    pub allowed_values: Option<Vec<serde_json::Value>>,
+    /// Regex that string values must match
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FlowStepResult {
     pub step_id: String,
@@ -135,6 +185,8 @@ pub struct Flow {
     #[serde(default)]
     pub variables: HashMap<String, serde_json::Value>,
     #[serde(default)]
+    pub inputs: HashMap<String, FlowInput>,
+    #[serde(default)]
     pub metadata: HashMap<String, serde_json::Value>,
     #[serde(default)]
     pub edge_metadata: HashMap<String, EdgeMetadata>,
@@ -191,6 +243,159 @@ pub struct FlowExecution {
     pub pending_approval_step_id: Option<String>,
     #[serde(default)]
     pub approval_decision: Option<bool>,
+    /// Approval steps waiting for decisions, by step ID (`<step_id>#<item>`
+    /// for the items of a map step)
+    #[serde(default)]
+    pub pending_approvals: HashMap<String, PendingApproval>,
+    /// Every approval decision taken on this execution, oldest first
+    #[serde(default)]
+    pub approval_history: Vec<ApprovalDecision>,
+    /// Set on executions started by a sub-flow step
+    #[serde(default)]
+    pub parent_execution_id: Option<String>,
+    #[serde(default)]
+    pub parent_step_id: Option<String>,
+    /// Executions above this one, outermost first
+    #[serde(default)]
+    pub ancestor_execution_ids: Vec<String>,
+    /// Sub-flow nesting level, 0 for top-level executions
+    #[serde(default)]
+    pub depth: i32,
+    #[serde(default)]
+    pub child_execution_ids: Vec<String>,
+    /// Set on executions created by a rerun
+    #[serde(default)]
+    pub rerun_of_execution_id: Option<String>,
+    #[serde(default)]
+    pub rerun_from_step_id: Option<String>,
+    /// Step parameter edits applied to the flow for this execution only
+    #[serde(default)]
+    pub parameter_overrides: HashMap<String, HashMap<String, serde_json::Value>>,
+    /// Queue ordering: higher priority starts first, then earlier `queued_at`
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub queued_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub trigger: Option<ExecutionTrigger>,
+    /// Forms of human input steps waiting for a submission, by step ID
+    #[serde(default)]
+    pub pending_inputs: HashMap<String, PendingInput>,
+    /// Set on executions run in debug mode
+    #[serde(default)]
+    pub debug: Option<DebugOptions>,
+    /// Where the execution is stopped, set while `paused`
+    #[serde(default)]
+    pub paused: Option<PausedAt>,
+    /// When a `waiting` execution goes back in the queue
+    #[serde(default)]
+    pub wake_at: Option<DateTime<Utc>>,
+    /// Wait-for-signal steps waiting for a signal
+    #[serde(default)]
+    pub signal_waits: Vec<SignalWait>,
+}
+
+/// What started a top-level execution
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExecutionTrigger {
+    /// Started through the API or CLI
+    Manual,
+    /// Started by a cron schedule for the run due at `scheduled_for`
+    Schedule { schedule_id: String, scheduled_for: DateTime<Utc> },
+    /// Started by a request to a webhook trigger, audited as `delivery_id`
+    Webhook { trigger_id: String, delivery_id: String },
+}
+
+/// A wait-for-signal step's wait
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignalWait {
+    pub step_id: String,
+    pub signal: String,
+    pub correlation_id: String,
+    pub requested_at: DateTime<Utc>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Debug mode settings of an execution
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DebugOptions {
+    /// Step IDs the execution pauses before
+    #[serde(default)]
+    pub breakpoints: Vec<String>,
+}
+
+/// Why an execution paused
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseReason {
+    Breakpoint,
+    /// Stepping stopped it before the next step
+    Step,
+    /// A pause was requested
+    Request,
+}
+
+/// The step an execution is paused before
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PausedAt {
+    pub step_id: String,
+    pub reason: PauseReason,
+    pub paused_at: DateTime<Utc>,
+}
+
+/// A human input step's request for values
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingInput {
+    pub step_id: String,
+    pub prompt: String,
+    /// Form schema, in the same shape as flow inputs
+    #[serde(default)]
+    pub fields: HashMap<String, FlowInput>,
+    pub requested_at: DateTime<Utc>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// An approval step's request for decisions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingApproval {
+    /// Distinguishes visits of the same step, e.g. after a rework
+    pub request_id: String,
+    pub step_id: String,
+    pub message: String,
+    /// Usernames allowed to decide; only the execution's owner when empty
+    #[serde(default)]
+    pub approvers: Vec<String>,
+    pub required_approvals: u32,
+    /// Step whose output an approver may replace
+    #[serde(default)]
+    pub target_step_id: Option<String>,
+    /// Step a rejection continues at instead of failing the execution
+    #[serde(default)]
+    pub rework_step_id: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub decisions: Vec<ApprovalDecision>,
+}
+
+/// One approver's decision on an approval step
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovalDecision {
+    pub request_id: String,
+    pub step_id: String,
+    pub user_id: String,
+    pub username: String,
+    pub approved: bool,
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Replacement for the target step's output
+    #[serde(default)]
+    pub edited_output: Option<serde_json::Value>,
+    pub decided_at: DateTime<Utc>,
 }
 
 #[allow(dead_code)]
@@ -206,10 +411,13 @@ pub struct FlowCreate {
     #[serde(default)]
     pub description: Option<String>,
     pub steps: Vec<FlowStep>,
+    #[serde(alias = "start_step")]
     pub start_step_id: String,
     #[serde(default)]
     pub variables: HashMap<String, serde_json::Value>,
     #[serde(default)]
+    pub inputs: HashMap<String, FlowInput>,
+    #[serde(default)]
     pub metadata: HashMap<String, serde_json::Value>,
     #[serde(default)]
     pub edge_metadata: HashMap<String, EdgeMetadata>,
@@ -228,6 +436,8 @@ pub struct FlowUpdate {
     #[serde(default)]
     pub variables: Option<HashMap<String, serde_json::Value>>,
     #[serde(default)]
+    pub inputs: Option<HashMap<String, FlowInput>>,
+    #[serde(default)]
     pub metadata: Option<HashMap<String, serde_json::Value>>,
     #[serde(default)]
     pub edge_metadata: Option<HashMap<String, EdgeMetadata>>,
@@ -247,6 +457,8 @@ pub struct FlowResponse {
     #[serde(default)]
     pub variables: HashMap<String, serde_json::Value>,
     #[serde(default)]
+    pub inputs: HashMap<String, FlowInput>,
+    #[serde(default)]
     pub metadata: HashMap<String, serde_json::Value>,
     #[serde(default)]
     pub edge_metadata: HashMap<String, EdgeMetadata>,
@@ -256,14 +468,38 @@ pub struct FlowResponse {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct FlowExecutionCreate {
+    /// May repeat the flow named in the URL, but not name another one
     #[serde(default)]
     pub flow_id: Option<String>,
     #[serde(default)]
     pub input_data: HashMap<String, serde_json::Value>,
     #[serde(default)]
     pub variables: HashMap<String, serde_json::Value>,
+    /// Queue priority; the flow's `metadata.priority` (or 0) when omitted
+    #[serde(default)]
+    pub priority: Option<i32>,
+    /// Run in debug mode, pausing before the breakpoints
+    #[serde(default)]
+    pub debug: Option<DebugOptions>,
+}
+
+/// Edits applied to a rerun before it starts
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FlowExecutionRerun {
+    /// Variables to set or replace, including restored `step_<id>_output` values
+    #[serde(default)]
+    pub variables: HashMap<String, serde_json::Value>,
+    /// Step ID to parameters to set; `null` removes a parameter
+    #[serde(default)]
+    pub step_parameters: HashMap<String, HashMap<String, serde_json::Value>>,
+    /// Queue priority; the flow's default when omitted
+    #[serde(default)]
+    pub priority: Option<i32>,
+    /// Run in debug mode, pausing before the breakpoints
+    #[serde(default)]
+    pub debug: Option<DebugOptions>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -292,10 +528,93 @@ pub struct FlowExecutionResponse {
     pub end_time: Option<DateTime<Utc>>,
     #[serde(default)]
     pub execution_time_ms: Option<i64>,
+    #[serde(default)]
+    pub parent_execution_id: Option<String>,
+    #[serde(default)]
+    pub parent_step_id: Option<String>,
+    #[serde(default)]
+    pub depth: i32,
+    #[serde(default)]
+    pub child_execution_ids: Vec<String>,
+    #[serde(default)]
+    pub rerun_of_execution_id: Option<String>,
+    #[serde(default)]
+    pub rerun_from_step_id: Option<String>,
+    #[serde(default)]
+    pub priority: i32,
+    /// 1-based position among queued executions, set while `queued`
+    #[serde(default)]
+    pub queue_position: Option<u64>,
+    #[serde(default)]
+    pub trigger: Option<ExecutionTrigger>,
+    /// Forms waiting for a submission, set while `awaiting_input`
+    #[serde(default)]
+    pub pending_inputs: HashMap<String, PendingInput>,
+    /// Approval requests waiting for decisions, by step ID
+    #[serde(default)]
+    pub pending_approvals: HashMap<String, PendingApproval>,
+    #[serde(default)]
+    pub approval_history: Vec<ApprovalDecision>,
+    #[serde(default)]
+    pub debug: Option<DebugOptions>,
+    /// Where the execution is stopped, set while `paused`
+    #[serde(default)]
+    pub paused: Option<PausedAt>,
+    /// When a `waiting` execution goes back in the queue
+    #[serde(default)]
+    pub wake_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub signal_waits: Vec<SignalWait>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// An approver's decision
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApprovalSubmission {
+    /// Request to decide, by its key in `pending_approvals`; may be omitted
+    /// while the caller can decide only one
+    #[serde(default)]
+    pub step_id: Option<String>,
+    #[serde(default = "default_true")]
+    pub approved: bool,
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Replace the output of the step under review before continuing
+    #[serde(default)]
+    pub edited_output: Option<serde_json::Value>,
+}
+
+/// Values for a human input step
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HumanInputSubmission {
+    /// Step to answer; may be omitted while only one step is waiting
+    #[serde(default)]
+    pub step_id: Option<String>,
+    #[serde(default)]
+    pub values: HashMap<String, serde_json::Value>,
+}
+
+/// A signal for the wait-for-signal steps waiting on it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignalDelivery {
+    pub correlation_id: String,
+    /// Output of the steps that receive the signal
+    #[serde(default)]
+    pub payload: serde_json::Value,
+}
+
+/// Changes to a paused execution's variables
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VariablesUpdate {
+    /// Variables to set or replace
+    #[serde(default)]
+    pub set: HashMap<String, serde_json::Value>,
+    /// Variables to remove
+    #[serde(default)]
+    pub unset: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FlowExecutionListResponse {
     pub executions: Vec<FlowExecutionResponse>,
@@ -5,15 +5,19 @@ use std::collections::HashMap;
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum FlowEventType {
+    ExecutionQueued,
     ExecutionStarted,
     ExecutionCompleted,
     ExecutionFailed,
     ExecutionCancelled,
+    ExecutionResumed,
     StepStarted,
     StepCompleted,
     StepFailed,
     StepSkipped,
     StepProgress,
+    StepRetrying,
+    BranchSelected,
     ConnectionEstablished,
     Heartbeat,
     // Agent-level progress events
@@ -33,6 +37,17 @@ pub enum FlowEventType {
     ApprovalRequired,
     ApprovalGranted,
     ApprovalRejected,
+    // Human input events
+    InputRequired,
+    InputReceived,
+    // Delay and signal events
+    ExecutionWaiting,
+    SignalWaiting,
+    SignalReceived,
+    // Debugger events
+    ExecutionPaused,
+    VariablesUpdated,
+    BreakpointsUpdated,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -5,4 +5,6 @@ pub mod mcp_connection;
 pub mod mcp_tools;
 pub mod flow;
 pub mod flow_events;
+pub mod schedule;
+pub mod trigger;
 pub mod chat;
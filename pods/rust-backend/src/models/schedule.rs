@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// What a schedule does with the runs it missed while the backend was down
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUpPolicy {
+    /// Drop missed runs and wait for the next one
+    #[default]
+    Skip,
+    /// Start one execution for all the missed runs
+    RunOnce,
+    /// Start an execution for each missed run, up to a limit
+    RunAll,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlowScheduleCreate {
+    pub flow_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Standard 5-field cron expression, a 6/7-field one with seconds, or a
+    /// macro such as `@daily`
+    pub cron: String,
+    /// IANA timezone the cron expression is read in
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    #[serde(default)]
+    pub input_data: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub variables: HashMap<String, serde_json::Value>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub catch_up: CatchUpPolicy,
+    /// Queue priority of the executions; the flow's default when omitted
+    #[serde(default)]
+    pub priority: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlowScheduleUpdate {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub cron: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub input_data: Option<HashMap<String, serde_json::Value>>,
+    #[serde(default)]
+    pub variables: Option<HashMap<String, serde_json::Value>>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub catch_up: Option<CatchUpPolicy>,
+    #[serde(default)]
+    pub priority: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlowScheduleResponse {
+    pub id: String,
+    pub user_id: String,
+    pub flow_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub cron: String,
+    pub timezone: String,
+    #[serde(default)]
+    pub input_data: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub variables: HashMap<String, serde_json::Value>,
+    pub enabled: bool,
+    pub catch_up: CatchUpPolicy,
+    #[serde(default)]
+    pub priority: Option<i32>,
+    /// Next fire time, `None` while disabled
+    #[serde(default)]
+    pub next_run_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_run_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_execution_id: Option<String>,
+    /// Why the last run could not start an execution
+    #[serde(default)]
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub run_count: i64,
+    /// Missed runs dropped by the catch-up policy
+    #[serde(default)]
+    pub skipped_runs: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn default_timezone() -> String { "UTC".to_string() }
+fn default_true() -> bool { true }
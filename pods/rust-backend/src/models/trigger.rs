@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How a webhook request proves it may start the flow
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookAuth {
+    /// Knowing the generated URL is enough
+    #[default]
+    SecretUrl,
+    /// The body must be signed with HMAC-SHA256 using the trigger's secret
+    Hmac,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlowTriggerCreate {
+    pub flow_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub auth: WebhookAuth,
+    /// Header carrying the signature for `hmac` triggers
+    #[serde(default)]
+    pub signature_header: Option<String>,
+    /// Input or variable name to a request path such as `body.issue.number`,
+    /// `headers.x-github-event` or `query.ref`
+    #[serde(default)]
+    pub input_mapping: HashMap<String, String>,
+    /// Hold the response until the execution finishes (`?wait=` overrides)
+    #[serde(default)]
+    pub wait_for_completion: bool,
+    #[serde(default)]
+    pub wait_timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub priority: Option<i32>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlowTriggerUpdate {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub signature_header: Option<String>,
+    #[serde(default)]
+    pub input_mapping: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub wait_for_completion: Option<bool>,
+    #[serde(default)]
+    pub wait_timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub priority: Option<i32>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlowTriggerResponse {
+    pub id: String,
+    pub user_id: String,
+    pub flow_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub auth: WebhookAuth,
+    #[serde(default)]
+    pub signature_header: Option<String>,
+    #[serde(default)]
+    pub input_mapping: HashMap<String, String>,
+    pub wait_for_completion: bool,
+    #[serde(default)]
+    pub wait_timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub priority: Option<i32>,
+    pub enabled: bool,
+    /// Webhook URL path; only returned when the trigger is created or its
+    /// secrets are rotated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// HMAC signing secret; only returned when created or rotated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub delivery_count: i64,
+    #[serde(default)]
+    pub last_delivery_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_execution_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// An execution was started or queued
+    Accepted,
+    /// Refused before reaching the flow (disabled, bad signature, bad body)
+    Rejected,
+    /// The flow refused the execution (invalid inputs, concurrency policy)
+    Failed,
+}
+
+/// Audit record of one request to a webhook trigger
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TriggerDeliveryResponse {
+    pub id: String,
+    pub trigger_id: String,
+    pub status: DeliveryStatus,
+    pub http_status: u16,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub execution_id: Option<String>,
+    /// Headers (without credentials), query and body as received
+    #[serde(default)]
+    pub request: serde_json::Value,
+    /// Values the mapping produced for the flow
+    #[serde(default)]
+    pub mapped_values: HashMap<String, serde_json::Value>,
+    pub received_at: DateTime<Utc>,
+}
+
+fn default_true() -> bool { true }
@@ -17,7 +17,9 @@ use crate::auth::middleware::AuthUser;
 use crate::db::collections::*;
 use crate::error::AppError;
 use crate::models::chat::*;
+use crate::models::flow::{ExecutionTrigger, Flow, FlowExecutionCreate, FlowInput};
 use crate::services::agent_api_client::AgentApiClient;
+use crate::services::{flow_inputs, flow_validator};
 use crate::state::AppState;
 
 pub fn router() -> Router<AppState> {
@@ -68,6 +70,13 @@ fn access_query_by_name(user_id: &str, name: &str) -> bson::Document {
     }
 }
 
+/// A variable whose value is only a `{{name}}` placeholder has no real default
+fn is_placeholder(value: &Value) -> bool {
+    value.as_str().map(str::trim).is_some_and(|s| {
+        (s.starts_with("{{") && s.ends_with("}}")) || (s.starts_with("${") && s.ends_with('}'))
+    })
+}
+
 // ========================
 // Flow endpoints
 // ========================
@@ -172,30 +181,48 @@ async fn execute_flow(
         .and_then(|d| bson::from_document(d.clone()).ok())
         .unwrap_or_default();
 
+    // Values for declared inputs are validated as input; the rest are variables
+    let inputs: HashMap<String, FlowInput> = flow_doc.get_document("inputs")
+        .ok()
+        .and_then(|d| bson::from_document(d.clone()).ok())
+        .unwrap_or_default();
+    let mut input_data = HashMap::new();
+
     let cli_overrides = payload.get("cli_overrides").and_then(|v| v.as_bool()).unwrap_or(false);
     if let Some(cli_vars) = payload.get("variables").and_then(|v| v.as_object()) {
+        let (cli_inputs, cli_vars) = flow_inputs::split_inputs(
+            &inputs,
+            cli_vars.iter().map(|(k, v)| (k.clone(), v.clone())),
+        );
+        input_data = cli_inputs;
+
         if cli_overrides {
             // CLI takes full precedence
-            variables = cli_vars.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            variables = cli_vars;
         } else {
             // Merge: CLI overrides specific keys
-            for (k, v) in cli_vars {
-                variables.insert(k.clone(), v.clone());
-            }
+            variables.extend(cli_vars);
         }
     }
+    for name in inputs.keys() {
+        variables.remove(name);
+    }
 
-    let execution_id = state.flow_service.execute_flow(
+    let priority = payload.get("priority").and_then(|v| v.as_i64()).map(|p| p as i32);
+    let debug = payload.get("debug").and_then(|v| serde_json::from_value(v.clone()).ok());
+    let admitted = state.flow_service.execute_flow(
         &flow_id,
         &auth_user.id,
-        HashMap::new(),
-        variables.clone(),
+        FlowExecutionCreate { flow_id: None, input_data, variables: variables.clone(), priority, debug },
+        ExecutionTrigger::Manual,
     ).await?;
+    let execution_id = admitted.execution_id;
 
     Ok(Json(json!({
         "execution_id": execution_id,
         "flow_name": flow_name,
-        "status": "running",
+        "status": admitted.status,
+        "queue_position": admitted.queue_position,
         "variables": variables,
         "stream_url": format!("/api/executions/{}/stream", execution_id),
     })))
@@ -306,12 +333,22 @@ async fn get_flow_help(
         }
     }
 
-    // Build usage info
-    let var_keys: Vec<String> = variables.keys().cloned().collect();
-    let var_args = var_keys.iter()
-        .map(|k| format!("--{} <value>", k))
-        .collect::<Vec<_>>()
-        .join(" ");
+    // Build usage info from the input schema, falling back to the flow's variables
+    let inputs: HashMap<String, FlowInput> = flow_doc.get_document("inputs")
+        .ok()
+        .and_then(|d| bson::from_document(d.clone()).ok())
+        .unwrap_or_else(|| {
+            variables.iter().map(|(name, value)| {
+                let placeholder = is_placeholder(value);
+                let input = FlowInput {
+                    required: placeholder,
+                    default: (!placeholder).then(|| value.clone()),
+                    ..Default::default()
+                };
+                (name.clone(), input)
+            }).collect()
+        });
+    let (command, example) = flow_inputs::run_usage(&flow_name, &inputs);
 
     Ok(Json(json!({
         "name": flow_name,
@@ -324,11 +361,11 @@ async fn get_flow_help(
         "metadata": flow_doc.get_document("metadata").ok()
             .and_then(|d| bson::from_document::<Value>(d.clone()).ok())
             .unwrap_or(json!({})),
+        "inputs": flow_inputs::describe_inputs(&inputs),
         "usage": {
-            "command": format!("pod run {} {}", flow_name, var_args),
-            "examples": [
-                format!("pod run {}", flow_name),
-            ],
+            "command": command,
+            "example": example,
+            "examples": [example],
         },
     })))
 }
@@ -426,6 +463,9 @@ async fn get_flow_full(
         "description": flow_doc.get_str("description").ok(),
         "is_active": flow_doc.get_bool("is_active").unwrap_or(true),
         "variables": variables,
+        "inputs": flow_doc.get_document("inputs").ok()
+            .and_then(|d| bson::from_document::<Value>(d.clone()).ok())
+            .unwrap_or(json!({})),
         "agents": agents_info,
         "steps": steps_info,
         "start_step": flow_doc.get_str("start_step_id").ok(),
@@ -470,6 +510,8 @@ struct FlowImportCLI {
     is_active: bool,
     #[serde(default)]
     variables: HashMap<String, Value>,
+    #[serde(default)]
+    inputs: HashMap<String, FlowInput>,
     steps: Vec<FlowStepCLI>,
     start_step: String,
     #[serde(default)]
@@ -480,6 +522,38 @@ struct FlowImportCLI {
 
 fn default_true_val() -> bool { true }
 
+impl FlowImportCLI {
+    /// The flow as the executor will load it, for validation
+    fn to_flow(&self, user_id: &str) -> Result<Flow, AppError> {
+        let steps: Vec<Value> = self.steps.iter().map(|s| json!({
+            "id": s.id,
+            "name": s.name,
+            "agent_id": s.agent_id,
+            "description": s.description,
+            "type": s.step_type,
+            "parameters": s.parameters,
+            "next_steps": s.next_steps,
+            "timeout_seconds": s.timeout_seconds,
+            "retry_count": s.retry_count,
+            "condition": s.condition,
+            "agent_overrides": s.agent_overrides,
+        })).collect();
+
+        serde_json::from_value(json!({
+            "user_id": user_id,
+            "name": self.name,
+            "description": self.description,
+            "steps": steps,
+            "start_step_id": self.start_step,
+            "variables": self.variables,
+            "inputs": self.inputs,
+            "metadata": self.metadata,
+            "edge_metadata": self.edge_metadata,
+            "is_active": self.is_active,
+        })).map_err(|e| AppError::BadRequest(format!("Invalid flow definition: {}", e)))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct OverwriteQuery {
     #[serde(default)]
@@ -498,13 +572,8 @@ async fn create_flow(
     // Check if flow with same name exists
     let existing = collection.find_one(doc! { "name": &payload.name, "user_id": &auth_user.id }).await?;
 
-    // Validate start_step exists
-    let step_ids: Vec<&str> = payload.steps.iter().map(|s| s.id.as_str()).collect();
-    if !step_ids.contains(&payload.start_step.as_str()) {
-        return Err(AppError::BadRequest(format!(
-            "start_step '{}' not found in steps", payload.start_step
-        )));
-    }
+    let flow = payload.to_flow(&auth_user.id)?;
+    flow_validator::validate_flow_in_db(&db, &flow).await?.into_result()?;
 
     // Convert steps to BSON
     let steps_bson: Vec<bson::Bson> = payload.steps.iter().map(|s| {
@@ -546,6 +615,7 @@ async fn create_flow(
                 "description": payload.description.as_deref(),
                 "is_active": payload.is_active,
                 "variables": bson::to_bson(&payload.variables).unwrap_or(bson::Bson::Document(doc!{})),
+                "inputs": bson::to_bson(&payload.inputs).unwrap_or(bson::Bson::Document(doc!{})),
                 "steps": &steps_bson,
                 "start_step_id": &payload.start_step,
                 "metadata": bson::to_bson(&payload.metadata).unwrap_or(bson::Bson::Document(doc!{})),
@@ -568,6 +638,7 @@ async fn create_flow(
             "description": payload.description.as_deref(),
             "is_active": payload.is_active,
             "variables": bson::to_bson(&payload.variables).unwrap_or(bson::Bson::Document(doc!{})),
+            "inputs": bson::to_bson(&payload.inputs).unwrap_or(bson::Bson::Document(doc!{})),
             "steps": steps_bson,
             "start_step_id": &payload.start_step,
             "metadata": bson::to_bson(&payload.metadata).unwrap_or(bson::Bson::Document(doc!{})),
@@ -1101,6 +1172,7 @@ async fn send_message(
             &agent_id,
             "chat",
             &params,
+            None,
             Some(conversation),
             None,
         ).await;
@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, Query, State},
     response::sse::{Event, KeepAlive, Sse},
-    routing::{get, post},
+    routing::{get, post, put},
     Json, Router,
 };
 use bson::oid::ObjectId;
@@ -17,6 +17,7 @@ use crate::db::collections::*;
 use crate::error::AppError;
 use crate::models::flow::*;
 use crate::models::flow_events::*;
+use crate::services::flow_executor::debugger::ExecutionVariables;
 use crate::state::AppState;
 
 pub fn router() -> Router<AppState> {
@@ -25,6 +26,13 @@ pub fn router() -> Router<AppState> {
         .route("/{execution_id}", get(get_execution))
         .route("/{execution_id}/cancel", post(cancel_execution))
         .route("/{execution_id}/approve", post(approve_execution))
+        .route("/{execution_id}/input", post(submit_execution_input))
+        .route("/{execution_id}/rerun", post(rerun_execution))
+        .route("/{execution_id}/pause", post(pause_execution))
+        .route("/{execution_id}/resume", post(resume_execution))
+        .route("/{execution_id}/step", post(step_execution))
+        .route("/{execution_id}/variables", get(get_execution_variables).patch(update_execution_variables))
+        .route("/{execution_id}/breakpoints", put(set_execution_breakpoints))
         .route("/{execution_id}/events", get(list_execution_events))
         .route("/{execution_id}/stream", get(stream_execution))
 }
@@ -37,6 +45,15 @@ struct ListQuery {
     per_page: i64,
     #[serde(default)]
     flow_id: Option<String>,
+    /// List the sub-flow executions started by this execution
+    #[serde(default)]
+    parent_execution_id: Option<String>,
+    /// List the executions started by this schedule
+    #[serde(default)]
+    schedule_id: Option<String>,
+    /// List the executions started by this webhook trigger
+    #[serde(default)]
+    trigger_id: Option<String>,
 }
 
 fn default_page() -> i64 { 1 }
@@ -54,6 +71,15 @@ async fn list_executions(
     if let Some(ref fid) = params.flow_id {
         query.insert("flow_id", fid);
     }
+    if let Some(ref parent_id) = params.parent_execution_id {
+        query.insert("parent_execution_id", parent_id);
+    }
+    if let Some(ref schedule_id) = params.schedule_id {
+        query.insert("trigger.schedule_id", schedule_id);
+    }
+    if let Some(ref trigger_id) = params.trigger_id {
+        query.insert("trigger.trigger_id", trigger_id);
+    }
 
     // Count total
     let total = collection.count_documents(query.clone()).await
@@ -72,7 +98,8 @@ async fn list_executions(
 
     while cursor.advance().await? {
         let doc = cursor.deserialize_current()?;
-        if let Ok(resp) = doc_to_execution_response(&doc) {
+        if let Ok(mut resp) = doc_to_execution_response(&doc) {
+            resp.queue_position = state.flow_service.queue_position(&doc).await?;
             executions.push(resp);
         }
     }
@@ -99,7 +126,9 @@ async fn get_execution(
         .await?
         .ok_or_else(|| AppError::NotFound("Execution not found".to_string()))?;
 
-    Ok(Json(doc_to_execution_response(&doc)?))
+    let mut response = doc_to_execution_response(&doc)?;
+    response.queue_position = state.flow_service.queue_position(&doc).await?;
+    Ok(Json(response))
 }
 
 async fn cancel_execution(
@@ -115,25 +144,154 @@ async fn cancel_execution(
     })))
 }
 
-#[derive(Debug, Deserialize)]
-struct ApprovalPayload {
-    #[serde(default = "default_true")]
-    approved: bool,
+/// Record the caller's decision on the approval step the execution is waiting on
+async fn approve_execution(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(execution_id): Path<String>,
+    Json(payload): Json<ApprovalSubmission>,
+) -> Result<Json<Value>, AppError> {
+    let approved = payload.approved;
+    let request = state.flow_service.submit_approval(&execution_id, &auth_user, payload).await?;
+
+    Ok(Json(json!({
+        "message": if approved { "Approval granted" } else { "Approval rejected" },
+        "execution_id": execution_id,
+        "step_id": request.step_id,
+        "approved": approved,
+        "approvals": request.decisions.iter().filter(|d| d.approved).count(),
+        "required_approvals": request.required_approvals,
+        "decided": request.outcome(),
+    })))
 }
-fn default_true() -> bool { true }
 
-async fn approve_execution(
+/// Answer a human input step the execution is waiting on
+async fn submit_execution_input(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(execution_id): Path<String>,
+    Json(payload): Json<HumanInputSubmission>,
+) -> Result<Json<Value>, AppError> {
+    let (step_id, values) = state.flow_service.submit_input(&execution_id, &auth_user.id, payload).await?;
+
+    Ok(Json(json!({
+        "message": "Input received",
+        "execution_id": execution_id,
+        "step_id": step_id,
+        "values": values,
+    })))
+}
+
+/// Pause a running execution before its next step
+async fn pause_execution(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(execution_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    state.flow_service.pause_execution(&execution_id, &auth_user.id).await?;
+
+    Ok(Json(json!({
+        "message": "Pause requested; the execution pauses before its next step",
+        "execution_id": execution_id,
+    })))
+}
+
+/// Continue a paused execution up to the next breakpoint
+async fn resume_execution(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(execution_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let paused = state.flow_service.resume_execution(&execution_id, &auth_user.id, false).await?;
+
+    Ok(Json(json!({
+        "message": "Execution resumed",
+        "execution_id": execution_id,
+        "step_id": paused.step_id,
+    })))
+}
+
+/// Run the step a paused execution stopped before, then pause again
+async fn step_execution(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(execution_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let paused = state.flow_service.resume_execution(&execution_id, &auth_user.id, true).await?;
+
+    Ok(Json(json!({
+        "message": "Running one step",
+        "execution_id": execution_id,
+        "step_id": paused.step_id,
+    })))
+}
+
+/// Variables the execution's next step will see
+async fn get_execution_variables(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(execution_id): Path<String>,
+) -> Result<Json<ExecutionVariables>, AppError> {
+    Ok(Json(state.flow_service.execution_variables(&execution_id, &auth_user.id).await?))
+}
+
+/// Set or remove variables of a paused execution
+async fn update_execution_variables(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(execution_id): Path<String>,
+    Json(payload): Json<VariablesUpdate>,
+) -> Result<Json<ExecutionVariables>, AppError> {
+    Ok(Json(state.flow_service.update_execution_variables(&execution_id, &auth_user.id, payload).await?))
+}
+
+/// Replace the breakpoints of an execution
+async fn set_execution_breakpoints(
     State(state): State<AppState>,
     auth_user: AuthUser,
     Path(execution_id): Path<String>,
-    Json(payload): Json<ApprovalPayload>,
+    Json(payload): Json<DebugOptions>,
 ) -> Result<Json<Value>, AppError> {
-    state.flow_service.submit_approval(&execution_id, &auth_user.id, payload.approved).await?;
+    let debug = state.flow_service.set_breakpoints(&execution_id, &auth_user.id, payload).await?;
 
     Ok(Json(json!({
-        "message": if payload.approved { "Approval granted" } else { "Approval rejected" },
         "execution_id": execution_id,
-        "approved": payload.approved,
+        "breakpoints": debug.breakpoints,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+struct RerunQuery {
+    /// Step to start from; the flow's start step when omitted
+    #[serde(default)]
+    from_step: Option<String>,
+}
+
+/// Start a new execution from a step of an earlier one, with the state it had then
+async fn rerun_execution(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(execution_id): Path<String>,
+    Query(params): Query<RerunQuery>,
+    payload: Option<Json<FlowExecutionRerun>>,
+) -> Result<Json<Value>, AppError> {
+    let Json(edits) = payload.unwrap_or_default();
+    let (admitted, from_step) = state.flow_service.rerun_execution(
+        &execution_id,
+        &auth_user.id,
+        params.from_step.as_deref(),
+        edits,
+    ).await?;
+    let rerun_id = admitted.execution_id;
+
+    Ok(Json(json!({
+        "id": rerun_id,
+        "execution_id": rerun_id,
+        "rerun_of_execution_id": execution_id,
+        "from_step": from_step,
+        "status": admitted.status,
+        "queue_position": admitted.queue_position,
+        "stream_url": format!("/api/executions/{}/stream", rerun_id),
     })))
 }
 
@@ -338,6 +496,11 @@ fn doc_to_execution_response(doc: &bson::Document) -> Result<FlowExecutionRespon
         .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
         .unwrap_or_default();
 
+    let child_execution_ids: Vec<String> = doc.get_array("child_execution_ids")
+        .ok()
+        .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+        .unwrap_or_default();
+
     Ok(FlowExecutionResponse {
         id: doc.get_object_id("_id").map(|id| id.to_hex()).unwrap_or_default(),
         flow_id: doc.get_str("flow_id").unwrap_or("").to_string(),
@@ -353,6 +516,32 @@ fn doc_to_execution_response(doc: &bson::Document) -> Result<FlowExecutionRespon
         start_time: doc.get_datetime("start_time").ok().map(|d| d.to_chrono()),
         end_time: doc.get_datetime("end_time").ok().map(|d| d.to_chrono()),
         execution_time_ms: doc.get_i64("execution_time_ms").ok(),
+        parent_execution_id: doc.get_str("parent_execution_id").ok().map(String::from),
+        parent_step_id: doc.get_str("parent_step_id").ok().map(String::from),
+        depth: doc.get_i32("depth").unwrap_or(0),
+        child_execution_ids,
+        rerun_of_execution_id: doc.get_str("rerun_of_execution_id").ok().map(String::from),
+        rerun_from_step_id: doc.get_str("rerun_from_step_id").ok().map(String::from),
+        priority: doc.get_i32("priority").unwrap_or(0),
+        queue_position: None,
+        trigger: doc.get("trigger").and_then(|t| bson::from_bson(t.clone()).ok()),
+        pending_inputs: doc.get_document("pending_inputs").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default(),
+        pending_approvals: doc.get_document("pending_approvals").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default(),
+        approval_history: doc.get_array("approval_history").ok()
+            .map(|arr| arr.iter().filter_map(|d| bson::from_bson(d.clone()).ok()).collect())
+            .unwrap_or_default(),
+        debug: doc.get_document("debug").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok()),
+        paused: doc.get_document("paused").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok()),
+        wake_at: doc.get_datetime("wake_at").ok().map(|d| d.to_chrono()),
+        signal_waits: doc.get_array("signal_waits").ok()
+            .map(|arr| arr.iter().filter_map(|d| bson::from_bson(d.clone()).ok()).collect())
+            .unwrap_or_default(),
         created_at: doc.get_datetime("created_at").map(|d| d.to_chrono()).unwrap_or_else(|_| Utc::now()),
         updated_at: doc.get_datetime("updated_at").map(|d| d.to_chrono()).unwrap_or_else(|_| Utc::now()),
     })
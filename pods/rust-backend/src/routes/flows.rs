@@ -12,11 +12,13 @@ use crate::auth::middleware::AuthUser;
 use crate::db::collections::{DB_NAME, FLOWS};
 use crate::error::AppError;
 use crate::models::flow::*;
+use crate::services::flow_validator::{self, ValidationReport};
 use crate::state::AppState;
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(get_flows).post(create_flow))
+        .route("/validate", post(validate_flow))
         .route("/{flow_id}", get(get_flow).put(update_flow).delete(delete_flow))
         .route("/{flow_id}/execute", post(execute_flow))
 }
@@ -68,20 +70,8 @@ async fn create_flow(
 
     let _now = bson::DateTime::from_chrono(Utc::now());
 
-    let flow = Flow {
-        id: None,
-        user_id: auth_user.id.clone(),
-        name: payload.name,
-        description: payload.description,
-        steps: payload.steps,
-        start_step_id: payload.start_step_id,
-        variables: payload.variables,
-        metadata: payload.metadata,
-        edge_metadata: payload.edge_metadata,
-        is_active: true,
-        created_at: Some(Utc::now()),
-        updated_at: Some(Utc::now()),
-    };
+    let flow = draft_flow(payload, &auth_user.id);
+    flow_validator::validate_flow_in_db(&db, &flow).await?.into_result()?;
 
     let flow_bson = bson::to_document(&flow)
         .map_err(|e| AppError::Internal(format!("Serialization error: {}", e)))?;
@@ -109,11 +99,25 @@ async fn update_flow(
     let collection = db.collection::<bson::Document>(FLOWS);
 
     // Verify ownership
-    collection
+    let existing = collection
         .find_one(doc! { "_id": oid, "user_id": &auth_user.id })
         .await?
         .ok_or_else(|| AppError::NotFound("Flow not found".to_string()))?;
 
+    // Validate the flow as it will be after the update
+    let current = doc_to_flow_response(&existing)?;
+    let merged = draft_flow(FlowCreate {
+        name: current.name,
+        description: current.description,
+        steps: payload.steps.clone().unwrap_or(current.steps),
+        start_step_id: payload.start_step_id.clone().unwrap_or(current.start_step_id),
+        variables: payload.variables.clone().unwrap_or(current.variables),
+        inputs: payload.inputs.clone().unwrap_or(current.inputs),
+        metadata: payload.metadata.clone().unwrap_or(current.metadata),
+        edge_metadata: payload.edge_metadata.clone().unwrap_or(current.edge_metadata),
+    }, &auth_user.id);
+    flow_validator::validate_flow_in_db(&db, &merged).await?.into_result()?;
+
     let mut update_doc = doc! { "updated_at": bson::DateTime::from_chrono(Utc::now()) };
 
     if let Some(ref name) = payload.name { update_doc.insert("name", name); }
@@ -127,6 +131,10 @@ async fn update_flow(
         let vars_bson = bson::to_bson(vars).map_err(|e| AppError::Internal(e.to_string()))?;
         update_doc.insert("variables", vars_bson);
     }
+    if let Some(ref inputs) = payload.inputs {
+        let inputs_bson = bson::to_bson(inputs).map_err(|e| AppError::Internal(e.to_string()))?;
+        update_doc.insert("inputs", inputs_bson);
+    }
     if let Some(ref meta) = payload.metadata {
         let meta_bson = bson::to_bson(meta).map_err(|e| AppError::Internal(e.to_string()))?;
         update_doc.insert("metadata", meta_bson);
@@ -147,6 +155,36 @@ async fn update_flow(
     Ok(Json(doc_to_flow_response(&updated)?))
 }
 
+/// Lint a flow definition without saving it. Accepts the same body as create
+/// (or the CLI format with `start_step`) and always returns the full report.
+async fn validate_flow(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<FlowCreate>,
+) -> Result<Json<ValidationReport>, AppError> {
+    let db = state.mongo_client.database(DB_NAME);
+    let flow = draft_flow(payload, &auth_user.id);
+    Ok(Json(flow_validator::validate_flow_in_db(&db, &flow).await?))
+}
+
+fn draft_flow(payload: FlowCreate, user_id: &str) -> Flow {
+    Flow {
+        id: None,
+        user_id: user_id.to_string(),
+        name: payload.name,
+        description: payload.description,
+        steps: payload.steps,
+        start_step_id: payload.start_step_id,
+        variables: payload.variables,
+        inputs: payload.inputs,
+        metadata: payload.metadata,
+        edge_metadata: payload.edge_metadata,
+        is_active: true,
+        created_at: Some(Utc::now()),
+        updated_at: Some(Utc::now()),
+    }
+}
+
 async fn delete_flow(
     State(state): State<AppState>,
     auth_user: AuthUser,
@@ -173,18 +211,20 @@ async fn execute_flow(
     Path(flow_id): Path<String>,
     Json(payload): Json<FlowExecutionCreate>,
 ) -> Result<Json<Value>, AppError> {
-    let execution_id = state.flow_service.execute_flow(
+    let admitted = state.flow_service.execute_flow(
         &flow_id,
         &auth_user.id,
-        payload.input_data,
-        payload.variables,
+        payload,
+        ExecutionTrigger::Manual,
     ).await?;
+    let execution_id = admitted.execution_id;
 
     Ok(Json(json!({
         "id": execution_id,
         "execution_id": execution_id,
         "flow_id": flow_id,
-        "status": "running",
+        "status": admitted.status,
+        "queue_position": admitted.queue_position,
         "stream_url": format!("/api/executions/{}/stream", execution_id),
     })))
 }
@@ -214,6 +254,7 @@ fn doc_to_flow_response(doc: &bson::Document) -> Result<FlowResponse, AppError>
         steps: flow.steps,
         start_step_id: flow.start_step_id,
         variables: flow.variables,
+        inputs: flow.inputs,
         metadata: flow.metadata,
         edge_metadata: flow.edge_metadata,
         is_active: flow.is_active,
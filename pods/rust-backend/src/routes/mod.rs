@@ -4,6 +4,9 @@ pub mod llms;
 pub mod mcp;
 pub mod flows;
 pub mod executions;
+pub mod schedules;
+pub mod triggers;
+pub mod signals;
 pub mod cli;
 pub mod status;
 pub mod health;
@@ -25,6 +28,10 @@ pub fn create_router() -> Router<AppState> {
         .nest("/api/mcp-server-connections", mcp::router())
         .nest("/api/flows", flows::router())
         .nest("/api/executions", executions::router())
+        .nest("/api/schedules", schedules::router())
+        .nest("/api/triggers", triggers::router())
+        .nest("/api/hooks", triggers::hooks_router())
+        .nest("/api/signals", signals::router())
         .nest("/api/cli", cli::router())
         .nest("/status", status::router())
         .nest("/api/mcp", mcp_client::router())
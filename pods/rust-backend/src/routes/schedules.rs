@@ -0,0 +1,223 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::bson::doc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::auth::middleware::AuthUser;
+use crate::db::collections::{DB_NAME, FLOW_SCHEDULES};
+use crate::error::AppError;
+use crate::models::schedule::*;
+use crate::services::flow_inputs;
+use crate::services::flow_scheduler::CronSchedule;
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_schedules).post(create_schedule))
+        .route("/{schedule_id}", get(get_schedule).put(update_schedule).delete(delete_schedule))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    flow_id: Option<String>,
+}
+
+async fn list_schedules(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Query(params): Query<ListQuery>,
+) -> Result<Json<Vec<FlowScheduleResponse>>, AppError> {
+    let db = state.mongo_client.database(DB_NAME);
+    let collection = db.collection::<bson::Document>(FLOW_SCHEDULES);
+
+    let mut query = doc! { "user_id": &auth_user.id };
+    if let Some(ref flow_id) = params.flow_id {
+        query.insert("flow_id", flow_id);
+    }
+
+    let mut cursor = collection.find(query).sort(doc! { "created_at": -1 }).await?;
+    let mut schedules = Vec::new();
+    while cursor.advance().await? {
+        let doc = cursor.deserialize_current()?;
+        schedules.push(doc_to_schedule_response(&doc));
+    }
+
+    Ok(Json(schedules))
+}
+
+async fn get_schedule(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(schedule_id): Path<String>,
+) -> Result<Json<FlowScheduleResponse>, AppError> {
+    let oid = ObjectId::parse_str(&schedule_id)?;
+    let db = state.mongo_client.database(DB_NAME);
+    let collection = db.collection::<bson::Document>(FLOW_SCHEDULES);
+
+    let doc = collection
+        .find_one(doc! { "_id": oid, "user_id": &auth_user.id })
+        .await?
+        .ok_or_else(|| AppError::NotFound("Schedule not found".to_string()))?;
+
+    Ok(Json(doc_to_schedule_response(&doc)))
+}
+
+async fn create_schedule(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<FlowScheduleCreate>,
+) -> Result<Json<FlowScheduleResponse>, AppError> {
+    let db = state.mongo_client.database(DB_NAME);
+    let collection = db.collection::<bson::Document>(FLOW_SCHEDULES);
+
+    let flow = state.flow_service.load_flow(&payload.flow_id, &auth_user.id).await?;
+    flow_inputs::validate_inputs(&flow.inputs, &payload.input_data).map_err(AppError::InvalidInput)?;
+    let cron = CronSchedule::parse(&payload.cron, &payload.timezone).map_err(AppError::BadRequest)?;
+
+    let now = Utc::now();
+    let next_run_at = payload.enabled.then(|| cron.next_after(now)).flatten();
+    let schedule_doc = doc! {
+        "user_id": &auth_user.id,
+        "flow_id": &payload.flow_id,
+        "name": payload.name.as_deref(),
+        "cron": payload.cron.trim(),
+        "timezone": payload.timezone.trim(),
+        "input_data": to_bson(&payload.input_data)?,
+        "variables": to_bson(&payload.variables)?,
+        "enabled": payload.enabled,
+        "catch_up": to_bson(&payload.catch_up)?,
+        "priority": payload.priority,
+        "next_run_at": next_run_at.map(bson::DateTime::from_chrono),
+        "run_count": 0_i64,
+        "skipped_runs": 0_i64,
+        "created_at": bson::DateTime::from_chrono(now),
+        "updated_at": bson::DateTime::from_chrono(now),
+    };
+
+    let result = collection.insert_one(schedule_doc).await?;
+    let inserted_id = result.inserted_id.as_object_id()
+        .ok_or_else(|| AppError::Internal("Failed to get inserted ID".to_string()))?;
+
+    let created = collection
+        .find_one(doc! { "_id": inserted_id })
+        .await?
+        .ok_or_else(|| AppError::Internal("Failed to retrieve created schedule".to_string()))?;
+
+    Ok(Json(doc_to_schedule_response(&created)))
+}
+
+async fn update_schedule(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(schedule_id): Path<String>,
+    Json(payload): Json<FlowScheduleUpdate>,
+) -> Result<Json<FlowScheduleResponse>, AppError> {
+    let oid = ObjectId::parse_str(&schedule_id)?;
+    let db = state.mongo_client.database(DB_NAME);
+    let collection = db.collection::<bson::Document>(FLOW_SCHEDULES);
+
+    let existing = collection
+        .find_one(doc! { "_id": oid, "user_id": &auth_user.id })
+        .await?
+        .ok_or_else(|| AppError::NotFound("Schedule not found".to_string()))?;
+    let current = doc_to_schedule_response(&existing);
+
+    let now = Utc::now();
+    let mut update_doc = doc! { "updated_at": bson::DateTime::from_chrono(now) };
+
+    if let Some(ref name) = payload.name { update_doc.insert("name", name); }
+    if let Some(ref input_data) = payload.input_data {
+        let flow = state.flow_service.load_flow(&current.flow_id, &auth_user.id).await?;
+        flow_inputs::validate_inputs(&flow.inputs, input_data).map_err(AppError::InvalidInput)?;
+        update_doc.insert("input_data", to_bson(input_data)?);
+    }
+    if let Some(ref variables) = payload.variables { update_doc.insert("variables", to_bson(variables)?); }
+    if let Some(catch_up) = payload.catch_up { update_doc.insert("catch_up", to_bson(&catch_up)?); }
+    if let Some(priority) = payload.priority { update_doc.insert("priority", priority); }
+
+    // A new timing or re-enabling starts counting from now, without catching
+    // up on runs that fell in between
+    if payload.cron.is_some() || payload.timezone.is_some() || payload.enabled.is_some() {
+        let expression = payload.cron.unwrap_or(current.cron);
+        let timezone = payload.timezone.unwrap_or(current.timezone);
+        let enabled = payload.enabled.unwrap_or(current.enabled);
+        let cron = CronSchedule::parse(&expression, &timezone).map_err(AppError::BadRequest)?;
+        let next_run_at = enabled.then(|| cron.next_after(now)).flatten();
+
+        update_doc.insert("cron", expression.trim());
+        update_doc.insert("timezone", timezone.trim());
+        update_doc.insert("enabled", enabled);
+        update_doc.insert("next_run_at", next_run_at.map(bson::DateTime::from_chrono));
+    }
+
+    collection.update_one(doc! { "_id": oid }, doc! { "$set": update_doc }).await?;
+
+    let updated = collection
+        .find_one(doc! { "_id": oid })
+        .await?
+        .ok_or_else(|| AppError::NotFound("Schedule not found after update".to_string()))?;
+
+    Ok(Json(doc_to_schedule_response(&updated)))
+}
+
+async fn delete_schedule(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(schedule_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let oid = ObjectId::parse_str(&schedule_id)?;
+    let db = state.mongo_client.database(DB_NAME);
+    let collection = db.collection::<bson::Document>(FLOW_SCHEDULES);
+
+    let result = collection
+        .delete_one(doc! { "_id": oid, "user_id": &auth_user.id })
+        .await?;
+
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound("Schedule not found".to_string()));
+    }
+
+    Ok(Json(json!({ "message": "Schedule deleted successfully" })))
+}
+
+fn to_bson<T: serde::Serialize>(value: &T) -> Result<bson::Bson, AppError> {
+    bson::to_bson(value).map_err(|e| AppError::Internal(format!("Serialization error: {}", e)))
+}
+
+fn doc_to_schedule_response(doc: &bson::Document) -> FlowScheduleResponse {
+    let map = |key: &str| -> HashMap<String, Value> {
+        doc.get_document(key).ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default()
+    };
+
+    FlowScheduleResponse {
+        id: doc.get_object_id("_id").map(|id| id.to_hex()).unwrap_or_default(),
+        user_id: doc.get_str("user_id").unwrap_or("").to_string(),
+        flow_id: doc.get_str("flow_id").unwrap_or("").to_string(),
+        name: doc.get_str("name").ok().map(String::from),
+        cron: doc.get_str("cron").unwrap_or("").to_string(),
+        timezone: doc.get_str("timezone").unwrap_or("UTC").to_string(),
+        input_data: map("input_data"),
+        variables: map("variables"),
+        enabled: doc.get_bool("enabled").unwrap_or(false),
+        catch_up: doc.get("catch_up").and_then(|c| bson::from_bson(c.clone()).ok()).unwrap_or_default(),
+        priority: doc.get_i32("priority").ok(),
+        next_run_at: doc.get_datetime("next_run_at").ok().map(|d| d.to_chrono()),
+        last_run_at: doc.get_datetime("last_run_at").ok().map(|d| d.to_chrono()),
+        last_execution_id: doc.get_str("last_execution_id").ok().map(String::from),
+        last_error: doc.get_str("last_error").ok().map(String::from),
+        run_count: doc.get_i64("run_count").unwrap_or(0),
+        skipped_runs: doc.get_i64("skipped_runs").unwrap_or(0),
+        created_at: doc.get_datetime("created_at").map(|d| d.to_chrono()).unwrap_or_else(|_| Utc::now()),
+        updated_at: doc.get_datetime("updated_at").map(|d| d.to_chrono()).unwrap_or_else(|_| Utc::now()),
+    }
+}
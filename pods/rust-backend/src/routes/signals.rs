@@ -0,0 +1,36 @@
+use axum::{
+    extract::{Path, State},
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+use crate::auth::middleware::AuthUser;
+use crate::error::AppError;
+use crate::models::flow::SignalDelivery;
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/{signal}", post(send_signal))
+}
+
+/// Resume the caller's wait-for-signal steps waiting for this signal and correlation ID
+async fn send_signal(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(signal): Path<String>,
+    Json(payload): Json<SignalDelivery>,
+) -> Result<Json<Value>, AppError> {
+    let correlation_id = payload.correlation_id.clone();
+    let delivered = state.flow_service.deliver_signal(&auth_user.id, &signal, payload).await?;
+
+    Ok(Json(json!({
+        "message": "Signal delivered",
+        "signal": signal,
+        "correlation_id": correlation_id,
+        "delivered": delivered.iter()
+            .map(|(execution_id, step_id)| json!({ "execution_id": execution_id, "step_id": step_id }))
+            .collect::<Vec<_>>(),
+    })))
+}
@@ -0,0 +1,466 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::bson::doc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::auth::encryption::{decrypt_api_key, encrypt_api_key};
+use crate::auth::middleware::AuthUser;
+use crate::db::collections::{DB_NAME, FLOW_TRIGGERS, TRIGGER_DELIVERIES};
+use crate::error::AppError;
+use crate::models::flow::{ExecutionTrigger, FlowExecutionCreate, FlowStepResult, FlowStepStatus};
+use crate::models::trigger::*;
+use crate::services::flow_inputs;
+use crate::services::webhook_triggers::{
+    generate_token, hash_token, map_request, request_value, verify_signature, DEFAULT_SIGNATURE_HEADER,
+    MAX_RECORDED_BODY_BYTES,
+};
+use crate::state::AppState;
+
+/// How long a webhook waits for its execution when asked to, by default and at most
+const DEFAULT_WAIT_SECONDS: u64 = 30;
+const MAX_WAIT_SECONDS: u64 = 300;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_triggers).post(create_trigger))
+        .route("/{trigger_id}", get(get_trigger).put(update_trigger).delete(delete_trigger))
+        .route("/{trigger_id}/rotate", post(rotate_trigger_secrets))
+        .route("/{trigger_id}/deliveries", get(list_deliveries))
+}
+
+/// Public webhook endpoint; requests authenticate with the URL token and,
+/// for `hmac` triggers, a body signature
+pub fn hooks_router() -> Router<AppState> {
+    Router::new().route("/{token}", post(receive_webhook))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    flow_id: Option<String>,
+}
+
+async fn list_triggers(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Query(params): Query<ListQuery>,
+) -> Result<Json<Vec<FlowTriggerResponse>>, AppError> {
+    let db = state.mongo_client.database(DB_NAME);
+    let collection = db.collection::<bson::Document>(FLOW_TRIGGERS);
+
+    let mut query = doc! { "user_id": &auth_user.id };
+    if let Some(ref flow_id) = params.flow_id {
+        query.insert("flow_id", flow_id);
+    }
+
+    let mut cursor = collection.find(query).sort(doc! { "created_at": -1 }).await?;
+    let mut triggers = Vec::new();
+    while cursor.advance().await? {
+        let doc = cursor.deserialize_current()?;
+        triggers.push(doc_to_trigger_response(&doc));
+    }
+
+    Ok(Json(triggers))
+}
+
+async fn get_trigger(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(trigger_id): Path<String>,
+) -> Result<Json<FlowTriggerResponse>, AppError> {
+    let doc = find_trigger(&state, &trigger_id, &auth_user.id).await?;
+    Ok(Json(doc_to_trigger_response(&doc)))
+}
+
+async fn create_trigger(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<FlowTriggerCreate>,
+) -> Result<Json<FlowTriggerResponse>, AppError> {
+    let db = state.mongo_client.database(DB_NAME);
+    let collection = db.collection::<bson::Document>(FLOW_TRIGGERS);
+
+    state.flow_service.load_flow(&payload.flow_id, &auth_user.id).await?;
+
+    let token = generate_token();
+    let secret = (payload.auth == WebhookAuth::Hmac).then(generate_token);
+    let now = bson::DateTime::from_chrono(Utc::now());
+    let trigger_doc = doc! {
+        "user_id": &auth_user.id,
+        "flow_id": &payload.flow_id,
+        "name": payload.name.as_deref(),
+        "auth": to_bson(&payload.auth)?,
+        "signature_header": payload.signature_header.as_deref().map(str::to_lowercase),
+        "input_mapping": to_bson(&payload.input_mapping)?,
+        "wait_for_completion": payload.wait_for_completion,
+        "wait_timeout_seconds": payload.wait_timeout_seconds.map(|s| s as i64),
+        "priority": payload.priority,
+        "enabled": payload.enabled,
+        "token_hash": hash_token(&token),
+        "secret": secret.as_deref().map(|s| encrypt_api_key(&state.config.fernet_key, s)).transpose()?,
+        "delivery_count": 0_i64,
+        "created_at": now,
+        "updated_at": now,
+    };
+
+    let result = collection.insert_one(trigger_doc).await?;
+    let inserted_id = result.inserted_id.as_object_id()
+        .ok_or_else(|| AppError::Internal("Failed to get inserted ID".to_string()))?;
+
+    let created = collection
+        .find_one(doc! { "_id": inserted_id })
+        .await?
+        .ok_or_else(|| AppError::Internal("Failed to retrieve created trigger".to_string()))?;
+
+    let mut response = doc_to_trigger_response(&created);
+    response.url = Some(hook_url(&token));
+    response.secret = secret;
+    Ok(Json(response))
+}
+
+async fn update_trigger(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(trigger_id): Path<String>,
+    Json(payload): Json<FlowTriggerUpdate>,
+) -> Result<Json<FlowTriggerResponse>, AppError> {
+    let existing = find_trigger(&state, &trigger_id, &auth_user.id).await?;
+    let oid = existing.get_object_id("_id").map_err(|e| AppError::Internal(e.to_string()))?;
+    let collection = state.mongo_client.database(DB_NAME).collection::<bson::Document>(FLOW_TRIGGERS);
+
+    let mut update_doc = doc! { "updated_at": bson::DateTime::from_chrono(Utc::now()) };
+    if let Some(ref name) = payload.name { update_doc.insert("name", name); }
+    if let Some(ref header) = payload.signature_header { update_doc.insert("signature_header", header.to_lowercase()); }
+    if let Some(ref mapping) = payload.input_mapping { update_doc.insert("input_mapping", to_bson(mapping)?); }
+    if let Some(wait) = payload.wait_for_completion { update_doc.insert("wait_for_completion", wait); }
+    if let Some(timeout) = payload.wait_timeout_seconds { update_doc.insert("wait_timeout_seconds", timeout as i64); }
+    if let Some(priority) = payload.priority { update_doc.insert("priority", priority); }
+    if let Some(enabled) = payload.enabled { update_doc.insert("enabled", enabled); }
+
+    collection.update_one(doc! { "_id": oid }, doc! { "$set": update_doc }).await?;
+
+    let updated = collection
+        .find_one(doc! { "_id": oid })
+        .await?
+        .ok_or_else(|| AppError::NotFound("Trigger not found after update".to_string()))?;
+
+    Ok(Json(doc_to_trigger_response(&updated)))
+}
+
+/// Replace the trigger's URL token (and HMAC secret); the old URL stops working
+async fn rotate_trigger_secrets(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(trigger_id): Path<String>,
+) -> Result<Json<FlowTriggerResponse>, AppError> {
+    let existing = find_trigger(&state, &trigger_id, &auth_user.id).await?;
+    let oid = existing.get_object_id("_id").map_err(|e| AppError::Internal(e.to_string()))?;
+    let collection = state.mongo_client.database(DB_NAME).collection::<bson::Document>(FLOW_TRIGGERS);
+
+    let token = generate_token();
+    let mut update_doc = doc! {
+        "token_hash": hash_token(&token),
+        "updated_at": bson::DateTime::from_chrono(Utc::now()),
+    };
+    let auth: WebhookAuth = existing.get("auth").and_then(|a| bson::from_bson(a.clone()).ok()).unwrap_or_default();
+    let secret = (auth == WebhookAuth::Hmac).then(generate_token);
+    if let Some(ref secret) = secret {
+        update_doc.insert("secret", encrypt_api_key(&state.config.fernet_key, secret)?);
+    }
+    collection.update_one(doc! { "_id": oid }, doc! { "$set": update_doc }).await?;
+
+    let updated = collection
+        .find_one(doc! { "_id": oid })
+        .await?
+        .ok_or_else(|| AppError::NotFound("Trigger not found after update".to_string()))?;
+
+    let mut response = doc_to_trigger_response(&updated);
+    response.url = Some(hook_url(&token));
+    response.secret = secret;
+    Ok(Json(response))
+}
+
+async fn delete_trigger(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(trigger_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let oid = ObjectId::parse_str(&trigger_id)?;
+    let db = state.mongo_client.database(DB_NAME);
+
+    let result = db.collection::<bson::Document>(FLOW_TRIGGERS)
+        .delete_one(doc! { "_id": oid, "user_id": &auth_user.id })
+        .await?;
+
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound("Trigger not found".to_string()));
+    }
+    db.collection::<bson::Document>(TRIGGER_DELIVERIES)
+        .delete_many(doc! { "trigger_id": &trigger_id })
+        .await?;
+
+    Ok(Json(json!({ "message": "Trigger deleted successfully" })))
+}
+
+#[derive(Debug, Deserialize)]
+struct DeliveriesQuery {
+    #[serde(default = "default_page")]
+    page: i64,
+    #[serde(default = "default_per_page")]
+    per_page: i64,
+}
+
+fn default_page() -> i64 { 1 }
+fn default_per_page() -> i64 { 20 }
+
+/// Audit history of the requests a trigger received, newest first
+async fn list_deliveries(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(trigger_id): Path<String>,
+    Query(params): Query<DeliveriesQuery>,
+) -> Result<Json<Value>, AppError> {
+    find_trigger(&state, &trigger_id, &auth_user.id).await?;
+    let collection = state.mongo_client.database(DB_NAME).collection::<bson::Document>(TRIGGER_DELIVERIES);
+
+    let query = doc! { "trigger_id": &trigger_id };
+    let total = collection.count_documents(query.clone()).await?;
+
+    let per_page = params.per_page.clamp(1, 100);
+    let skip = ((params.page - 1).max(0)) * per_page;
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! { "received_at": -1 })
+        .skip(Some(skip as u64))
+        .limit(Some(per_page))
+        .build();
+
+    let mut cursor = collection.find(query).with_options(options).await?;
+    let mut deliveries = Vec::new();
+    while cursor.advance().await? {
+        let doc = cursor.deserialize_current()?;
+        deliveries.push(doc_to_delivery_response(&doc));
+    }
+
+    Ok(Json(json!({
+        "deliveries": deliveries,
+        "total": total,
+        "page": params.page,
+        "per_page": per_page,
+    })))
+}
+
+/// Start the trigger's flow from an inbound request, recording the delivery.
+/// `?wait=true` (or the trigger's `wait_for_completion`) holds the response
+/// until the execution finishes or `?timeout=` seconds pass.
+async fn receive_webhook(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<Value>), AppError> {
+    let db = state.mongo_client.database(DB_NAME);
+    let trigger = db.collection::<bson::Document>(FLOW_TRIGGERS)
+        .find_one(doc! { "token_hash": hash_token(&token) })
+        .await?
+        .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))?;
+    let trigger_oid = trigger.get_object_id("_id").map_err(|e| AppError::Internal(e.to_string()))?;
+    let trigger_id = trigger_oid.to_hex();
+    let flow_id = trigger.get_str("flow_id").unwrap_or_default();
+    let user_id = trigger.get_str("user_id").unwrap_or_default();
+    let delivery_id = ObjectId::new();
+
+    let signature_header = trigger.get_str("signature_header").unwrap_or(DEFAULT_SIGNATURE_HEADER).to_string();
+    let parsed_body = if body.is_empty() {
+        Ok(Value::Null)
+    } else {
+        serde_json::from_slice::<Value>(&body).map_err(|e| format!("Body is not valid JSON: {}", e))
+    };
+
+    let mut mapped_values = HashMap::new();
+    let mut execution_id = None;
+    let mut reached_flow = false;
+    let outcome: Result<(StatusCode, Value), AppError> = async {
+        if !trigger.get_bool("enabled").unwrap_or(true) {
+            return Err(AppError::Forbidden("Trigger is disabled".to_string()));
+        }
+        let auth: WebhookAuth = trigger.get("auth").and_then(|a| bson::from_bson(a.clone()).ok()).unwrap_or_default();
+        if auth == WebhookAuth::Hmac {
+            let secret = decrypt_api_key(&state.config.fernet_key, trigger.get_str("secret").unwrap_or_default())?;
+            let signature = headers.get(signature_header.as_str())
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| AppError::Unauthorized(format!("Missing {} header", signature_header)))?;
+            if !verify_signature(&secret, &body, signature) {
+                return Err(AppError::Unauthorized("Invalid signature".to_string()));
+            }
+        }
+        let parsed_body = parsed_body.clone().map_err(AppError::BadRequest)?;
+
+        let flow = state.flow_service.load_flow(flow_id, user_id).await?;
+        let mapping: HashMap<String, String> = trigger.get_document("input_mapping").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default();
+        let request = request_value(parsed_body, &headers, &query, &[&signature_header]);
+        mapped_values = map_request(&mapping, &request, &flow.inputs);
+        let (input_data, variables) = flow_inputs::split_inputs(&flow.inputs, mapped_values.clone());
+
+        reached_flow = true;
+        let admitted = state.flow_service.execute_flow(
+            flow_id,
+            user_id,
+            FlowExecutionCreate { input_data, variables, priority: trigger.get_i32("priority").ok(), ..Default::default() },
+            ExecutionTrigger::Webhook { trigger_id: trigger_id.clone(), delivery_id: delivery_id.to_hex() },
+        ).await?;
+        let id = admitted.execution_id.clone();
+        execution_id = Some(id.clone());
+        let stream_url = format!("/api/executions/{}/stream", id);
+
+        let wait = match query.get("wait") {
+            Some(value) => matches!(value.as_str(), "" | "1" | "true"),
+            None => trigger.get_bool("wait_for_completion").unwrap_or(false),
+        };
+        if !wait {
+            return Ok((StatusCode::ACCEPTED, json!({
+                "execution_id": id,
+                "status": admitted.status,
+                "queue_position": admitted.queue_position,
+                "stream_url": stream_url,
+            })));
+        }
+
+        let timeout = query.get("timeout").and_then(|t| t.parse::<u64>().ok())
+            .or_else(|| trigger.get_i64("wait_timeout_seconds").ok().map(|t| t.max(0) as u64))
+            .unwrap_or(DEFAULT_WAIT_SECONDS)
+            .min(MAX_WAIT_SECONDS);
+        let exec_doc = state.flow_service.wait_for_execution(&id, std::time::Duration::from_secs(timeout)).await?;
+        let status = exec_doc.get_str("status").unwrap_or("running");
+        let finished = matches!(status, "completed" | "failed" | "cancelled");
+        Ok((if finished { StatusCode::OK } else { StatusCode::ACCEPTED }, json!({
+            "execution_id": id,
+            "status": status,
+            "error": exec_doc.get_str("error").ok(),
+            "output": final_output(&exec_doc),
+            "stream_url": stream_url,
+        })))
+    }.await;
+
+    // Record the delivery, keeping credentials and oversized bodies out
+    let recorded_body = match &parsed_body {
+        _ if body.len() > MAX_RECORDED_BODY_BYTES => json!({ "omitted_bytes": body.len() }),
+        Ok(value) => value.clone(),
+        Err(_) => json!(String::from_utf8_lossy(&body)),
+    };
+    let (status, http_status, error) = match &outcome {
+        Ok((code, _)) => (DeliveryStatus::Accepted, code.as_u16(), None),
+        Err(e) => (
+            if reached_flow { DeliveryStatus::Failed } else { DeliveryStatus::Rejected },
+            e.status_code().as_u16(),
+            Some(e.to_string()),
+        ),
+    };
+    let now = bson::DateTime::from_chrono(Utc::now());
+    db.collection::<bson::Document>(TRIGGER_DELIVERIES).insert_one(doc! {
+        "_id": delivery_id,
+        "trigger_id": &trigger_id,
+        "user_id": user_id,
+        "flow_id": flow_id,
+        "status": to_bson(&status)?,
+        "http_status": http_status as i32,
+        "error": error,
+        "execution_id": execution_id.as_deref(),
+        "request": to_bson(&request_value(recorded_body, &headers, &query, &[&signature_header]))?,
+        "mapped_values": to_bson(&mapped_values)?,
+        "received_at": now,
+    }).await?;
+
+    let mut set = doc! { "last_delivery_at": now };
+    if let Some(ref id) = execution_id {
+        set.insert("last_execution_id", id);
+    }
+    db.collection::<bson::Document>(FLOW_TRIGGERS)
+        .update_one(doc! { "_id": trigger_oid }, doc! { "$set": set, "$inc": { "delivery_count": 1_i64 } })
+        .await?;
+
+    outcome.map(|(code, value)| (code, Json(value)))
+}
+
+async fn find_trigger(state: &AppState, trigger_id: &str, user_id: &str) -> Result<bson::Document, AppError> {
+    let oid = ObjectId::parse_str(trigger_id)?;
+    state.mongo_client.database(DB_NAME).collection::<bson::Document>(FLOW_TRIGGERS)
+        .find_one(doc! { "_id": oid, "user_id": user_id })
+        .await?
+        .ok_or_else(|| AppError::NotFound("Trigger not found".to_string()))
+}
+
+fn hook_url(token: &str) -> String {
+    format!("/api/hooks/{}", token)
+}
+
+/// Output of the step a finished execution completed last. `completed_steps`
+/// keeps first-completion order, so steps that ran again are found by end time.
+fn final_output(exec_doc: &bson::Document) -> Option<Value> {
+    exec_doc.get_document("step_results").ok()?
+        .values()
+        .filter_map(|record| bson::from_bson::<FlowStepResult>(record.clone()).ok())
+        .filter(|record| record.status == FlowStepStatus::Completed)
+        .max_by_key(|record| record.end_time)?
+        .result?
+        .get("output")
+        .cloned()
+}
+
+fn to_bson<T: serde::Serialize>(value: &T) -> Result<bson::Bson, AppError> {
+    bson::to_bson(value).map_err(|e| AppError::Internal(format!("Serialization error: {}", e)))
+}
+
+fn doc_to_trigger_response(doc: &bson::Document) -> FlowTriggerResponse {
+    FlowTriggerResponse {
+        id: doc.get_object_id("_id").map(|id| id.to_hex()).unwrap_or_default(),
+        user_id: doc.get_str("user_id").unwrap_or("").to_string(),
+        flow_id: doc.get_str("flow_id").unwrap_or("").to_string(),
+        name: doc.get_str("name").ok().map(String::from),
+        auth: doc.get("auth").and_then(|a| bson::from_bson(a.clone()).ok()).unwrap_or_default(),
+        signature_header: doc.get_str("signature_header").ok().map(String::from),
+        input_mapping: doc.get_document("input_mapping").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default(),
+        wait_for_completion: doc.get_bool("wait_for_completion").unwrap_or(false),
+        wait_timeout_seconds: doc.get_i64("wait_timeout_seconds").ok().map(|s| s.max(0) as u64),
+        priority: doc.get_i32("priority").ok(),
+        enabled: doc.get_bool("enabled").unwrap_or(true),
+        url: None,
+        secret: None,
+        delivery_count: doc.get_i64("delivery_count").unwrap_or(0),
+        last_delivery_at: doc.get_datetime("last_delivery_at").ok().map(|d| d.to_chrono()),
+        last_execution_id: doc.get_str("last_execution_id").ok().map(String::from),
+        created_at: doc.get_datetime("created_at").map(|d| d.to_chrono()).unwrap_or_else(|_| Utc::now()),
+        updated_at: doc.get_datetime("updated_at").map(|d| d.to_chrono()).unwrap_or_else(|_| Utc::now()),
+    }
+}
+
+fn doc_to_delivery_response(doc: &bson::Document) -> TriggerDeliveryResponse {
+    TriggerDeliveryResponse {
+        id: doc.get_object_id("_id").map(|id| id.to_hex()).unwrap_or_default(),
+        trigger_id: doc.get_str("trigger_id").unwrap_or("").to_string(),
+        status: doc.get("status").and_then(|s| bson::from_bson(s.clone()).ok()).unwrap_or(DeliveryStatus::Rejected),
+        http_status: doc.get_i32("http_status").map(|s| s as u16).unwrap_or(0),
+        error: doc.get_str("error").ok().map(String::from),
+        execution_id: doc.get_str("execution_id").ok().map(String::from),
+        request: doc.get_document("request").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or(Value::Null),
+        mapped_values: doc.get_document("mapped_values").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default(),
+        received_at: doc.get_datetime("received_at").map(|d| d.to_chrono()).unwrap_or_else(|_| Utc::now()),
+    }
+}
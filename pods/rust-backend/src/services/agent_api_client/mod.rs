@@ -11,19 +11,34 @@ use std::sync::Arc;
 
 use crate::auth::encryption::{decrypt_api_key, FernetCipher};
 use crate::db::collections::*;
+use crate::models::flow::AgentOverride;
 use crate::models::llm::LLMProvider;
 use crate::models::mcp_tools::MCPToolInfo;
-use crate::services::mcp_session_manager::McpSessionManager;
+use crate::services::mcp_session_manager::{McpSessionManager, DEFAULT_TOOL_CALL_TIMEOUT};
 
 use message_formatter::LLMMessage;
 use providers::LLMApiResponse;
 
+/// Resolved connection and sampling settings for one LLM document
+struct LlmSettings {
+    provider: LLMProvider,
+    model_name: String,
+    max_tokens: i64,
+    temperature: f64,
+    api_key: String,
+    config: bson::Document,
+}
+
 /// Main agent API client for orchestrating LLM calls with tool use
 pub struct AgentApiClient {
     mongo_client: mongodb::Client,
     cipher: FernetCipher,
     http_client: HttpClient,
     mcp_manager: Arc<McpSessionManager>,
+    /// User running a flow; LLMs the flow names itself must belong to them
+    owner_id: Option<String>,
+    /// When the step being run times out; tool calls may not outlast it
+    deadline: Option<tokio::time::Instant>,
     /// Maps tool names to MCP connection IDs for routing
     pub tool_to_connection_map: HashMap<String, String>,
 }
@@ -39,10 +54,31 @@ impl AgentApiClient {
             cipher,
             http_client: HttpClient::new(),
             mcp_manager,
+            owner_id: None,
+            deadline: None,
             tool_to_connection_map: HashMap::new(),
         }
     }
 
+    /// Run on behalf of a flow owned by `owner_id`
+    pub fn for_owner(mut self, owner_id: &str) -> Self {
+        self.owner_id = Some(owner_id.to_string());
+        self
+    }
+
+    /// Stop waiting on tool calls at `deadline`
+    pub fn with_deadline(mut self, deadline: tokio::time::Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Time a tool call started now may take
+    fn tool_call_timeout(&self) -> std::time::Duration {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()))
+            .unwrap_or(DEFAULT_TOOL_CALL_TIMEOUT)
+    }
+
     fn db(&self) -> mongodb::Database {
         self.mongo_client.database(DB_NAME)
     }
@@ -65,6 +101,18 @@ impl AgentApiClient {
         }
     }
 
+    /// An LLM named by a flow (step override, judge, router), only if the
+    /// flow's owner owns it
+    pub async fn get_owned_llm(&self, llm_id: &str) -> Option<bson::Document> {
+        let owner_id = self.owner_id.as_deref()?;
+        let collection = self.db().collection::<bson::Document>(LLMS);
+        if let Ok(oid) = ObjectId::parse_str(llm_id) {
+            collection.find_one(doc! { "_id": oid, "user_id": owner_id }).await.ok().flatten()
+        } else {
+            collection.find_one(doc! { "_id": llm_id, "user_id": owner_id }).await.ok().flatten()
+        }
+    }
+
     /// Discover tools from all MCP connections for an agent and populate routing map
     pub async fn get_available_tools_for_agent(
         &mut self,
@@ -145,28 +193,42 @@ impl AgentApiClient {
         all_tools
     }
 
-    /// Main orchestration: execute an agent step with LLM + tool use loop
+    /// Main orchestration: execute an agent step with LLM + tool use loop.
+    /// `overrides` replace the stored agent's settings for this call only.
     pub async fn execute_agent_step(
         &mut self,
         agent_id: &str,
         step_description: &str,
         parameters: &Value,
+        overrides: Option<&AgentOverride>,
         conversation_history: Option<Vec<LLMMessage>>,
         mut event_callback: Option<Box<dyn FnMut(&str, Value) + Send>>,
     ) -> Value {
         // Load agent
-        let agent_data = match self.get_agent_by_id(agent_id).await {
+        let mut agent_data = match self.get_agent_by_id(agent_id).await {
             Some(a) => a,
             None => return json!({"success": false, "error": format!("Agent {} not found", agent_id)}),
         };
+        if let Some(overrides) = overrides {
+            if let Err(e) = apply_agent_override(&mut agent_data, overrides) {
+                return json!({"success": false, "error": e});
+            }
+        }
 
         let agent_name = agent_data.get_str("name").unwrap_or("Unknown").to_string();
 
-        // Load LLM
+        // Load LLM; one overridden by the flow must be its owner's
         let llm_id = agent_data.get_str("llm_id").unwrap_or("").to_string();
-        let llm_data = match self.get_llm_by_id(&llm_id).await {
-            Some(l) => l,
-            None => return json!({"success": false, "error": format!("LLM {} not found", llm_id)}),
+        let llm_data = if overrides.is_some_and(|o| o.llm_id.as_deref().is_some_and(|id| !id.is_empty())) {
+            match self.get_owned_llm(&llm_id).await {
+                Some(l) => l,
+                None => return json!({"success": false, "error": format!("LLM {} not found or not accessible", llm_id)}),
+            }
+        } else {
+            match self.get_llm_by_id(&llm_id).await {
+                Some(l) => l,
+                None => return json!({"success": false, "error": format!("LLM {} not found", llm_id)}),
+            }
         };
 
         // Discover tools
@@ -204,31 +266,19 @@ impl AgentApiClient {
         messages.push(LLMMessage::user(task_content));
 
         // Extract LLM config
-        let provider_str = llm_data.get_str("provider").unwrap_or("anthropic");
-        let config = llm_data.get_document("config").ok().cloned().unwrap_or_default();
-        let model_name = config.get_str("model_name").unwrap_or("claude-3-5-sonnet-20241022").to_string();
-        let max_tokens = config.get_i64("max_tokens").unwrap_or(4000);
-        let temperature = config.get_f64("temperature").unwrap_or(0.7);
-
-        // Decrypt API key (field is "api_key_encrypted" in MongoDB)
-        let api_key = match llm_data.get_str("api_key_encrypted").ok()
-            .or_else(|| llm_data.get_str("api_key").ok())
-            .and_then(|encrypted| decrypt_api_key(&self.cipher, encrypted).ok())
-        {
-            Some(key) if !key.is_empty() => key,
-            _ => return json!({"success": false, "error": "Failed to decrypt API key — check that the LLM has a valid API key"}),
-        };
+        let LlmSettings { provider, model_name, mut max_tokens, mut temperature, api_key, config } =
+            match self.llm_settings(&llm_data) {
+                Ok(settings) => settings,
+                Err(e) => return json!({"success": false, "error": e}),
+            };
+        if let Some(value) = overrides.and_then(|o| o.max_tokens) {
+            max_tokens = value;
+        }
+        if let Some(value) = overrides.and_then(|o| o.temperature) {
+            temperature = value;
+        }
 
         // Format tools for provider
-        let provider = match provider_str {
-            "anthropic" => LLMProvider::Anthropic,
-            "openai" => LLMProvider::Openai,
-            "openrouter" => LLMProvider::Openrouter,
-            "custom" => LLMProvider::Custom,
-            "claude_cli" => LLMProvider::ClaudeCli,
-            _ => LLMProvider::Anthropic,
-        };
-
         let tools_formatted = match provider {
             LLMProvider::Anthropic => providers::anthropic::format_tools(&available_tools),
             _ => providers::openai::format_tools(&available_tools),
@@ -240,10 +290,14 @@ impl AgentApiClient {
             Some(&tools_formatted)
         };
 
+        // Ask for JSON output when the step declares an output schema
+        let response_format = parameters.get("output_schema")
+            .and_then(|schema| structured_response_format(&provider, schema));
+
         // Initial LLM call
         let mut current_response = self.call_llm(
             &provider, &api_key, &model_name, &messages, max_tokens, temperature,
-            tools_ref, &config, &mut event_callback,
+            tools_ref, response_format.as_ref(), &config, &mut event_callback,
         ).await;
 
         if !current_response.success {
@@ -302,6 +356,7 @@ impl AgentApiClient {
                     &self.mcp_manager,
                     &self.tool_to_connection_map,
                     tool_call,
+                    self.tool_call_timeout(),
                 ).await;
 
                 if let Some(ref mut cb) = event_callback {
@@ -330,7 +385,7 @@ impl AgentApiClient {
             // Follow-up LLM call
             current_response = self.call_llm(
                 &provider, &api_key, &model_name, &messages, max_tokens, temperature,
-                tools_ref, &config, &mut event_callback,
+                tools_ref, response_format.as_ref(), &config, &mut event_callback,
             ).await;
 
             if !current_response.success {
@@ -361,9 +416,85 @@ impl AgentApiClient {
             "tool_rounds": round_count,
             "agent_id": agent_id,
             "agent_name": agent_name,
+            "llm_id": llm_id,
         })
     }
 
+    /// Run a single prompt against one of the flow owner's LLMs directly,
+    /// without an agent or tools
+    pub async fn execute_llm_prompt(
+        &mut self,
+        llm_id: &str,
+        system_prompt: Option<&str>,
+        prompt: &str,
+    ) -> Value {
+        let llm_data = match self.get_owned_llm(llm_id).await {
+            Some(l) => l,
+            None => return json!({"success": false, "error": format!("LLM {} not found or not accessible", llm_id)}),
+        };
+
+        let LlmSettings { provider, model_name, max_tokens, temperature, api_key, config } =
+            match self.llm_settings(&llm_data) {
+                Ok(settings) => settings,
+                Err(e) => return json!({"success": false, "error": e}),
+            };
+
+        let mut messages = Vec::new();
+        if let Some(system) = system_prompt.filter(|s| !s.is_empty()) {
+            messages.push(LLMMessage::system(system));
+        }
+        messages.push(LLMMessage::user(prompt));
+
+        let response = self.call_llm(
+            &provider, &api_key, &model_name, &messages, max_tokens, temperature,
+            None, None, &config, &mut None,
+        ).await;
+
+        if !response.success {
+            return json!({
+                "success": false,
+                "error": response.error.unwrap_or_else(|| "LLM call failed".into()),
+                "llm_id": llm_id,
+            });
+        }
+
+        json!({
+            "success": true,
+            "content": response.content,
+            "model_used": response.model_used,
+            "llm_id": llm_id,
+        })
+    }
+
+    /// Read provider, model, sampling settings and decrypted API key from an LLM document
+    fn llm_settings(&self, llm_data: &bson::Document) -> Result<LlmSettings, String> {
+        let provider_str = llm_data.get_str("provider").unwrap_or("anthropic");
+        let config = llm_data.get_document("config").ok().cloned().unwrap_or_default();
+        let model_name = config.get_str("model_name").unwrap_or("claude-3-5-sonnet-20241022").to_string();
+        let max_tokens = config.get_i64("max_tokens").unwrap_or(4000);
+        let temperature = config.get_f64("temperature").unwrap_or(0.7);
+
+        // Decrypt API key (field is "api_key_encrypted" in MongoDB)
+        let api_key = match llm_data.get_str("api_key_encrypted").ok()
+            .or_else(|| llm_data.get_str("api_key").ok())
+            .and_then(|encrypted| decrypt_api_key(&self.cipher, encrypted).ok())
+        {
+            Some(key) if !key.is_empty() => key,
+            _ => return Err("Failed to decrypt API key — check that the LLM has a valid API key".to_string()),
+        };
+
+        let provider = match provider_str {
+            "anthropic" => LLMProvider::Anthropic,
+            "openai" => LLMProvider::Openai,
+            "openrouter" => LLMProvider::Openrouter,
+            "custom" => LLMProvider::Custom,
+            "claude_cli" => LLMProvider::ClaudeCli,
+            _ => LLMProvider::Anthropic,
+        };
+
+        Ok(LlmSettings { provider, model_name, max_tokens, temperature, api_key, config })
+    }
+
     /// Dispatch LLM call to the correct provider
     async fn call_llm(
         &self,
@@ -374,6 +505,7 @@ impl AgentApiClient {
         max_tokens: i64,
         temperature: f64,
         tools: Option<&[Value]>,
+        response_format: Option<&Value>,
         config: &bson::Document,
         event_callback: &mut Option<Box<dyn FnMut(&str, Value) + Send>>,
     ) -> LLMApiResponse {
@@ -412,6 +544,7 @@ impl AgentApiClient {
                     max_tokens,
                     temperature,
                     tools,
+                    response_format,
                     org_id,
                     None,
                 ).await
@@ -427,6 +560,7 @@ impl AgentApiClient {
                     max_tokens,
                     temperature,
                     tools,
+                    response_format,
                     site_url,
                     app_name,
                     None,
@@ -444,6 +578,7 @@ impl AgentApiClient {
                     max_tokens,
                     temperature,
                     tools,
+                    response_format,
                     None,
                 ).await
             }
@@ -453,3 +588,78 @@ impl AgentApiClient {
         }
     }
 }
+
+/// Provider request setting for JSON output. OpenAI and OpenRouter take the
+/// schema itself, other OpenAI-compatible servers get plain JSON mode. Both only
+/// accept object schemas; Anthropic and the Claude CLI rely on the prompt.
+fn structured_response_format(provider: &LLMProvider, schema: &Value) -> Option<Value> {
+    if schema.get("type").and_then(|t| t.as_str()) != Some("object") {
+        return None;
+    }
+    match provider {
+        LLMProvider::Openai | LLMProvider::Openrouter => Some(json!({
+            "type": "json_schema",
+            "json_schema": {"name": "step_output", "schema": schema},
+        })),
+        LLMProvider::Custom => Some(json!({"type": "json_object"})),
+        LLMProvider::Anthropic | LLMProvider::ClaudeCli => None,
+    }
+}
+
+/// Merge a flow step's overrides over a loaded agent document. Sampling
+/// overrides (temperature, max_tokens) apply to the LLM settings instead.
+/// MCP connections can only narrow the agent's own set down.
+fn apply_agent_override(agent_data: &mut bson::Document, overrides: &AgentOverride) -> Result<(), String> {
+    if let Some(ref connections) = overrides.mcp_connections {
+        let own: Vec<&str> = agent_data.get_array("mcp_connections").ok()
+            .map(|arr| arr.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default();
+        let foreign: Vec<&str> = connections.iter()
+            .map(String::as_str)
+            .filter(|id| !own.contains(id))
+            .collect();
+        if !foreign.is_empty() {
+            return Err(format!(
+                "Agent {} is not connected to MCP connection(s) {}",
+                agent_data.get_str("name").unwrap_or("Unknown"), foreign.join(", ")
+            ));
+        }
+        agent_data.insert("mcp_connections", connections.clone());
+    }
+    if let Some(llm_id) = overrides.llm_id.as_deref().filter(|id| !id.is_empty()) {
+        agent_data.insert("llm_id", llm_id);
+    }
+    if let Some(ref prompt) = overrides.system_prompt {
+        agent_data.insert("system_prompt", prompt.clone());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::Bson;
+
+    fn override_connections(connections: &[&str]) -> AgentOverride {
+        AgentOverride {
+            llm_id: None,
+            mcp_connections: Some(connections.iter().map(|c| c.to_string()).collect()),
+            system_prompt: None,
+            temperature: None,
+            max_tokens: None,
+        }
+    }
+
+    #[test]
+    fn test_override_narrows_the_agents_connections() {
+        let mut agent = doc! { "name": "Researcher", "mcp_connections": ["search", "files"] };
+        apply_agent_override(&mut agent, &override_connections(&["search"])).unwrap();
+        assert_eq!(agent.get_array("mcp_connections").unwrap(), &vec![Bson::String("search".to_string())]);
+
+        // Another user's connection can't be slipped in through a step override
+        let mut agent = doc! { "name": "Researcher", "mcp_connections": ["search"] };
+        let error = apply_agent_override(&mut agent, &override_connections(&["search", "someone-elses"])).unwrap_err();
+        assert_eq!(error, "Agent Researcher is not connected to MCP connection(s) someone-elses");
+        assert_eq!(agent.get_array("mcp_connections").unwrap().len(), 1);
+    }
+}
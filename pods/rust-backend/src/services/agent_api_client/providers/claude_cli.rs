@@ -61,6 +61,8 @@ pub async fn call_streaming(
         .or_else(|_| std::env::var("TMP"))
         .unwrap_or_else(|_| "/tmp".to_string());
     cmd.current_dir(&work_dir);
+    // Kill the CLI if the step is cancelled or times out while waiting on it
+    cmd.kill_on_drop(true);
 
     let output = match tokio::time::timeout(
         std::time::Duration::from_secs(300),
@@ -14,6 +14,7 @@ pub async fn call_streaming(
     max_tokens: i64,
     temperature: f64,
     tools: Option<&[Value]>,
+    response_format: Option<&Value>,
     stream_callback: Option<&mut (dyn FnMut(&str) + Send)>,
 ) -> LLMApiResponse {
     let endpoint = format!("{}/v1/chat/completions", base_url.trim_end_matches('/'));
@@ -27,6 +28,7 @@ pub async fn call_streaming(
         max_tokens,
         temperature,
         tools,
+        response_format,
         None,
         None,
         None,
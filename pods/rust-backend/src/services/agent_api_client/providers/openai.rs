@@ -15,6 +15,7 @@ pub async fn call_streaming(
     max_tokens: i64,
     temperature: f64,
     tools: Option<&[Value]>,
+    response_format: Option<&Value>,
     organization_id: Option<&str>,
     stream_callback: Option<&mut (dyn FnMut(&str) + Send)>,
 ) -> LLMApiResponse {
@@ -27,6 +28,7 @@ pub async fn call_streaming(
         max_tokens,
         temperature,
         tools,
+        response_format,
         organization_id,
         None,
         None,
@@ -45,6 +47,7 @@ pub async fn call_openai_compatible(
     max_tokens: i64,
     temperature: f64,
     tools: Option<&[Value]>,
+    response_format: Option<&Value>,
     organization_id: Option<&str>,
     http_referer: Option<&str>,
     x_title: Option<&str>,
@@ -70,6 +73,10 @@ pub async fn call_openai_compatible(
         }
     }
 
+    if let Some(format) = response_format {
+        payload["response_format"] = format.clone();
+    }
+
     let mut request = http_client
         .post(endpoint)
         .header("Content-Type", "application/json")
@@ -15,6 +15,7 @@ pub async fn call_streaming(
     max_tokens: i64,
     temperature: f64,
     tools: Option<&[Value]>,
+    response_format: Option<&Value>,
     site_url: Option<&str>,
     app_name: Option<&str>,
     stream_callback: Option<&mut (dyn FnMut(&str) + Send)>,
@@ -30,6 +31,7 @@ pub async fn call_streaming(
         max_tokens,
         temperature,
         tools,
+        response_format,
         None,
         site_url,
         app_name,
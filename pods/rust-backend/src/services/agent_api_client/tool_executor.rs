@@ -3,11 +3,12 @@ use std::collections::HashMap;
 
 use crate::services::mcp_session_manager::McpSessionManager;
 
-/// Execute a tool call by routing to the correct MCP session
+/// Execute a tool call by routing to the correct MCP session, giving up after `timeout`
 pub async fn execute_tool_call(
     mcp_manager: &McpSessionManager,
     tool_to_connection_map: &HashMap<String, String>,
     tool_call: &Value,
+    timeout: std::time::Duration,
 ) -> Value {
     let function_name = tool_call
         .get("function")
@@ -43,7 +44,7 @@ pub async fn execute_tool_call(
         }
     };
 
-    match mcp_manager.call_tool(&connection_id, function_name, args_map).await {
+    match mcp_manager.call_tool_with_timeout(&connection_id, function_name, args_map, timeout).await {
         Ok(result) => {
             json!({
                 "tool_name": function_name,
@@ -0,0 +1,246 @@
+//! In-process control of running executions.
+//!
+//! Every execution running in this process has an `ExecutionControl`: a
+//! cancellation token, a signal channel that delivers approval decisions,
+//! human input and debugger commands to the step waiting for them, and the
+//! debugger's breakpoints. Mongo stays the durable
+//! record (`is_cancellation_requested`, `pending_approvals`, `input_submissions`)
+//! and is written before a signal is sent; the handle only wakes the executor.
+//!
+//! A sub-flow execution's token is a child of its parent's, so cancelling the
+//! parent stops the whole tree. Cancelling interrupts the step in flight,
+//! dropping any LLM stream or tool call it was waiting on.
+
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use crate::models::flow::{ApprovalDecision, PauseReason};
+
+/// Signals kept for a waiting step that falls behind; it re-reads the
+/// execution record when it misses some
+const SIGNAL_CAPACITY: usize = 32;
+
+/// A decision delivered to a waiting step
+#[derive(Debug, Clone)]
+pub enum ControlSignal {
+    ApprovalDecided { decision: ApprovalDecision },
+    InputSubmitted { step_id: String, values: HashMap<String, Value> },
+    /// A signal for a wait-for-signal step
+    SignalReceived { step_id: String, payload: Value },
+    /// A paused execution may go on
+    Resumed,
+    /// Variables of a paused execution were edited
+    VariablesEdited { set: HashMap<String, Value>, unset: Vec<String> },
+}
+
+/// Where the execution should pause next
+#[derive(Debug, Default)]
+struct PausePoints {
+    breakpoints: HashSet<String>,
+    /// Pause before the next step, whatever it is
+    requested: Option<PauseReason>,
+}
+
+/// Control handle of one running execution
+#[derive(Debug, Clone)]
+pub struct ExecutionControl {
+    cancel: CancellationToken,
+    signals: broadcast::Sender<ControlSignal>,
+    pauses: Arc<Mutex<PausePoints>>,
+}
+
+impl ExecutionControl {
+    fn new(cancel: CancellationToken) -> Self {
+        Self {
+            cancel,
+            signals: broadcast::channel(SIGNAL_CAPACITY).0,
+            pauses: Arc::default(),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Resolves once the execution is cancelled
+    pub async fn cancelled(&self) {
+        self.cancel.cancelled().await
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Receive the signals sent from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<ControlSignal> {
+        self.signals.subscribe()
+    }
+
+    pub fn send(&self, signal: ControlSignal) {
+        let _ = self.signals.send(signal);
+    }
+
+    pub fn set_breakpoints(&self, breakpoints: &[String]) {
+        self.pause_points().breakpoints = breakpoints.iter().cloned().collect();
+    }
+
+    /// Pause before the next step
+    pub fn request_pause(&self, reason: PauseReason) {
+        self.pause_points().requested = Some(reason);
+    }
+
+    /// Whether to pause before `step_id`, and why. A pending request is used up.
+    pub fn take_pause(&self, step_id: &str) -> Option<PauseReason> {
+        let mut pauses = self.pause_points();
+        match pauses.requested.take() {
+            Some(reason) => Some(reason),
+            None if pauses.breakpoints.contains(step_id) => Some(PauseReason::Breakpoint),
+            None => None,
+        }
+    }
+
+    fn pause_points(&self) -> std::sync::MutexGuard<'_, PausePoints> {
+        self.pauses.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Handles of the executions running in this process, by execution ID
+#[derive(Debug, Default)]
+pub(crate) struct ExecutionControls {
+    handles: Mutex<HashMap<String, ExecutionControl>>,
+}
+
+impl ExecutionControls {
+    /// The execution's handle, created on first use. A new sub-flow handle is
+    /// cancelled along with its parent's.
+    pub fn register(&self, execution_id: &str, parent_execution_id: Option<&str>) -> ExecutionControl {
+        let mut handles = self.handles.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(control) = handles.get(execution_id) {
+            return control.clone();
+        }
+        let token = match parent_execution_id.and_then(|id| handles.get(id)) {
+            Some(parent) => parent.cancel.child_token(),
+            None => CancellationToken::new(),
+        };
+        let control = ExecutionControl::new(token);
+        handles.insert(execution_id.to_string(), control.clone());
+        control
+    }
+
+    pub fn get(&self, execution_id: &str) -> Option<ExecutionControl> {
+        self.handles.lock().unwrap_or_else(|e| e.into_inner()).get(execution_id).cloned()
+    }
+
+    /// Drop the handle once the execution has finished
+    pub fn remove(&self, execution_id: &str) {
+        self.handles.lock().unwrap_or_else(|e| e.into_inner()).remove(execution_id);
+    }
+
+    /// Cancel the execution if it runs here; returns whether it did
+    pub fn cancel(&self, execution_id: &str) -> bool {
+        match self.get(execution_id) {
+            Some(control) => {
+                control.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Deliver a signal to the execution's waiting steps, if it runs here
+    pub fn send(&self, execution_id: &str, signal: ControlSignal) {
+        if let Some(control) = self.get(execution_id) {
+            control.send(signal);
+        }
+    }
+}
+
+/// The next control signal, or `None` once `deadline` passes
+pub async fn next_signal(
+    signals: &mut broadcast::Receiver<ControlSignal>,
+    deadline: Option<tokio::time::Instant>,
+) -> Option<Result<ControlSignal, broadcast::error::RecvError>> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, signals.recv()).await.ok(),
+        None => Some(signals.recv().await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancelling_a_parent_cancels_its_sub_flows() {
+        let controls = ExecutionControls::default();
+        let parent = controls.register("parent", None);
+        let child = controls.register("child", Some("parent"));
+        let other = controls.register("other", None);
+
+        assert!(controls.cancel("parent"));
+        assert!(parent.is_cancelled());
+        assert!(child.is_cancelled());
+        assert!(!other.is_cancelled());
+
+        // Cancelling a sub-flow leaves its parent running
+        let child = controls.register("child-2", Some("other"));
+        controls.cancel("child-2");
+        assert!(child.is_cancelled());
+        assert!(!other.is_cancelled());
+    }
+
+    #[test]
+    fn test_register_reuses_handles_and_remove_forgets_them() {
+        let controls = ExecutionControls::default();
+        let first = controls.register("exec", None);
+        let again = controls.register("exec", None);
+        again.cancel();
+        assert!(first.is_cancelled());
+
+        controls.remove("exec");
+        assert!(controls.get("exec").is_none());
+        assert!(!controls.cancel("exec"));
+    }
+
+    #[test]
+    fn test_pauses_before_breakpoints_and_requested_steps() {
+        let controls = ExecutionControls::default();
+        let control = controls.register("exec", None);
+        control.set_breakpoints(&["review".to_string()]);
+
+        assert_eq!(control.take_pause("plan"), None);
+        assert_eq!(control.take_pause("review"), Some(PauseReason::Breakpoint));
+        // Breakpoints stay; requests are used by the next step
+        assert_eq!(control.take_pause("review"), Some(PauseReason::Breakpoint));
+        control.request_pause(PauseReason::Step);
+        assert_eq!(control.take_pause("plan"), Some(PauseReason::Step));
+        assert_eq!(control.take_pause("plan"), None);
+
+        control.request_pause(PauseReason::Request);
+        assert_eq!(controls.get("exec").unwrap().take_pause("review"), Some(PauseReason::Request));
+        control.set_breakpoints(&[]);
+        assert_eq!(control.take_pause("review"), None);
+    }
+
+    #[tokio::test]
+    async fn test_signals_reach_subscribers() {
+        let controls = ExecutionControls::default();
+        let control = controls.register("exec", None);
+        let mut receiver = control.subscribe();
+
+        controls.send("exec", ControlSignal::InputSubmitted {
+            step_id: "ask".to_string(),
+            values: HashMap::from([("path".to_string(), Value::from("src/lib.rs"))]),
+        });
+        match receiver.recv().await.unwrap() {
+            ControlSignal::InputSubmitted { step_id, values } => {
+                assert_eq!(step_id, "ask");
+                assert_eq!(values["path"], "src/lib.rs");
+            }
+            other => panic!("unexpected signal {:?}", other),
+        }
+    }
+}
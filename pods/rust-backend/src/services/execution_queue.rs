@@ -0,0 +1,219 @@
+//! Execution queue: admission of queued executions under concurrency limits.
+//!
+//! New executions are stored as `queued` and started by a dispatcher in
+//! priority order (higher first, then oldest first) while the global and
+//! per-user limits allow. Sub-flow executions run inside their parent's slot
+//! and are neither queued nor counted.
+//!
+//! A flow can restrict its own concurrency with `metadata.concurrency`:
+//! - `"parallel"` (default): no restriction beyond the limits
+//! - `"singleton"`: one execution of the flow runs at a time, the rest wait
+//! - `"skip_if_running"`: a new execution is refused while another one is
+//!   queued or running
+//!
+//! `metadata.priority` sets the flow's default priority; a request can
+//! override it.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::{Mutex, Notify};
+
+use crate::models::flow::{Flow, FlowExecutionStatus};
+
+/// Concurrency limits, `None` meaning unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QueueLimits {
+    pub global: Option<usize>,
+    pub per_user: Option<usize>,
+}
+
+impl QueueLimits {
+    /// Limits from configuration, where 0 means unlimited
+    pub fn new(global: usize, per_user: usize) -> Self {
+        let limit = |n: usize| (n > 0).then_some(n);
+        Self { global: limit(global), per_user: limit(per_user) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ConcurrencyPolicy {
+    #[default]
+    Parallel,
+    Singleton,
+    SkipIfRunning,
+}
+
+impl ConcurrencyPolicy {
+    pub fn from_flow(flow: &Flow) -> Self {
+        Self::parse(flow.metadata.get("concurrency").and_then(|v| v.as_str()).unwrap_or_default())
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "singleton" => ConcurrencyPolicy::Singleton,
+            "skip_if_running" => ConcurrencyPolicy::SkipIfRunning,
+            _ => ConcurrencyPolicy::Parallel,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConcurrencyPolicy::Parallel => "parallel",
+            ConcurrencyPolicy::Singleton => "singleton",
+            ConcurrencyPolicy::SkipIfRunning => "skip_if_running",
+        }
+    }
+}
+
+/// Priority of a new execution: the requested one, else the flow's default
+pub fn execution_priority(flow: &Flow, requested: Option<i32>) -> i32 {
+    requested
+        .or_else(|| flow.metadata.get("priority").and_then(Value::as_i64).map(|p| p as i32))
+        .unwrap_or(0)
+}
+
+/// Where a new execution stands right after it was queued
+#[derive(Debug, Clone)]
+pub struct AdmittedExecution {
+    pub execution_id: String,
+    /// `running` if it started right away, `queued` otherwise
+    pub status: FlowExecutionStatus,
+    pub queue_position: Option<u64>,
+}
+
+/// A running top-level execution, as counted against the limits
+#[derive(Debug, Clone)]
+pub(crate) struct ActiveExecution {
+    pub user_id: String,
+    pub flow_id: String,
+}
+
+/// A queued execution; the queue is passed in dispatch order
+#[derive(Debug, Clone)]
+pub(crate) struct QueuedExecution {
+    pub execution_id: String,
+    pub user_id: String,
+    pub flow_id: String,
+    pub policy: ConcurrencyPolicy,
+}
+
+/// IDs of the queued executions to start now, in dispatch order. An execution
+/// held back by its user's limit or its flow's policy doesn't block the ones
+/// behind it; a full global limit stops the pass.
+pub(crate) fn select_ready(
+    running: &[ActiveExecution],
+    queued: &[QueuedExecution],
+    limits: QueueLimits,
+) -> Vec<String> {
+    let mut total = running.len();
+    let mut per_user: HashMap<&str, usize> = HashMap::new();
+    let mut per_flow: HashMap<&str, usize> = HashMap::new();
+    for execution in running {
+        *per_user.entry(&execution.user_id).or_default() += 1;
+        *per_flow.entry(&execution.flow_id).or_default() += 1;
+    }
+
+    let mut ready = Vec::new();
+    for execution in queued {
+        if limits.global.is_some_and(|limit| total >= limit) {
+            break;
+        }
+        let user_running = per_user.get(execution.user_id.as_str()).copied().unwrap_or(0);
+        if limits.per_user.is_some_and(|limit| user_running >= limit) {
+            continue;
+        }
+        let flow_running = per_flow.get(execution.flow_id.as_str()).copied().unwrap_or(0);
+        if execution.policy != ConcurrencyPolicy::Parallel && flow_running > 0 {
+            continue;
+        }
+
+        total += 1;
+        *per_user.entry(&execution.user_id).or_default() += 1;
+        *per_flow.entry(&execution.flow_id).or_default() += 1;
+        ready.push(execution.execution_id.clone());
+    }
+    ready
+}
+
+/// Dispatcher state shared by a `FlowService` and the executors it spawns
+#[derive(Debug, Default)]
+pub(crate) struct ExecutionQueue {
+    pub limits: QueueLimits,
+    /// Signalled when an execution finishes and frees its slot
+    pub wake: Notify,
+    /// Held during a dispatch pass so concurrent passes can't overshoot the limits
+    pub dispatch_lock: Mutex<()>,
+}
+
+impl ExecutionQueue {
+    pub fn new(limits: QueueLimits) -> Self {
+        Self { limits, ..Default::default() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn running(user_id: &str, flow_id: &str) -> ActiveExecution {
+        ActiveExecution { user_id: user_id.to_string(), flow_id: flow_id.to_string() }
+    }
+
+    fn queued(execution_id: &str, user_id: &str, flow_id: &str, policy: ConcurrencyPolicy) -> QueuedExecution {
+        QueuedExecution {
+            execution_id: execution_id.to_string(),
+            user_id: user_id.to_string(),
+            flow_id: flow_id.to_string(),
+            policy,
+        }
+    }
+
+    #[test]
+    fn test_limits_from_config() {
+        assert_eq!(QueueLimits::new(10, 3), QueueLimits { global: Some(10), per_user: Some(3) });
+        assert_eq!(QueueLimits::new(0, 0), QueueLimits::default());
+    }
+
+    #[test]
+    fn test_select_ready_respects_limits() {
+        let limits = QueueLimits::new(3, 2);
+        let queue = vec![
+            queued("e1", "alice", "f1", ConcurrencyPolicy::Parallel),
+            queued("e2", "alice", "f1", ConcurrencyPolicy::Parallel),
+            queued("e3", "bob", "f2", ConcurrencyPolicy::Parallel),
+            queued("e4", "carol", "f3", ConcurrencyPolicy::Parallel),
+        ];
+
+        // alice already has one running: she gets one more slot, and her
+        // second execution doesn't hold bob back
+        let ready = select_ready(&[running("alice", "f9")], &queue, limits);
+        assert_eq!(ready, vec!["e1", "e3"]);
+
+        assert!(select_ready(&[running("x", "a"), running("y", "b"), running("z", "c")], &queue, limits).is_empty());
+        assert_eq!(select_ready(&[], &queue, QueueLimits::default()).len(), 4);
+    }
+
+    #[test]
+    fn test_select_ready_singleton_flows() {
+        let queue = vec![
+            queued("e1", "alice", "f1", ConcurrencyPolicy::Singleton),
+            queued("e2", "alice", "f1", ConcurrencyPolicy::Singleton),
+            queued("e3", "alice", "f2", ConcurrencyPolicy::Singleton),
+        ];
+        assert_eq!(select_ready(&[], &queue, QueueLimits::default()), vec!["e1", "e3"]);
+        assert_eq!(select_ready(&[running("bob", "f1")], &queue, QueueLimits::default()), vec!["e3"]);
+    }
+
+    #[test]
+    fn test_policy_and_priority_from_flow() {
+        let flow: Flow = serde_json::from_value(json!({
+            "user_id": "u1", "name": "Deploy", "start_step_id": "a", "steps": [],
+            "metadata": {"concurrency": "skip_if_running", "priority": 5},
+        })).unwrap();
+        assert_eq!(ConcurrencyPolicy::from_flow(&flow), ConcurrencyPolicy::SkipIfRunning);
+        assert_eq!(execution_priority(&flow, None), 5);
+        assert_eq!(execution_priority(&flow, Some(-1)), -1);
+        assert_eq!(ConcurrencyPolicy::parse("unknown"), ConcurrencyPolicy::Parallel);
+    }
+}
@@ -0,0 +1,152 @@
+//! Reruns of an execution from a chosen step.
+//!
+//! The new execution starts at the chosen step with the state the source had
+//! when that step started: its variables and inputs plus the outputs of every
+//! step that completed before it. If the step never ran in the source, every
+//! completed step is restored. Callers can override variables (including
+//! restored `step_<id>_output` values) and step parameters for the rerun only;
+//! the saved flow is not changed.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::models::flow::{Flow, FlowStepResult, FlowStepStatus};
+
+/// Scope and step records carried over from the source execution
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RestoredState {
+    pub variables: HashMap<String, Value>,
+    /// Restored steps in the order they completed
+    pub completed_steps: Vec<String>,
+    /// Last step to complete before the rerun step
+    pub previous_step_id: Option<String>,
+    pub step_results: HashMap<String, FlowStepResult>,
+}
+
+pub(crate) fn restore_state(
+    variables: &HashMap<String, Value>,
+    input_data: &HashMap<String, Value>,
+    step_results: &HashMap<String, FlowStepResult>,
+    from_step: &str,
+) -> RestoredState {
+    let cutoff = step_results.get(from_step).and_then(|r| r.start_time);
+    let mut restored: Vec<&FlowStepResult> = step_results.values()
+        .filter(|r| r.status == FlowStepStatus::Completed && r.step_id != from_step)
+        .filter(|r| match (cutoff, r.end_time) {
+            (Some(cutoff), Some(end)) => end <= cutoff,
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .collect();
+    restored.sort_by(|a, b| a.end_time.cmp(&b.end_time).then(a.step_id.cmp(&b.step_id)));
+
+    let mut state = RestoredState {
+        variables: variables.clone(),
+        ..Default::default()
+    };
+    state.variables.extend(input_data.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+    for record in restored {
+        if let Some(result) = &record.result {
+            if let Some(output) = result.get("output") {
+                state.variables.insert(format!("step_{}_output", record.step_id), output.clone());
+            }
+            if let Some(mapped) = result.get("mapped_variables").and_then(|v| v.as_object()) {
+                state.variables.extend(mapped.iter().map(|(k, v)| (k.clone(), v.clone())));
+            }
+        }
+        state.completed_steps.push(record.step_id.clone());
+        state.previous_step_id = Some(record.step_id.clone());
+        state.step_results.insert(record.step_id.clone(), record.clone());
+    }
+
+    state
+}
+
+/// Apply per-step parameter edits to a copy of the flow; a `null` value removes
+/// the parameter
+pub(crate) fn apply_parameter_overrides(
+    flow: &mut Flow,
+    overrides: &HashMap<String, HashMap<String, Value>>,
+) -> Result<(), String> {
+    for (step_id, parameters) in overrides {
+        let step = flow.steps.iter_mut()
+            .find(|s| &s.id == step_id)
+            .ok_or_else(|| format!("Step '{}' is not in flow '{}'", step_id, flow.name))?;
+        for (key, value) in parameters {
+            if value.is_null() {
+                step.parameters.remove(key);
+            } else {
+                step.parameters.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+
+    fn record(step_id: &str, status: FlowStepStatus, started_min: i64, result: Value) -> FlowStepResult {
+        let start = Utc::now() - Duration::minutes(60 - started_min);
+        FlowStepResult {
+            step_id: step_id.to_string(),
+            status,
+            result: Some(result),
+            error: None,
+            start_time: Some(start),
+            end_time: Some(start + Duration::seconds(30)),
+            execution_time_ms: Some(30_000),
+            retry_attempt: 0,
+            agent_output: None,
+        }
+    }
+
+    #[test]
+    fn test_restores_steps_completed_before_the_rerun_step() {
+        let results = HashMap::from([
+            ("plan".to_string(), record("plan", FlowStepStatus::Completed, 1, json!({"output": "the plan"}))),
+            ("fetch".to_string(), record("fetch", FlowStepStatus::Completed, 2,
+                json!({"output": {"id": 7}, "mapped_variables": {"ticket_id": 7}}))),
+            ("build".to_string(), record("build", FlowStepStatus::Failed, 3, json!({}))),
+            ("notify".to_string(), record("notify", FlowStepStatus::Completed, 4, json!({"output": "sent"}))),
+        ]);
+        let variables = HashMap::from([("repo".to_string(), json!("pods"))]);
+        let inputs = HashMap::from([("issue".to_string(), json!(42))]);
+
+        let state = restore_state(&variables, &inputs, &results, "build");
+        assert_eq!(state.completed_steps, vec!["plan", "fetch"]);
+        assert_eq!(state.previous_step_id.as_deref(), Some("fetch"));
+        assert_eq!(state.variables["step_plan_output"], json!("the plan"));
+        assert_eq!(state.variables["ticket_id"], json!(7));
+        assert_eq!(state.variables["issue"], json!(42));
+        assert_eq!(state.variables["repo"], json!("pods"));
+        assert!(!state.variables.contains_key("step_notify_output"));
+
+        // A step that never ran gets everything that completed
+        let state = restore_state(&variables, &inputs, &results, "review");
+        assert_eq!(state.completed_steps, vec!["plan", "fetch", "notify"]);
+    }
+
+    #[test]
+    fn test_parameter_overrides() {
+        let mut flow: Flow = serde_json::from_value(json!({
+            "user_id": "u1", "name": "Fix", "start_step_id": "a",
+            "steps": [{"id": "a", "name": "A", "parameters": {"task": "old", "output_retries": 2}}],
+        })).unwrap();
+
+        let overrides = HashMap::from([(
+            "a".to_string(),
+            HashMap::from([("task".to_string(), json!("new")), ("output_retries".to_string(), Value::Null)]),
+        )]);
+        apply_parameter_overrides(&mut flow, &overrides).unwrap();
+        assert_eq!(flow.steps[0].parameters.get("task"), Some(&json!("new")));
+        assert!(!flow.steps[0].parameters.contains_key("output_retries"));
+
+        let unknown = HashMap::from([("zz".to_string(), HashMap::new())]);
+        assert!(apply_parameter_overrides(&mut flow, &unknown).is_err());
+    }
+}
@@ -0,0 +1,430 @@
+//! Step debugger: breakpoints, pausing, stepping and variable edits.
+//!
+//! An execution started with `debug.breakpoints` pauses before each of those
+//! steps of its main run; steps inside parallel branches and map items run
+//! without stopping. Any running execution can be paused with
+//! `POST /api/executions/{id}/pause`, which takes effect before its next step.
+//!
+//! While `paused`, `GET /api/executions/{id}/variables` shows the variables the
+//! next step will see and `PATCH` edits them. `POST .../step` runs that step and
+//! pauses before the following one, `POST .../resume` runs on to the next
+//! breakpoint, and `PUT .../breakpoints` replaces the breakpoints at any time.
+//!
+//! The pause and the edited variables are kept in the execution's checkpoint, so
+//! an execution of a flow that resumes after a restart is paused again.
+
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::bson::doc;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+use crate::db::collections::*;
+use crate::error::AppError;
+use crate::models::flow::*;
+use crate::models::flow_events::*;
+use crate::services::execution_control::{ControlSignal, ExecutionControl};
+use crate::services::flow_service::{ExecutionCheckpoint, ExecutionScope, FlowExecutor, FlowService, StepRunError};
+
+/// Variables of an execution as its next step will see them
+#[derive(Debug, Serialize)]
+pub struct ExecutionVariables {
+    pub execution_id: String,
+    pub status: FlowExecutionStatus,
+    /// Step the variables were captured before
+    pub step_id: Option<String>,
+    pub paused: Option<PausedAt>,
+    pub variables: HashMap<String, Value>,
+}
+
+impl ExecutionVariables {
+    /// Read from the execution record's last checkpoint
+    pub fn from_document(exec_doc: &bson::Document) -> Self {
+        let checkpoint = exec_doc.get_document("checkpoint").ok()
+            .and_then(|c| bson::from_document::<ExecutionCheckpoint>(c.clone()).ok());
+        Self {
+            execution_id: exec_doc.get_object_id("_id").map(|id| id.to_hex()).unwrap_or_default(),
+            status: serde_json::from_value(json!(exec_doc.get_str("status").unwrap_or("pending")))
+                .unwrap_or(FlowExecutionStatus::Pending),
+            step_id: checkpoint.as_ref().map(|c| c.step_id.clone()),
+            paused: exec_doc.get_document("paused").ok()
+                .and_then(|d| bson::from_document(d.clone()).ok()),
+            variables: checkpoint.map(|c| c.variables).unwrap_or_default(),
+        }
+    }
+}
+
+/// Check that every breakpoint is a step of the flow
+pub(crate) fn check_breakpoints(flow: &Flow, debug: &DebugOptions) -> Result<(), AppError> {
+    match debug.breakpoints.iter().find(|id| !flow.steps.iter().any(|s| &s.id == *id)) {
+        Some(step_id) => Err(AppError::BadRequest(format!("Step '{}' is not in flow '{}'", step_id, flow.name))),
+        None => Ok(()),
+    }
+}
+
+/// Check an edit against the names variables can be stored under
+fn check_variables_update(update: &VariablesUpdate) -> Result<(), String> {
+    for name in update.set.keys().chain(&update.unset) {
+        if name.is_empty() || name.contains('.') || name.starts_with('$') {
+            return Err(format!("Invalid variable name '{}': it can't be empty, contain '.' or start with '$'", name));
+        }
+    }
+    match update.unset.iter().find(|name| update.set.contains_key(*name)) {
+        Some(name) => Err(format!("Variable '{}' is both set and unset", name)),
+        None => Ok(()),
+    }
+}
+
+fn apply_variable_edits(variables: &mut HashMap<String, Value>, set: HashMap<String, Value>, unset: &[String]) {
+    for name in unset {
+        variables.remove(name);
+    }
+    variables.extend(set);
+}
+
+impl FlowExecutor {
+    /// Pause before `step` when a breakpoint or a pause request says so, until
+    /// the execution is resumed. Only the main run pauses.
+    pub(crate) async fn pause_point(
+        &self,
+        step: &FlowStep,
+        execution_id: &str,
+        previous_step_id: Option<&str>,
+        scope: &mut ExecutionScope,
+    ) -> Result<(), StepRunError> {
+        if scope.branch_id.is_some() {
+            return Ok(());
+        }
+        let control = self.control(execution_id);
+        let Some(reason) = control.take_pause(&step.id) else {
+            return Ok(());
+        };
+        let Ok(oid) = ObjectId::parse_str(execution_id) else { return Ok(()) };
+        let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+
+        // Subscribe before pausing so a resume right after isn't missed
+        let mut signals = control.subscribe();
+        let paused = PausedAt { step_id: step.id.clone(), reason: reason.clone(), paused_at: Utc::now() };
+        let mut set = doc! {
+            "status": "paused",
+            "paused": bson::to_bson(&paused).unwrap_or(bson::Bson::Null),
+            "current_step_id": &step.id,
+            "updated_at": bson::DateTime::from_chrono(Utc::now()),
+        };
+        match bson::to_bson(&ExecutionCheckpoint::capture(&step.id, previous_step_id, scope)) {
+            Ok(checkpoint) => { set.insert("checkpoint", checkpoint); }
+            Err(e) => tracing::warn!(execution_id = %execution_id, error = %e, "Failed to serialize checkpoint"),
+        }
+        let _ = collection.update_one(doc! { "_id": oid }, doc! { "$set": set }).await;
+
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::ExecutionPaused,
+            step_id: Some(step.id.clone()),
+            message: match reason {
+                PauseReason::Breakpoint => format!("Execution paused at breakpoint before step '{}'", step.name),
+                _ => format!("Execution paused before step '{}'", step.name),
+            },
+            data: HashMap::from([("reason".to_string(), json!(reason))]),
+            timestamp: Utc::now(),
+        }).await;
+
+        if !self.wait_until_resumed(&control, &mut signals, &oid, scope).await {
+            return Err(StepRunError::Cancelled { step_id: step.id.clone() });
+        }
+
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::ExecutionResumed,
+            step_id: Some(step.id.clone()),
+            message: format!("Execution resumed at step '{}'", step.name),
+            data: HashMap::from([("reason".to_string(), json!("requested"))]),
+            timestamp: Utc::now(),
+        }).await;
+        Ok(())
+    }
+
+    /// Apply variable edits until a resume signal arrives. Returns false when
+    /// the execution is cancelled instead.
+    async fn wait_until_resumed(
+        &self,
+        control: &ExecutionControl,
+        signals: &mut broadcast::Receiver<ControlSignal>,
+        oid: &ObjectId,
+        scope: &mut ExecutionScope,
+    ) -> bool {
+        let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        loop {
+            let signal = tokio::select! {
+                biased;
+                _ = control.cancelled() => return false,
+                signal = signals.recv() => signal,
+            };
+            match signal {
+                Ok(ControlSignal::Resumed) => return true,
+                Ok(ControlSignal::VariablesEdited { set, unset }) => {
+                    apply_variable_edits(&mut scope.variables, set, &unset);
+                }
+                Ok(_) => {}
+                // Missed signals: the execution record has the edits and whether it was resumed
+                Err(broadcast::error::RecvError::Lagged(_) | broadcast::error::RecvError::Closed) => {
+                    let Ok(Some(exec_doc)) = collection.find_one(doc! { "_id": oid }).await else { continue };
+                    scope.variables = ExecutionVariables::from_document(&exec_doc).variables;
+                    if exec_doc.get_str("status") != Ok("paused") {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl FlowService {
+    /// Pause a running execution before its next step
+    pub async fn pause_execution(&self, execution_id: &str, user_id: &str) -> Result<(), AppError> {
+        let exec_doc = self.owned_execution(execution_id, user_id).await?;
+        match exec_doc.get_str("status") {
+            Ok("running" | "awaiting_input") => {}
+            Ok("paused") => return Err(AppError::Conflict("Execution is already paused".to_string())),
+            _ => return Err(AppError::Conflict("Execution is not running".to_string())),
+        }
+        let control = self.controls.get(execution_id)
+            .ok_or_else(|| AppError::Conflict("Execution is not running".to_string()))?;
+        control.request_pause(PauseReason::Request);
+        Ok(())
+    }
+
+    /// Let a paused execution go on; with `stepping` it pauses again before the
+    /// step after this one. Returns where it was paused.
+    pub async fn resume_execution(&self, execution_id: &str, user_id: &str, stepping: bool) -> Result<PausedAt, AppError> {
+        let oid = ObjectId::parse_str(execution_id)
+            .map_err(|_| AppError::BadRequest("Invalid execution ID".to_string()))?;
+        let control = self.controls.get(execution_id)
+            .ok_or_else(|| AppError::Conflict("Execution is not paused".to_string()))?;
+
+        let exec_doc = self.db().collection::<bson::Document>(FLOW_EXECUTIONS)
+            .find_one_and_update(
+                doc! { "_id": oid, "user_id": user_id, "status": "paused" },
+                doc! {
+                    "$set": { "status": "running", "updated_at": bson::DateTime::from_chrono(Utc::now()) },
+                    "$unset": { "paused": "" },
+                },
+            )
+            .await?;
+        let Some(exec_doc) = exec_doc else {
+            self.owned_execution(execution_id, user_id).await?;
+            return Err(AppError::Conflict("Execution is not paused".to_string()));
+        };
+        let paused: PausedAt = exec_doc.get_document("paused").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .ok_or_else(|| AppError::Internal("Paused execution has no pause record".to_string()))?;
+
+        if stepping {
+            control.request_pause(PauseReason::Step);
+        }
+        control.send(ControlSignal::Resumed);
+        Ok(paused)
+    }
+
+    /// The variables an execution's next step will see
+    pub async fn execution_variables(&self, execution_id: &str, user_id: &str) -> Result<ExecutionVariables, AppError> {
+        let exec_doc = self.owned_execution(execution_id, user_id).await?;
+        Ok(ExecutionVariables::from_document(&exec_doc))
+    }
+
+    /// Set and remove variables of a paused execution
+    pub async fn update_execution_variables(
+        &self,
+        execution_id: &str,
+        user_id: &str,
+        update: VariablesUpdate,
+    ) -> Result<ExecutionVariables, AppError> {
+        check_variables_update(&update).map_err(AppError::BadRequest)?;
+        let oid = ObjectId::parse_str(execution_id)
+            .map_err(|_| AppError::BadRequest("Invalid execution ID".to_string()))?;
+
+        let mut set = doc! { "updated_at": bson::DateTime::from_chrono(Utc::now()) };
+        for (name, value) in &update.set {
+            let value = bson::to_bson(value).map_err(|e| AppError::Internal(e.to_string()))?;
+            set.insert(format!("checkpoint.variables.{}", name), value);
+        }
+        let mut changes = doc! { "$set": set };
+        if !update.unset.is_empty() {
+            let unset: bson::Document = update.unset.iter()
+                .map(|name| (format!("checkpoint.variables.{}", name), bson::Bson::String(String::new())))
+                .collect();
+            changes.insert("$unset", unset);
+        }
+
+        let exec_doc = self.db().collection::<bson::Document>(FLOW_EXECUTIONS)
+            .find_one_and_update(doc! { "_id": oid, "user_id": user_id, "status": "paused" }, changes)
+            .return_document(mongodb::options::ReturnDocument::After)
+            .await?;
+        let Some(exec_doc) = exec_doc else {
+            self.owned_execution(execution_id, user_id).await?;
+            return Err(AppError::Conflict("Execution is not paused".to_string()));
+        };
+        let variables = ExecutionVariables::from_document(&exec_doc);
+
+        self.controls.send(execution_id, ControlSignal::VariablesEdited {
+            set: update.set.clone(),
+            unset: update.unset.clone(),
+        });
+        self.emit_event(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::VariablesUpdated,
+            step_id: variables.step_id.clone(),
+            message: format!("{} variable(s) set, {} removed", update.set.len(), update.unset.len()),
+            data: HashMap::from([
+                ("set".to_string(), json!(update.set)),
+                ("unset".to_string(), json!(update.unset)),
+            ]),
+            timestamp: Utc::now(),
+        }).await;
+
+        Ok(variables)
+    }
+
+    /// Replace the breakpoints of an execution that hasn't finished
+    pub async fn set_breakpoints(
+        &self,
+        execution_id: &str,
+        user_id: &str,
+        debug: DebugOptions,
+    ) -> Result<DebugOptions, AppError> {
+        let exec_doc = self.owned_execution(execution_id, user_id).await?;
+        if !matches!(exec_doc.get_str("status"), Ok("queued" | "running" | "awaiting_input" | "paused" | "waiting")) {
+            return Err(AppError::Conflict("Execution has finished".to_string()));
+        }
+        let flow = self.execution_flow(&exec_doc).await?;
+        check_breakpoints(&flow, &debug)?;
+
+        let mut breakpoints = Vec::new();
+        for step_id in debug.breakpoints {
+            if !breakpoints.contains(&step_id) {
+                breakpoints.push(step_id);
+            }
+        }
+        let debug = DebugOptions { breakpoints };
+
+        self.db().collection::<bson::Document>(FLOW_EXECUTIONS).update_one(
+            doc! { "_id": exec_doc.get_object_id("_id").map_err(|e| AppError::Internal(e.to_string()))? },
+            doc! { "$set": {
+                "debug": bson::to_bson(&debug).map_err(|e| AppError::Internal(e.to_string()))?,
+                "updated_at": bson::DateTime::from_chrono(Utc::now()),
+            } },
+        ).await?;
+        if let Some(control) = self.controls.get(execution_id) {
+            control.set_breakpoints(&debug.breakpoints);
+        }
+
+        self.emit_event(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::BreakpointsUpdated,
+            step_id: None,
+            message: format!("{} breakpoint(s) set", debug.breakpoints.len()),
+            data: HashMap::from([("breakpoints".to_string(), json!(debug.breakpoints))]),
+            timestamp: Utc::now(),
+        }).await;
+
+        Ok(debug)
+    }
+
+    /// Load the debug state saved on an execution into its control handle
+    /// before it starts. A paused execution pauses again where it was.
+    pub(crate) fn restore_debug_state(&self, execution_id: &str, exec_doc: &bson::Document) {
+        let control = self.controls.register(execution_id, None);
+        if let Some(debug) = exec_doc.get_document("debug").ok()
+            .and_then(|d| bson::from_document::<DebugOptions>(d.clone()).ok())
+        {
+            control.set_breakpoints(&debug.breakpoints);
+        }
+        if let Some(paused) = exec_doc.get_document("paused").ok()
+            .and_then(|d| bson::from_document::<PausedAt>(d.clone()).ok())
+        {
+            control.request_pause(paused.reason);
+        }
+    }
+
+    async fn owned_execution(&self, execution_id: &str, user_id: &str) -> Result<bson::Document, AppError> {
+        let oid = ObjectId::parse_str(execution_id)
+            .map_err(|_| AppError::BadRequest("Invalid execution ID".to_string()))?;
+        self.db().collection::<bson::Document>(FLOW_EXECUTIONS)
+            .find_one(doc! { "_id": oid, "user_id": user_id })
+            .await?
+            .ok_or_else(|| AppError::NotFound("Execution not found".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakpoints_must_be_flow_steps() {
+        let flow: Flow = serde_json::from_value(json!({
+            "user_id": "u", "name": "Fix issue", "start_step_id": "plan",
+            "steps": [{"id": "plan", "name": "Plan"}, {"id": "patch", "name": "Patch"}],
+        })).unwrap();
+        let debug = |ids: &[&str]| DebugOptions { breakpoints: ids.iter().map(|s| s.to_string()).collect() };
+
+        assert!(check_breakpoints(&flow, &debug(&["patch"])).is_ok());
+        assert!(check_breakpoints(&flow, &debug(&[])).is_ok());
+        let error = check_breakpoints(&flow, &debug(&["patch", "deploy"])).unwrap_err();
+        assert!(error.to_string().contains("'deploy'"));
+    }
+
+    #[test]
+    fn test_variable_edits() {
+        let update = VariablesUpdate {
+            set: HashMap::from([("branch".to_string(), json!("fix/42"))]),
+            unset: vec!["step_plan_output".to_string()],
+        };
+        assert!(check_variables_update(&update).is_ok());
+
+        let mut variables = HashMap::from([
+            ("branch".to_string(), json!("main")),
+            ("step_plan_output".to_string(), json!("...")),
+            ("issue".to_string(), json!(42)),
+        ]);
+        apply_variable_edits(&mut variables, update.set, &update.unset);
+        assert_eq!(variables, HashMap::from([
+            ("branch".to_string(), json!("fix/42")),
+            ("issue".to_string(), json!(42)),
+        ]));
+
+        for name in ["", "a.b", "$where"] {
+            let update = VariablesUpdate { set: HashMap::from([(name.to_string(), json!(1))]), unset: vec![] };
+            assert!(check_variables_update(&update).is_err(), "{:?} accepted", name);
+        }
+        let both = VariablesUpdate { set: HashMap::from([("x".to_string(), json!(1))]), unset: vec!["x".to_string()] };
+        assert!(check_variables_update(&both).is_err());
+    }
+
+    #[test]
+    fn test_variables_read_from_checkpoint() {
+        let oid = ObjectId::new();
+        let exec_doc = doc! {
+            "_id": oid,
+            "status": "paused",
+            "paused": { "step_id": "patch", "reason": "breakpoint", "paused_at": "2026-01-05T10:00:00Z" },
+            "checkpoint": {
+                "step_id": "patch",
+                "variables": { "issue": 42, "step_plan_output": "Edit src/lib.rs" },
+                "checkpointed_at": "2026-01-05T10:00:00Z",
+            },
+        };
+        let view = ExecutionVariables::from_document(&exec_doc);
+        assert_eq!(view.execution_id, oid.to_hex());
+        assert_eq!(view.status, FlowExecutionStatus::Paused);
+        assert_eq!(view.step_id.as_deref(), Some("patch"));
+        assert_eq!(view.paused.unwrap().reason, PauseReason::Breakpoint);
+        assert_eq!(view.variables["step_plan_output"], json!("Edit src/lib.rs"));
+    }
+}
@@ -0,0 +1,679 @@
+//! Expression language for condition steps.
+//!
+//! Expressions are parsed into a small AST and evaluated against the execution's
+//! variables; they cannot call out or mutate anything. Supported syntax:
+//! - literals: `0.8`, `"text"` / `'text'`, `true`, `false`, `null`, `[1, 2]`
+//! - paths: `score`, `steps.review.output.verdict`, `items[0].name`, `steps["qa-testing"].output`,
+//!   and the template forms `{{score}}` / `${score}`
+//! - comparisons: `==`, `!=`, `<`, `<=`, `>`, `>=`, `contains`, `matches` (regex), `in`
+//! - boolean logic: `&&` / `and`, `||` / `or`, `!` / `not`, parentheses
+//! - functions: `number(x)`, `string(x)`, `len(x)`, `lower(x)`, `upper(x)`, `trim(x)`, `exists(x)`
+//!
+//! `steps.<id>.output` (and `.error`, `.status`) expose step results. When a path walks
+//! into a string holding JSON (e.g. an LLM reply), the string is parsed and walked.
+//! Numeric strings compare as numbers, so `{{score}} > 0.8` works when score is `"0.9"`.
+
+use regex::RegexBuilder;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+const MAX_EXPRESSION_LEN: usize = 4096;
+const MAX_NESTING_DEPTH: usize = 64;
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+const FUNCTIONS: &[&str] = &["number", "string", "len", "lower", "upper", "trim", "exists"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    Matches,
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    List(Vec<Expr>),
+    Path(Vec<PathSegment>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// Parse an expression, rejecting syntax errors, unknown functions and invalid regexes
+pub fn parse(source: &str) -> Result<Expr, String> {
+    if source.len() > MAX_EXPRESSION_LEN {
+        return Err(format!("Expression is longer than {} characters", MAX_EXPRESSION_LEN));
+    }
+    let tokens = tokenize(source)?;
+    if tokens.is_empty() {
+        return Err("Expression is empty".to_string());
+    }
+    let mut parser = Parser { tokens, pos: 0, depth: 0 };
+    let expr = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("Unexpected {} after end of expression", token.describe()));
+    }
+    Ok(expr)
+}
+
+/// Check that an expression is valid without evaluating it
+pub fn validate(source: &str) -> Result<(), String> {
+    parse(source).map(|_| ())
+}
+
+/// Evaluate an expression against execution variables and return whether it holds
+pub fn evaluate_condition(source: &str, variables: &HashMap<String, Value>) -> Result<bool, String> {
+    let expr = parse(source)?;
+    let context = build_context(variables);
+    Ok(is_truthy(&evaluate(&expr, &context)?))
+}
+
+/// Root object that paths are resolved against: every variable by name, the
+/// same map under `variables`, and step results under `steps.<id>`.
+pub fn build_context(variables: &HashMap<String, Value>) -> Value {
+    let mut steps: Map<String, Value> = Map::new();
+    for (name, value) in variables {
+        let Some(rest) = name.strip_prefix("step_") else { continue };
+        for field in ["output", "error", "status"] {
+            if let Some(step_id) = rest.strip_suffix(&format!("_{}", field)) {
+                let entry = steps.entry(step_id.to_string()).or_insert_with(|| Value::Object(Map::new()));
+                if let Some(obj) = entry.as_object_mut() {
+                    obj.insert(field.to_string(), value.clone());
+                }
+            }
+        }
+    }
+
+    let mut root: Map<String, Value> = variables.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    let all_variables = Value::Object(root.clone());
+    root.entry("variables".to_string()).or_insert(all_variables);
+    root.entry("steps".to_string()).or_insert(Value::Object(steps));
+    Value::Object(root)
+}
+
+pub fn evaluate(expr: &Expr, context: &Value) -> Result<Value, String> {
+    match expr {
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::List(items) => items.iter().map(|item| evaluate(item, context)).collect::<Result<Vec<_>, _>>().map(Value::Array),
+        Expr::Path(segments) => Ok(resolve_path(context, segments)),
+        Expr::Not(inner) => Ok(Value::Bool(!is_truthy(&evaluate(inner, context)?))),
+        Expr::And(left, right) => Ok(Value::Bool(
+            is_truthy(&evaluate(left, context)?) && is_truthy(&evaluate(right, context)?),
+        )),
+        Expr::Or(left, right) => Ok(Value::Bool(
+            is_truthy(&evaluate(left, context)?) || is_truthy(&evaluate(right, context)?),
+        )),
+        Expr::Compare(op, left, right) => {
+            let left = evaluate(left, context)?;
+            let right = evaluate(right, context)?;
+            compare(*op, &left, &right).map(Value::Bool)
+        }
+        Expr::Call(name, args) => {
+            let args = args.iter().map(|arg| evaluate(arg, context)).collect::<Result<Vec<_>, _>>()?;
+            call_function(name, &args)
+        }
+    }
+}
+
+/// Truthiness used for conditions: null, false, 0, empty collections and the
+/// strings "", "false", "no" and "0" are false.
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !matches!(s.trim().to_lowercase().as_str(), "" | "false" | "no" | "0"),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn resolve_path(root: &Value, segments: &[PathSegment]) -> Value {
+    let mut current = root.clone();
+    for segment in segments {
+        if let Value::String(text) = &current {
+            match parse_embedded_json(text) {
+                Some(parsed) => current = parsed,
+                None => return Value::Null,
+            }
+        }
+        current = match (&current, segment) {
+            (Value::Object(map), PathSegment::Key(key)) => map.get(key).cloned(),
+            (Value::Object(map), PathSegment::Index(index)) => map.get(&index.to_string()).cloned(),
+            (Value::Array(items), PathSegment::Index(index)) => items.get(*index).cloned(),
+            (Value::Array(items), PathSegment::Key(key)) => key.parse::<usize>().ok().and_then(|i| items.get(i).cloned()),
+            _ => None,
+        }
+        .unwrap_or(Value::Null);
+    }
+    current
+}
+
+/// Parse a string that holds a JSON object or array, optionally inside a code fence
+fn parse_embedded_json(text: &str) -> Option<Value> {
+    let trimmed = text.trim();
+    let unfenced = trimmed.strip_prefix("```")
+        .map(|rest| rest.trim_start_matches("json").trim_end_matches("```").trim())
+        .unwrap_or(trimmed);
+    if !(unfenced.starts_with('{') || unfenced.starts_with('[')) {
+        return None;
+    }
+    serde_json::from_str(unfenced).ok()
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Equality that treats numeric strings as numbers and "true"/"false" as booleans
+pub fn loose_eq(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(_), _) | (_, Value::Number(_)) => match (as_number(left), as_number(right)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        },
+        (Value::Bool(b), Value::String(s)) | (Value::String(s), Value::Bool(b)) => {
+            s.trim().eq_ignore_ascii_case(if *b { "true" } else { "false" })
+        }
+        _ => left == right,
+    }
+}
+
+fn compare(op: CompareOp, left: &Value, right: &Value) -> Result<bool, String> {
+    match op {
+        CompareOp::Eq => Ok(loose_eq(left, right)),
+        CompareOp::Ne => Ok(!loose_eq(left, right)),
+        CompareOp::Lt | CompareOp::Le | CompareOp::Gt | CompareOp::Ge => {
+            let ordering = match (as_number(left), as_number(right)) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => match (left, right) {
+                    (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                    _ => return Err(format!("Cannot compare {} with {}", type_name(left), type_name(right))),
+                },
+            };
+            let Some(ordering) = ordering else { return Ok(false) };
+            Ok(match op {
+                CompareOp::Lt => ordering.is_lt(),
+                CompareOp::Le => ordering.is_le(),
+                CompareOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+        CompareOp::Contains => Ok(contains(left, right)),
+        CompareOp::In => Ok(contains(right, left)),
+        CompareOp::Matches => {
+            let pattern = right.as_str()
+                .ok_or_else(|| format!("Right side of 'matches' must be a string, got {}", type_name(right)))?;
+            let regex = compile_regex(pattern)?;
+            Ok(regex.is_match(&as_text(left)))
+        }
+    }
+}
+
+fn contains(haystack: &Value, needle: &Value) -> bool {
+    match haystack {
+        Value::String(s) => s.contains(&as_text(needle)),
+        Value::Array(items) => items.iter().any(|item| loose_eq(item, needle)),
+        Value::Object(map) => needle.as_str().is_some_and(|key| map.contains_key(key)),
+        _ => false,
+    }
+}
+
+fn compile_regex(pattern: &str) -> Result<regex::Regex, String> {
+    RegexBuilder::new(pattern)
+        .size_limit(MAX_REGEX_SIZE)
+        .build()
+        .map_err(|e| format!("Invalid regex '{}': {}", pattern, e))
+}
+
+fn call_function(name: &str, args: &[Value]) -> Result<Value, String> {
+    let arg = args.first().unwrap_or(&Value::Null);
+    Ok(match name {
+        "number" => as_number(arg)
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        "string" => Value::String(as_text(arg)),
+        "len" => Value::from(match arg {
+            Value::String(s) => s.chars().count(),
+            Value::Array(items) => items.len(),
+            Value::Object(map) => map.len(),
+            _ => 0,
+        }),
+        "lower" => Value::String(as_text(arg).to_lowercase()),
+        "upper" => Value::String(as_text(arg).to_uppercase()),
+        "trim" => Value::String(as_text(arg).trim().to_string()),
+        "exists" => Value::Bool(!arg.is_null()),
+        other => return Err(format!("Unknown function '{}'", other)),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Lexer
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    /// `{{path}}` or `${path}`
+    Template(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Dot,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => format!("number {}", n),
+            Token::Str(s) => format!("string \"{}\"", s),
+            Token::Ident(name) => format!("'{}'", name),
+            Token::Template(path) => format!("'{{{{{}}}}}'", path),
+            Token::Op(op) => format!("'{}'", op),
+            Token::LParen => "'('".to_string(),
+            Token::RParen => "')'".to_string(),
+            Token::LBracket => "'['".to_string(),
+            Token::RBracket => "']'".to_string(),
+            Token::Comma => "','".to_string(),
+            Token::Dot => "'.'".to_string(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '{' if next == Some('{') => {
+                let (inner, end) = read_until(&chars, i + 2, "}}")
+                    .ok_or_else(|| format!("Unclosed '{{{{' at position {}", i))?;
+                tokens.push(Token::Template(inner.trim().to_string()));
+                i = end;
+            }
+            '$' if next == Some('{') => {
+                let (inner, end) = read_until(&chars, i + 2, "}")
+                    .ok_or_else(|| format!("Unclosed '${{' at position {}", i))?;
+                tokens.push(Token::Template(inner.trim().to_string()));
+                i = end;
+            }
+            '"' | '\'' => {
+                let mut text = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j) {
+                        None => return Err(format!("Unterminated string starting at position {}", i)),
+                        Some('\\') => {
+                            match chars.get(j + 1) {
+                                Some('n') => text.push('\n'),
+                                Some('t') => text.push('\t'),
+                                Some(escaped) => text.push(*escaped),
+                                None => return Err(format!("Unterminated string starting at position {}", i)),
+                            }
+                            j += 2;
+                        }
+                        Some(q) if *q == c => break,
+                        Some(other) => {
+                            text.push(*other);
+                            j += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Str(text));
+                i = j + 1;
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let start = i;
+                // After a '.', digits are an array index (`items.0.name`), not a decimal
+                let allow_fraction = tokens.last() != Some(&Token::Dot);
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                if allow_fraction && chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()) {
+                    i += 1;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = text.parse::<f64>().map_err(|_| format!("Invalid number '{}'", text))?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                // Hyphens are allowed inside identifiers so step IDs like `qa-testing` work
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '-') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            '(' => { tokens.push(Token::LParen); i += 1; }
+            ')' => { tokens.push(Token::RParen); i += 1; }
+            '[' => { tokens.push(Token::LBracket); i += 1; }
+            ']' => { tokens.push(Token::RBracket); i += 1; }
+            ',' => { tokens.push(Token::Comma); i += 1; }
+            '.' => { tokens.push(Token::Dot); i += 1; }
+            _ => {
+                let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+                let op = match two.as_str() {
+                    "==" => "==", "!=" => "!=", "<=" => "<=", ">=" => ">=", "&&" => "&&", "||" => "||",
+                    _ => match c {
+                        '<' => "<", '>' => ">", '!' => "!",
+                        _ => return Err(format!("Unexpected character '{}' at position {}", c, i)),
+                    },
+                };
+                tokens.push(Token::Op(op));
+                i += op.len();
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn read_until(chars: &[char], start: usize, terminator: &str) -> Option<(String, usize)> {
+    let term: Vec<char> = terminator.chars().collect();
+    (start..chars.len())
+        .find(|&j| chars[j..].starts_with(&term))
+        .map(|j| (chars[start..j].iter().collect(), j + term.len()))
+}
+
+// ---------------------------------------------------------------------------
+// Parser
+// ---------------------------------------------------------------------------
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(ref token) if *token == expected => Ok(()),
+            Some(token) => Err(format!("Expected {} but found {}", expected.describe(), token.describe())),
+            None => Err(format!("Expected {} but the expression ended", expected.describe())),
+        }
+    }
+
+    fn peek_keyword(&self, keywords: &[&str]) -> bool {
+        match self.peek() {
+            Some(Token::Op(op)) => keywords.contains(op),
+            Some(Token::Ident(name)) => keywords.contains(&name.to_lowercase().as_str()),
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err("Expression is nested too deeply".to_string());
+        }
+        let mut left = self.parse_and()?;
+        while self.peek_keyword(&["||", "or"]) {
+            self.next();
+            left = Expr::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_not()?;
+        while self.peek_keyword(&["&&", "and"]) {
+            self.next();
+            left = Expr::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, String> {
+        if self.peek_keyword(&["!", "not"]) {
+            self.next();
+            self.depth += 1;
+            if self.depth > MAX_NESTING_DEPTH {
+                return Err("Expression is nested too deeply".to_string());
+            }
+            let inner = self.parse_not()?;
+            self.depth -= 1;
+            return Ok(Expr::Not(Box::new(inner)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let left = self.parse_primary()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => CompareOp::Eq,
+            Some(Token::Op("!=")) => CompareOp::Ne,
+            Some(Token::Op("<")) => CompareOp::Lt,
+            Some(Token::Op("<=")) => CompareOp::Le,
+            Some(Token::Op(">")) => CompareOp::Gt,
+            Some(Token::Op(">=")) => CompareOp::Ge,
+            Some(Token::Ident(name)) => match name.to_lowercase().as_str() {
+                "contains" => CompareOp::Contains,
+                "matches" => CompareOp::Matches,
+                "in" => CompareOp::In,
+                _ => return Ok(left),
+            },
+            _ => return Ok(left),
+        };
+        self.next();
+        let right = self.parse_primary()?;
+
+        // Regex literals are compiled up front so bad patterns are rejected on save
+        if let (CompareOp::Matches, Expr::Literal(Value::String(pattern))) = (op, &right) {
+            compile_regex(pattern)?;
+        }
+
+        Ok(Expr::Compare(op, Box::new(left), Box::new(right)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(n)) => serde_json::Number::from_f64(n)
+                .map(|n| Expr::Literal(Value::Number(n)))
+                .ok_or_else(|| format!("Invalid number {}", n)),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Template(path)) => parse_template_path(&path),
+            Some(Token::LParen) => {
+                let inner = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Some(Token::LBracket) => {
+                let mut items = Vec::new();
+                if self.peek() != Some(&Token::RBracket) {
+                    loop {
+                        items.push(self.parse_or()?);
+                        if self.peek() == Some(&Token::Comma) {
+                            self.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect(Token::RBracket)?;
+                Ok(Expr::List(items))
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ if self.peek() == Some(&Token::LParen) => self.parse_call(name),
+                _ => self.parse_path(name),
+            },
+            Some(token) => Err(format!("Unexpected {}", token.describe())),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+
+    fn parse_call(&mut self, name: String) -> Result<Expr, String> {
+        if !FUNCTIONS.contains(&name.as_str()) {
+            return Err(format!("Unknown function '{}' (available: {})", name, FUNCTIONS.join(", ")));
+        }
+        self.expect(Token::LParen)?;
+        let arg = self.parse_or()?;
+        self.expect(Token::RParen)?;
+        Ok(Expr::Call(name, vec![arg]))
+    }
+
+    fn parse_path(&mut self, first: String) -> Result<Expr, String> {
+        let mut segments = vec![PathSegment::Key(first)];
+        loop {
+            match self.peek() {
+                Some(Token::Dot) => {
+                    self.next();
+                    match self.next() {
+                        Some(Token::Ident(key)) => segments.push(PathSegment::Key(key)),
+                        Some(Token::Number(n)) if n >= 0.0 && n.fract() == 0.0 => segments.push(PathSegment::Index(n as usize)),
+                        Some(token) => return Err(format!("Expected a field name after '.', found {}", token.describe())),
+                        None => return Err("Expected a field name after '.'".to_string()),
+                    }
+                }
+                Some(Token::LBracket) => {
+                    self.next();
+                    match self.next() {
+                        Some(Token::Str(key)) => segments.push(PathSegment::Key(key)),
+                        Some(Token::Number(n)) if n >= 0.0 && n.fract() == 0.0 => segments.push(PathSegment::Index(n as usize)),
+                        Some(token) => return Err(format!("Expected an index or quoted key in '[]', found {}", token.describe())),
+                        None => return Err("Expected an index or quoted key in '[]'".to_string()),
+                    }
+                    self.expect(Token::RBracket)?;
+                }
+                _ => return Ok(Expr::Path(segments)),
+            }
+        }
+    }
+}
+
+/// `{{steps.review.output}}` is the same as writing the path directly
+fn parse_template_path(path: &str) -> Result<Expr, String> {
+    let tokens = tokenize(path)?;
+    let mut parser = Parser { tokens, pos: 0, depth: 0 };
+    match parser.next() {
+        Some(Token::Ident(first)) => {
+            let expr = parser.parse_path(first)?;
+            match parser.peek() {
+                None => Ok(expr),
+                Some(token) => Err(format!("Unexpected {} in placeholder '{{{{{}}}}}'", token.describe(), path)),
+            }
+        }
+        _ => Err(format!("Invalid placeholder '{{{{{}}}}}'", path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_comparisons_and_boolean_logic() {
+        let v = vars(json!({"score": "0.65", "count": 3, "status": "open", "approved": true}));
+        assert!(!evaluate_condition("{{score}} > 0.8", &v).unwrap());
+        assert!(evaluate_condition("${score} <= 0.65 && count == 3", &v).unwrap());
+        assert!(evaluate_condition("status != 'closed' and not (count < 2 or approved == false)", &v).unwrap());
+        assert!(evaluate_condition("approved", &v).unwrap());
+        assert!(!evaluate_condition("missing", &v).unwrap());
+        assert!(evaluate_condition("number(score) >= 0.5", &v).unwrap());
+        assert!(evaluate_condition("len(status) == 4 && upper(status) == 'OPEN'", &v).unwrap());
+    }
+
+    #[test]
+    fn test_step_output_paths_and_string_operators() {
+        let v = vars(json!({
+            "step_review_output": "```json\n{\"verdict\": \"approve\", \"issues\": [\"naming\"]}\n```",
+            "step_qa-testing_output": {"tests": [{"name": "unit", "passed": true}]},
+            "tags": ["backend", "urgent"],
+        }));
+        assert!(evaluate_condition(r#"steps.review.output.verdict == "approve""#, &v).unwrap());
+        assert!(evaluate_condition("steps.review.output.issues contains 'naming'", &v).unwrap());
+        assert!(evaluate_condition("steps.qa-testing.output.tests[0].passed", &v).unwrap());
+        assert!(evaluate_condition(r#"steps["qa-testing"].output.tests.0.name == 'unit'"#, &v).unwrap());
+        assert!(evaluate_condition("'urgent' in tags && steps.review.output.verdict matches '^app'", &v).unwrap());
+        assert!(evaluate_condition("'bug' in ['bug', 'fix']", &v).unwrap());
+        assert!(!evaluate_condition("exists(steps.missing.output)", &v).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_expressions_are_rejected() {
+        assert!(validate("score >").is_err());
+        assert!(validate("(score > 1").is_err());
+        assert!(validate("score = 1").is_err());
+        assert!(validate("system('rm -rf /')").is_err());
+        assert!(validate("name matches '(unclosed'").is_err());
+        assert!(validate("{{score").is_err());
+        assert!(validate("").is_err());
+        assert!(validate("score > 1 extra").is_err());
+        assert!(validate(&"(".repeat(100)).is_err());
+
+        let v = vars(json!({"items": [1, 2]}));
+        assert!(evaluate_condition("items > 1", &v).is_err());
+    }
+}
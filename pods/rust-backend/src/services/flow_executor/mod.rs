@@ -1,2 +1,5 @@
 // TODO: Phase 8 - FlowExecutor, execute_flow
+pub mod debugger;
+pub mod expression;
+pub mod retry;
 pub mod step_handlers;
@@ -0,0 +1,196 @@
+//! Step retry policy, built from `FlowStep.retry_count` and step parameters.
+//!
+//! Parameters:
+//! - `retry_backoff`: `fixed` or `exponential` (default `exponential`)
+//! - `retry_delay_seconds`: delay before the first retry (default 2)
+//! - `retry_max_delay_seconds`: cap for exponential backoff (default 60)
+//! - `retry_jitter`: randomize each delay between 50% and 100% (default true)
+//! - `retryable_errors`: only retry errors matching one of these
+//! - `fatal_errors`: never retry errors matching one of these
+//!
+//! Error patterns are case-insensitive substrings of the error message, or one of
+//! the categories `rate_limit` (429), `server_error` (5xx), `timeout` and `network`.
+//!
+//! Rejected approvals and expired approval, human input and signal waits are
+//! never retried, also when they surface through a sub-flow: a retry would ask
+//! people again for a decision they already made.
+
+use std::time::Duration;
+
+use crate::models::flow::FlowStep;
+
+const DEFAULT_RETRY_DELAY_SECS: f64 = 2.0;
+const DEFAULT_MAX_RETRY_DELAY_SECS: f64 = 60.0;
+
+/// Errors of steps that wait on a decision, which retrying would ask for again
+const DECISION_OUTCOMES: &[&str] = &[
+    "approval rejected",
+    "approval timed out",
+    "timed out waiting for input",
+    "timed out waiting for signal",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    Fixed,
+    Exponential,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: Backoff,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: bool,
+    pub retryable_errors: Vec<String>,
+    pub fatal_errors: Vec<String>,
+}
+
+impl RetryPolicy {
+    pub fn from_step(step: &FlowStep) -> Self {
+        let params = &step.parameters;
+        let string_list = |key: &str| -> Vec<String> {
+            params.get(key)
+                .and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_lowercase())).collect())
+                .unwrap_or_default()
+        };
+        let seconds = |key: &str, default: f64| -> Duration {
+            Duration::from_secs_f64(params.get(key).and_then(|v| v.as_f64()).unwrap_or(default).max(0.0))
+        };
+
+        Self {
+            max_retries: step.retry_count.max(0) as u32,
+            backoff: match params.get("retry_backoff").and_then(|v| v.as_str()) {
+                Some("fixed") => Backoff::Fixed,
+                _ => Backoff::Exponential,
+            },
+            base_delay: seconds("retry_delay_seconds", DEFAULT_RETRY_DELAY_SECS),
+            max_delay: seconds("retry_max_delay_seconds", DEFAULT_MAX_RETRY_DELAY_SECS),
+            jitter: params.get("retry_jitter").and_then(|v| v.as_bool()).unwrap_or(true),
+            retryable_errors: string_list("retryable_errors"),
+            fatal_errors: string_list("fatal_errors"),
+        }
+    }
+
+    /// Whether `error` may be retried. Cancellations, decision outcomes and
+    /// fatal patterns win over retryable ones; without a retryable list every
+    /// other error is retried.
+    pub fn is_retryable(&self, error: &str) -> bool {
+        if error == "Execution cancelled"
+            || DECISION_OUTCOMES.iter().any(|p| error_matches(p, error))
+            || self.fatal_errors.iter().any(|p| error_matches(p, error))
+        {
+            return false;
+        }
+        self.retryable_errors.is_empty() || self.retryable_errors.iter().any(|p| error_matches(p, error))
+    }
+
+    /// Delay before retry number `retry` (1-based)
+    pub fn delay_for(&self, retry: u32) -> Duration {
+        let delay = match self.backoff {
+            Backoff::Fixed => self.base_delay,
+            Backoff::Exponential => self.base_delay
+                .checked_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+                .unwrap_or(self.max_delay)
+                .min(self.max_delay),
+        };
+        if self.jitter {
+            delay.mul_f64(0.5 + random_fraction() / 2.0)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Match an error message against a category name or a substring
+pub fn error_matches(pattern: &str, error: &str) -> bool {
+    let error = error.to_lowercase();
+    match pattern {
+        "rate_limit" => error.contains("429") || error.contains("rate limit") || error.contains("too many requests"),
+        "server_error" => status_codes(&error).any(|code| (500..600).contains(&code)),
+        "timeout" => error.contains("timed out") || error.contains("timeout"),
+        "network" => error.contains("request failed") || error.contains("connection"),
+        substring => error.contains(substring),
+    }
+}
+
+/// Three-digit numbers in an error message that look like HTTP status codes
+fn status_codes(error: &str) -> impl Iterator<Item = u16> + '_ {
+    error.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|token| token.len() == 3)
+        .filter_map(|token| token.parse::<u16>().ok())
+}
+
+fn random_fraction() -> f64 {
+    let mut bytes = [0u8; 8];
+    if getrandom::fill(&mut bytes).is_err() {
+        return 1.0;
+    }
+    (u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn step_with(retry_count: i32, params: serde_json::Value) -> FlowStep {
+        serde_json::from_value(json!({
+            "id": "s1", "name": "Step", "type": "llm",
+            "retry_count": retry_count, "parameters": params,
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        let policy = RetryPolicy::from_step(&step_with(3, json!({
+            "retry_delay_seconds": 1, "retry_max_delay_seconds": 3, "retry_jitter": false,
+        })));
+        assert_eq!(policy.max_retries, 3);
+        assert_eq!(policy.delay_for(1), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(2), Duration::from_secs(2));
+        assert_eq!(policy.delay_for(3), Duration::from_secs(3));
+
+        let fixed = RetryPolicy::from_step(&step_with(2, json!({"retry_backoff": "fixed", "retry_delay_seconds": 5})));
+        for _ in 0..20 {
+            let delay = fixed.delay_for(2);
+            assert!(delay >= Duration::from_millis(2500) && delay <= Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let policy = RetryPolicy::from_step(&step_with(2, json!({
+            "retryable_errors": ["rate_limit", "server_error", "timeout"],
+            "fatal_errors": ["invalid api key"],
+        })));
+        assert!(policy.is_retryable("API error 429 Too Many Requests: slow down"));
+        assert!(policy.is_retryable("Anthropic API error 503 Service Unavailable: overloaded"));
+        assert!(policy.is_retryable("Tool execution timed out after 120s: search"));
+        assert!(!policy.is_retryable("API error 400 Bad Request: missing field"));
+        assert!(!policy.is_retryable("API error 500: Invalid API key"));
+
+        let default_policy = RetryPolicy::from_step(&step_with(1, json!({})));
+        assert!(default_policy.is_retryable("anything at all"));
+        assert!(!default_policy.is_retryable("Execution cancelled"));
+    }
+
+    #[test]
+    fn test_decisions_are_never_retried() {
+        let policies = [
+            RetryPolicy::from_step(&step_with(3, json!({}))),
+            RetryPolicy::from_step(&step_with(3, json!({"retryable_errors": ["timeout"]}))),
+        ];
+        for policy in &policies {
+            assert!(!policy.is_retryable("Approval rejected: needs tests"));
+            assert!(!policy.is_retryable("Approval timed out after 300s"));
+            assert!(!policy.is_retryable("Timed out waiting for input after 60s"));
+            assert!(!policy.is_retryable("Timed out waiting for signal 'deploy_done' after 600s"));
+            assert!(!policy.is_retryable("Sub-flow 'release' failed: Step 'Sign-off' failed: Approval rejected"));
+        }
+        assert!(policies[0].is_retryable("Step timed out after 30s"));
+        assert!(policies[1].is_retryable("Step timed out after 30s"));
+    }
+}
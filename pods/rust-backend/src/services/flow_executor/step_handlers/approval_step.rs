@@ -1 +1,562 @@
-// TODO: Phase 8 - Human approval gate step
+//! Approval step: waits for one or more people to approve or reject.
+//!
+//! Parameters:
+//! - `message`: what to review, templated (defaults to the step description or name)
+//! - `approvers`: usernames allowed to decide; only the execution's owner when omitted
+//! - `required_approvals`: approvals needed to continue (default 1)
+//! - `target_step_id`: step whose output approvers review and may edit
+//!   (defaults to the previous step)
+//! - `rework_step_id`: step a rejection continues at; without it a rejection
+//!   fails the execution
+//!
+//! While waiting, the request is listed in the execution's `pending_approvals`
+//! under the step ID, so approval steps in parallel branches and map items wait
+//! side by side. Decisions are sent to `POST /api/executions/{id}/approve` with
+//! the request's `step_id` when several are waiting, an optional `comment` and,
+//! when approving, an `edited_output` that replaces the target step's output for
+//! the rest of the run. The step is approved once
+//! `required_approvals` approvers agree, and rejected as soon as that can no
+//! longer happen. Each decision records who took it and when, in the
+//! execution's `approval_history`. The step's `timeout_seconds` bounds the wait
+//! (none when 0). When an execution resumes at an approval step whose request
+//! is still open, it keeps waiting on that request and the decisions already
+//! taken.
+
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::bson::doc;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+use crate::auth::middleware::AuthUser;
+use crate::db::collections::{FLOW_EXECUTIONS, USERS};
+use crate::error::AppError;
+use crate::models::flow::{ApprovalDecision, ApprovalSubmission, FlowStep, PendingApproval};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::execution_control::{next_signal, ControlSignal};
+use crate::services::flow_service::{resolve_variables, ExecutionScope, FlowExecutor, FlowService};
+
+#[derive(Debug, Clone, Default)]
+pub struct ApprovalConfig {
+    pub message: Option<String>,
+    pub approvers: Vec<String>,
+    pub required_approvals: u32,
+    pub target_step_id: Option<String>,
+    pub rework_step_id: Option<String>,
+}
+
+impl ApprovalConfig {
+    pub fn from_parameters(parameters: &HashMap<String, Value>) -> Result<Self, String> {
+        let string_param = |key: &str| parameters.get(key).and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from);
+
+        let mut approvers: Vec<String> = match parameters.get("approvers") {
+            Some(list) => serde_json::from_value::<Vec<String>>(list.clone())
+                .map_err(|_| "approvers must be a list of usernames".to_string())?
+                .into_iter()
+                .map(|a| a.trim().to_string())
+                .filter(|a| !a.is_empty())
+                .collect(),
+            None => Vec::new(),
+        };
+        approvers.sort();
+        approvers.dedup();
+
+        let required_approvals = match parameters.get("required_approvals") {
+            Some(n) => n.as_u64().filter(|n| *n >= 1)
+                .ok_or("required_approvals must be a positive integer")? as u32,
+            None => 1,
+        };
+        let eligible = approvers.len().max(1) as u32;
+        if required_approvals > eligible {
+            return Err(format!(
+                "required_approvals is {} but only {} approver(s) can decide",
+                required_approvals, eligible
+            ));
+        }
+
+        Ok(Self {
+            message: string_param("message"),
+            approvers,
+            required_approvals,
+            target_step_id: string_param("target_step_id"),
+            rework_step_id: string_param("rework_step_id"),
+        })
+    }
+}
+
+impl PendingApproval {
+    /// Whether this user may decide: a listed approver, or the owner when
+    /// nobody is listed
+    pub fn can_decide(&self, user: &AuthUser, owner_id: &str) -> bool {
+        if self.approvers.is_empty() {
+            user.id == owner_id
+        } else {
+            self.approvers.contains(&user.username)
+        }
+    }
+
+    /// `Some(true)` once enough approvers agree, `Some(false)` once enough
+    /// have rejected that approval can no longer be reached
+    pub fn outcome(&self) -> Option<bool> {
+        let approvals = self.decisions.iter().filter(|d| d.approved).count() as u32;
+        let rejections = self.decisions.len() as u32 - approvals;
+        let eligible = self.approvers.len().max(1) as u32;
+        if approvals >= self.required_approvals {
+            Some(true)
+        } else if eligible.saturating_sub(rejections) < self.required_approvals {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// Key of a step's request in `pending_approvals`. Map items run the same steps,
+/// so theirs carry the item index.
+fn request_key(step_id: &str, scope: &ExecutionScope) -> String {
+    match scope.item_index {
+        Some(index) => format!("{}#{}", step_id, index),
+        None => step_id.to_string(),
+    }
+}
+
+impl FlowExecutor {
+    pub(crate) async fn execute_approval_step(
+        &self,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &ExecutionScope,
+    ) -> Result<Value, String> {
+        let config = ApprovalConfig::from_parameters(&step.parameters)?;
+        let oid = ObjectId::parse_str(execution_id).map_err(|e| e.to_string())?;
+        let db = self.service.db();
+        let collection = db.collection::<bson::Document>(FLOW_EXECUTIONS);
+
+        if !config.approvers.is_empty() {
+            let known = db.collection::<bson::Document>(USERS)
+                .distinct("username", doc! { "username": { "$in": &config.approvers } })
+                .await
+                .map_err(|e| format!("Failed to look up approvers: {}", e))?;
+            let unknown = unknown_approvers(&config.approvers, &known);
+            if !unknown.is_empty() {
+                return Err(format!("Approvers include unknown users: {}", unknown.join(", ")));
+            }
+        }
+
+        let wait = step.timeout_seconds.filter(|secs| *secs > 0)
+            .map(|secs| std::time::Duration::from_secs(secs as u64));
+        let key = request_key(&step.id, scope);
+        // Subscribed before the request is read or made visible, so no decision is missed
+        let mut signals = self.control(execution_id).subscribe();
+
+        // A request still open when the backend stopped keeps its decisions
+        let open = collection.find_one(doc! { "_id": oid }).await.ok().flatten()
+            .and_then(|d| recorded_request(&d, &key))
+            .filter(|request| request.step_id == step.id);
+        let reopened = open.is_some();
+        let request = match open {
+            Some(request) => request,
+            None => {
+                let now = Utc::now();
+                let message = config.message.as_deref()
+                    .or(step.description.as_deref())
+                    .unwrap_or(&step.name);
+                let request = PendingApproval {
+                    request_id: ObjectId::new().to_hex(),
+                    step_id: step.id.clone(),
+                    message: resolve_variables(message, &scope.variables),
+                    approvers: config.approvers.clone(),
+                    required_approvals: config.required_approvals,
+                    target_step_id: config.target_step_id.clone().or_else(|| scope.completed_steps.last().cloned()),
+                    rework_step_id: config.rework_step_id.clone(),
+                    requested_at: now,
+                    expires_at: wait.and_then(|w| chrono::Duration::from_std(w).ok()).map(|w| now + w),
+                    decisions: Vec::new(),
+                };
+                let request_bson = bson::to_bson(&request).map_err(|e| e.to_string())?;
+                collection.update_one(
+                    doc! { "_id": oid },
+                    doc! { "$set": {
+                        format!("pending_approvals.{}", key): request_bson,
+                        "pending_approval_step_id": &step.id,
+                        "approval_decision": bson::Bson::Null,
+                        "updated_at": bson::DateTime::from_chrono(now),
+                    }},
+                ).await.map_err(|e| format!("Failed to request approval: {}", e))?;
+                request
+            }
+        };
+
+        let mut data = HashMap::from([
+            ("request_key".to_string(), json!(key)),
+            ("reopened".to_string(), json!(reopened)),
+            ("decisions".to_string(), json!(request.decisions.len())),
+            ("message".to_string(), json!(request.message)),
+            ("approvers".to_string(), json!(request.approvers)),
+            ("required_approvals".to_string(), json!(request.required_approvals)),
+            ("target_step_id".to_string(), json!(request.target_step_id)),
+            ("expires_at".to_string(), json!(request.expires_at)),
+        ]);
+        scope.tag_event_data(&mut data);
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::ApprovalRequired,
+            step_id: Some(step.id.clone()),
+            message: format!("Approval required for step '{}'", step.name),
+            data,
+            timestamp: Utc::now(),
+        }).await;
+
+        // Wait for decisions; cancelling the execution drops this step
+        let deadline = request.expires_at
+            .map(|at| tokio::time::Instant::now() + (at - Utc::now()).to_std().unwrap_or_default());
+        let mut request = request;
+        loop {
+            if let Some(approved) = request.outcome() {
+                self.service.close_approval_request(execution_id, &key, Some(approved)).await;
+                return approval_result(&request, approved);
+            }
+
+            let Some(signal) = next_signal(&mut signals, deadline).await else {
+                self.service.close_approval_request(execution_id, &key, None).await;
+                return Err(format!("Approval timed out after {}s", wait.unwrap_or_default().as_secs()));
+            };
+            match signal {
+                Ok(ControlSignal::ApprovalDecided { decision }) if decision.request_id == request.request_id => {
+                    request.decisions.push(decision);
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Closed) => return Err("Execution is no longer running".to_string()),
+                // Missed signals: the execution record has every decision
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    let recorded = collection.find_one(doc! { "_id": oid }).await.ok().flatten()
+                        .and_then(|d| recorded_request(&d, &key))
+                        .filter(|p| p.request_id == request.request_id);
+                    if let Some(recorded) = recorded {
+                        request = recorded;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The request stored under `key` in the execution's `pending_approvals`
+fn recorded_request(exec_doc: &bson::Document, key: &str) -> Option<PendingApproval> {
+    let request = exec_doc.get_document("pending_approvals").ok()?.get_document(key).ok()?;
+    bson::from_document(request.clone()).ok()
+}
+
+/// Configured approvers missing from the usernames found in the database
+fn unknown_approvers<'a>(approvers: &'a [String], known: &[bson::Bson]) -> Vec<&'a str> {
+    approvers.iter()
+        .map(String::as_str)
+        .filter(|name| !known.iter().any(|k| k.as_str() == Some(*name)))
+        .collect()
+}
+
+/// Step result for a decided request: an approval continues (with any edited
+/// output), a rejection goes to the rework step or fails the step
+fn approval_result(request: &PendingApproval, approved: bool) -> Result<Value, String> {
+    let comments: Vec<Value> = request.decisions.iter()
+        .filter_map(|d| d.comment.as_ref().map(|c| json!({"username": d.username, "approved": d.approved, "comment": c})))
+        .collect();
+    let decided_by: Vec<&str> = request.decisions.iter()
+        .filter(|d| d.approved == approved)
+        .map(|d| d.username.as_str())
+        .collect();
+    let mut result = json!({
+        "output": if approved { "approved" } else { "rejected" },
+        "approved": approved,
+        "decided_by": decided_by,
+        "comments": comments,
+        "decisions": request.decisions,
+    });
+
+    if approved {
+        let edit = request.decisions.iter().rev().find(|d| d.approved && d.edited_output.is_some());
+        if let (Some(edit), Some(target)) = (edit, &request.target_step_id) {
+            result["edited_by"] = json!(edit.username);
+            result["mapped_variables"] = json!({ format!("step_{}_output", target): edit.edited_output });
+        }
+        return Ok(result);
+    }
+
+    match &request.rework_step_id {
+        Some(rework) => {
+            result["next_step_id"] = json!(rework);
+            Ok(result)
+        }
+        None => {
+            let reasons: Vec<&str> = request.decisions.iter()
+                .filter(|d| !d.approved)
+                .filter_map(|d| d.comment.as_deref())
+                .collect();
+            Err(if reasons.is_empty() {
+                "Approval rejected".to_string()
+            } else {
+                format!("Approval rejected: {}", reasons.join("; "))
+            })
+        }
+    }
+}
+
+impl FlowService {
+    /// Record one approver's decision on an approval step the execution is
+    /// waiting on. Returns the request with the decision added.
+    pub async fn submit_approval(
+        &self,
+        execution_id: &str,
+        user: &AuthUser,
+        submission: ApprovalSubmission,
+    ) -> Result<PendingApproval, AppError> {
+        let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let oid = ObjectId::parse_str(execution_id)
+            .map_err(|_| AppError::BadRequest("Invalid execution ID".to_string()))?;
+
+        let exec_doc = collection
+            .find_one(doc! { "_id": oid })
+            .await?
+            .ok_or_else(|| AppError::NotFound("Execution not found".to_string()))?;
+        let owner_id = exec_doc.get_str("user_id").unwrap_or_default();
+        let mut pending: HashMap<String, PendingApproval> = exec_doc.get_document("pending_approvals").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default();
+
+        // Only the owner and the listed approvers learn the execution exists
+        if user.id != owner_id && !pending.values().any(|p| p.can_decide(user, owner_id)) {
+            return Err(AppError::NotFound("Execution not found".to_string()));
+        }
+        let key = match submission.step_id {
+            Some(key) => key,
+            None => {
+                let mut decidable: Vec<&String> = pending.iter()
+                    .filter(|(_, p)| p.can_decide(user, owner_id))
+                    .map(|(key, _)| key)
+                    .collect();
+                decidable.sort();
+                match decidable.as_slice() {
+                    [key] => key.to_string(),
+                    [] if pending.is_empty() => {
+                        return Err(AppError::Conflict("Execution is not waiting for approval".to_string()));
+                    }
+                    [] => return Err(AppError::Forbidden("You are not one of this step's approvers".to_string())),
+                    keys => return Err(AppError::BadRequest(format!(
+                        "Several steps are waiting for approval ({}); set step_id",
+                        keys.iter().map(|k| k.as_str()).collect::<Vec<_>>().join(", ")
+                    ))),
+                }
+            }
+        };
+        let mut request = pending.remove(&key)
+            .ok_or_else(|| AppError::Conflict(format!("Step '{}' is not waiting for approval", key)))?;
+        if !request.can_decide(user, owner_id) {
+            return Err(AppError::Forbidden("You are not one of this step's approvers".to_string()));
+        }
+        if submission.edited_output.is_some() {
+            if !submission.approved {
+                return Err(AppError::BadRequest("edited_output can only be sent with an approval".to_string()));
+            }
+            if request.target_step_id.is_none() {
+                return Err(AppError::BadRequest("This approval step has no output to edit".to_string()));
+            }
+        }
+
+        let decision = ApprovalDecision {
+            request_id: request.request_id.clone(),
+            step_id: request.step_id.clone(),
+            user_id: user.id.clone(),
+            username: user.username.clone(),
+            approved: submission.approved,
+            comment: submission.comment.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()),
+            edited_output: submission.edited_output,
+            decided_at: Utc::now(),
+        };
+        let decision_bson = bson::to_bson(&decision).map_err(|e| AppError::Internal(e.to_string()))?;
+        let recorded = collection.update_one(
+            doc! {
+                "_id": oid,
+                format!("pending_approvals.{}.request_id", key): &request.request_id,
+                format!("pending_approvals.{}.decisions.user_id", key): { "$ne": &user.id },
+            },
+            doc! {
+                "$push": { format!("pending_approvals.{}.decisions", key): &decision_bson, "approval_history": &decision_bson },
+                "$set": { "updated_at": bson::DateTime::from_chrono(Utc::now()) },
+            },
+        ).await?.modified_count > 0;
+        if !recorded {
+            return Err(AppError::Conflict("You already decided on this approval, or it is closed".to_string()));
+        }
+        request.decisions.push(decision.clone());
+        self.controls.send(execution_id, ControlSignal::ApprovalDecided { decision: decision.clone() });
+
+        let approvals = request.decisions.iter().filter(|d| d.approved).count();
+        let event_type = if decision.approved { FlowEventType::ApprovalGranted } else { FlowEventType::ApprovalRejected };
+        self.emit_event(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type,
+            step_id: Some(request.step_id.clone()),
+            message: format!(
+                "{} by {} ({}/{} approvals)",
+                if decision.approved { "Approval granted" } else { "Approval rejected" },
+                decision.username, approvals, request.required_approvals
+            ),
+            data: HashMap::from([
+                ("username".to_string(), json!(decision.username)),
+                ("comment".to_string(), json!(decision.comment)),
+                ("edited_output".to_string(), json!(decision.edited_output.is_some())),
+                ("approvals".to_string(), json!(approvals)),
+                ("required_approvals".to_string(), json!(request.required_approvals)),
+            ]),
+            timestamp: Utc::now(),
+        }).await;
+
+        Ok(request)
+    }
+
+    /// Clear a pending request once its step stops waiting, recording the
+    /// outcome when there is one
+    async fn close_approval_request(&self, execution_id: &str, key: &str, outcome: Option<bool>) {
+        let Ok(oid) = ObjectId::parse_str(execution_id) else { return };
+        let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let _ = collection.update_one(
+            doc! { "_id": oid },
+            doc! {
+                "$set": { "approval_decision": outcome, "updated_at": bson::DateTime::from_chrono(Utc::now()) },
+                "$unset": { format!("pending_approvals.{}", key): "" },
+            },
+        ).await;
+        // The single-step field stays set while any request is open
+        let _ = collection.update_one(
+            doc! { "_id": oid, "pending_approvals": {} },
+            doc! { "$unset": { "pending_approval_step_id": "" } },
+        ).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn request(approvers: &[&str], required: u32, decisions: &[(&str, bool)]) -> PendingApproval {
+        PendingApproval {
+            request_id: "r1".to_string(),
+            step_id: "review".to_string(),
+            message: "Review".to_string(),
+            approvers: approvers.iter().map(|a| a.to_string()).collect(),
+            required_approvals: required,
+            target_step_id: Some("draft".to_string()),
+            rework_step_id: None,
+            requested_at: Utc::now(),
+            expires_at: None,
+            decisions: decisions.iter().map(|(user, approved)| ApprovalDecision {
+                request_id: "r1".to_string(),
+                step_id: "review".to_string(),
+                user_id: format!("id-{}", user),
+                username: user.to_string(),
+                approved: *approved,
+                comment: Some(format!("{} says {}", user, approved)),
+                edited_output: None,
+                decided_at: Utc::now(),
+            }).collect(),
+        }
+    }
+
+    #[test]
+    fn test_config_parsing() {
+        let config = ApprovalConfig::from_parameters(&HashMap::new()).unwrap();
+        assert!(config.approvers.is_empty());
+        assert_eq!(config.required_approvals, 1);
+
+        let config = ApprovalConfig::from_parameters(&parameters(json!({
+            "approvers": ["bob", "alice", "bob"], "required_approvals": 2, "rework_step_id": "draft",
+        }))).unwrap();
+        assert_eq!(config.approvers, vec!["alice", "bob"]);
+        assert_eq!(config.rework_step_id.as_deref(), Some("draft"));
+
+        assert!(ApprovalConfig::from_parameters(&parameters(json!({"required_approvals": 2}))).is_err());
+        assert!(ApprovalConfig::from_parameters(&parameters(json!({"required_approvals": 0}))).is_err());
+        assert!(ApprovalConfig::from_parameters(&parameters(json!({"approvers": "alice"}))).is_err());
+    }
+
+    #[test]
+    fn test_unknown_approvers_lists_only_missing_users() {
+        let approvers = vec!["alice".to_string(), "bob".to_string(), "carol".to_string()];
+        let known = vec![bson::Bson::from("alice"), bson::Bson::from("carol")];
+        assert_eq!(unknown_approvers(&approvers, &known), vec!["bob"]);
+        assert!(unknown_approvers(&approvers[..1], &known).is_empty());
+    }
+
+    #[test]
+    fn test_map_items_get_their_own_request() {
+        let mut scope = ExecutionScope::default();
+        assert_eq!(request_key("review", &scope), "review");
+        scope.item_index = Some(3);
+        assert_eq!(request_key("review", &scope), "review#3");
+    }
+
+    #[test]
+    fn test_open_request_is_read_back_with_its_decisions() {
+        let open = request(&["alice", "bob", "carol"], 2, &[("alice", true)]);
+        let exec_doc = doc! { "pending_approvals": { "review": bson::to_bson(&open).unwrap() } };
+
+        let recorded = recorded_request(&exec_doc, "review").unwrap();
+        assert_eq!(recorded.request_id, open.request_id);
+        assert_eq!(recorded.decisions, open.decisions);
+        assert!(recorded_request(&exec_doc, "review#0").is_none());
+    }
+
+    #[test]
+    fn test_quorum_outcome() {
+        let two_of_three = ["alice", "bob", "carol"];
+        assert_eq!(request(&two_of_three, 2, &[("alice", true)]).outcome(), None);
+        assert_eq!(request(&two_of_three, 2, &[("alice", true), ("bob", true)]).outcome(), Some(true));
+        assert_eq!(request(&two_of_three, 2, &[("alice", false)]).outcome(), None);
+        assert_eq!(request(&two_of_three, 2, &[("alice", false), ("bob", false)]).outcome(), Some(false));
+        assert_eq!(request(&[], 1, &[("owner", false)]).outcome(), Some(false));
+    }
+
+    #[test]
+    fn test_approver_eligibility() {
+        let user = |id: &str, name: &str| AuthUser { id: id.to_string(), username: name.to_string() };
+        let owner_only = request(&[], 1, &[]);
+        assert!(owner_only.can_decide(&user("u1", "owner"), "u1"));
+        assert!(!owner_only.can_decide(&user("u2", "alice"), "u1"));
+
+        let listed = request(&["alice"], 1, &[]);
+        assert!(listed.can_decide(&user("u2", "alice"), "u1"));
+        assert!(!listed.can_decide(&user("u1", "owner"), "u1"));
+    }
+
+    #[test]
+    fn test_rejection_goes_to_rework_step() {
+        let mut rejected = request(&[], 1, &[("owner", false)]);
+        let error = approval_result(&rejected, false).unwrap_err();
+        assert_eq!(error, "Approval rejected: owner says false");
+
+        rejected.rework_step_id = Some("draft".to_string());
+        let result = approval_result(&rejected, false).unwrap();
+        assert_eq!(result["next_step_id"], json!("draft"));
+        assert_eq!(result["comments"][0]["comment"], json!("owner says false"));
+    }
+
+    #[test]
+    fn test_approval_applies_edited_output() {
+        let mut approved = request(&["alice", "bob"], 2, &[("alice", true), ("bob", true)]);
+        approved.decisions[0].edited_output = Some(json!("fixed draft"));
+        let result = approval_result(&approved, true).unwrap();
+        assert_eq!(result["mapped_variables"]["step_draft_output"], json!("fixed draft"));
+        assert_eq!(result["edited_by"], json!("alice"));
+        assert_eq!(result["decided_by"], json!(["alice", "bob"]));
+    }
+}
@@ -0,0 +1,235 @@
+//! Delay step: waits for a while, or until a given time, then continues.
+//!
+//! Parameters (one of):
+//! - `seconds`: how long to wait, a number or a templated string
+//! - `until`: when to continue, an RFC 3339 timestamp, templated
+//!
+//! A top-level execution with at least `PARK_AFTER` left to wait gives up its
+//! concurrency slot: it becomes `waiting` until `wake_at` with no task running,
+//! and the queue dispatcher queues it again once that time has passed. As the
+//! wait lives in the execution record, restarts don't cut it short. Shorter
+//! delays, and delays in parallel branches, map items and sub-flows, sleep in
+//! place.
+//!
+//! The end of a main-run delay is recorded in `delays.<step_id>`, so a resumed
+//! execution only waits what is left. The step output is that end time.
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use mongodb::bson::doc;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::db::collections::FLOW_EXECUTIONS;
+use crate::models::flow::{FlowStep, FlowStepType};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::flow_service::{resolve_value_variables, ExecutionCheckpoint, ExecutionScope, FlowExecutor};
+
+/// Main-run delays with at least this much left park the execution
+pub const PARK_AFTER: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// When a delay step ends, from its `seconds` or `until` parameter
+pub fn delay_end(
+    parameters: &HashMap<String, Value>,
+    variables: &HashMap<String, Value>,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    match (parameters.get("seconds"), parameters.get("until")) {
+        (Some(_), Some(_)) => Err("Set either seconds or until, not both".to_string()),
+        (Some(seconds), None) => {
+            let resolved = resolve_value_variables(seconds, variables);
+            let secs = match &resolved {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse::<f64>().ok(),
+                _ => None,
+            };
+            let secs = secs.filter(|s| s.is_finite() && *s >= 0.0)
+                .ok_or_else(|| format!("seconds must be a non-negative number, got {}", resolved))?;
+            chrono::Duration::try_milliseconds((secs * 1000.0) as i64)
+                .and_then(|delay| now.checked_add_signed(delay))
+                .ok_or_else(|| format!("seconds is too large: {}", secs))
+        }
+        (None, Some(until)) => {
+            let resolved = resolve_value_variables(until, variables);
+            let text = resolved.as_str().ok_or_else(|| format!("until must be an RFC 3339 timestamp, got {}", resolved))?;
+            DateTime::parse_from_rfc3339(text.trim())
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| format!("Invalid until '{}': {}", text, e))
+        }
+        (None, None) => Err("Delay step requires seconds or until".to_string()),
+    }
+}
+
+/// Check the parameters when the flow is saved. Templated values are only
+/// checked when the step runs.
+pub fn validate_parameters(parameters: &HashMap<String, Value>) -> Result<(), String> {
+    let given: Vec<&Value> = ["seconds", "until"].iter().filter_map(|key| parameters.get(*key)).collect();
+    if let [value] = given.as_slice() {
+        let text = value.to_string();
+        if text.contains("{{") || text.contains("${") {
+            return Ok(());
+        }
+    }
+    delay_end(parameters, &HashMap::new(), Utc::now()).map(|_| ())
+}
+
+fn recorded_end(exec_doc: &bson::Document, step_id: &str) -> Option<DateTime<Utc>> {
+    exec_doc.get_document("delays").ok()?.get_datetime(step_id).ok().map(|d| d.to_chrono())
+}
+
+impl FlowExecutor {
+    /// The end of a main-run delay: the recorded one, or a new one that gets recorded
+    async fn main_run_delay_end(&self, step: &FlowStep, oid: &ObjectId, scope: &ExecutionScope) -> Result<DateTime<Utc>, String> {
+        let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let exec_doc = collection.find_one(doc! { "_id": oid }).await
+            .map_err(|e| format!("Failed to load execution: {}", e))?;
+        if let Some(end) = exec_doc.as_ref().and_then(|d| recorded_end(d, &step.id)) {
+            return Ok(end);
+        }
+        let end = delay_end(&step.parameters, &scope.variables, Utc::now())?;
+        collection.update_one(
+            doc! { "_id": oid },
+            doc! { "$set": { format!("delays.{}", step.id): bson::DateTime::from_chrono(end) } },
+        ).await.map_err(|e| format!("Failed to record delay: {}", e))?;
+        Ok(end)
+    }
+
+    /// Park a top-level execution at a long delay step, freeing its slot until
+    /// the delay ends. Returns whether it was parked; the step runs again when
+    /// the execution is started back up.
+    pub(crate) async fn park_at_delay(
+        &self,
+        step: &FlowStep,
+        execution_id: &str,
+        previous_step_id: Option<&str>,
+        scope: &ExecutionScope,
+    ) -> bool {
+        if step.step_type != FlowStepType::Delay || scope.branch_id.is_some() {
+            return false;
+        }
+        let Ok(oid) = ObjectId::parse_str(execution_id) else { return false };
+        let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        // Sub-flow executions keep their parent's step waiting on them
+        match collection.find_one(doc! { "_id": oid }).await {
+            Ok(Some(exec_doc)) if !exec_doc.contains_key("parent_execution_id") => {}
+            _ => return false,
+        }
+        // Errors are left to the step to report
+        let Ok(end) = self.main_run_delay_end(step, &oid, scope).await else { return false };
+        if (end - Utc::now()).to_std().unwrap_or_default() < PARK_AFTER {
+            return false;
+        }
+
+        let mut set = doc! {
+            "status": "waiting",
+            "wake_at": bson::DateTime::from_chrono(end),
+            "current_step_id": &step.id,
+            "updated_at": bson::DateTime::from_chrono(Utc::now()),
+        };
+        match bson::to_bson(&ExecutionCheckpoint::capture(&step.id, previous_step_id, scope)) {
+            Ok(checkpoint) => { set.insert("checkpoint", checkpoint); }
+            // Without a checkpoint it couldn't start again, so sleep in place
+            Err(e) => {
+                tracing::warn!(execution_id = %execution_id, error = %e, "Failed to serialize checkpoint");
+                return false;
+            }
+        }
+        // An execution cancelled meanwhile is not parked; its step is dropped instead
+        let parked = collection
+            .update_one(doc! { "_id": oid, "is_cancellation_requested": { "$ne": true } }, doc! { "$set": set })
+            .await
+            .is_ok_and(|result| result.modified_count > 0);
+        if !parked {
+            return false;
+        }
+
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::ExecutionWaiting,
+            step_id: Some(step.id.clone()),
+            message: format!("Execution waiting until {} at step '{}'", end.to_rfc3339(), step.name),
+            data: HashMap::from([("wake_at".to_string(), json!(end))]),
+            timestamp: Utc::now(),
+        }).await;
+        true
+    }
+
+    pub(crate) async fn execute_delay_step(
+        &self,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &ExecutionScope,
+    ) -> Result<Value, String> {
+        let oid = ObjectId::parse_str(execution_id).map_err(|e| e.to_string())?;
+        let main_run = scope.branch_id.is_none();
+        let end = if main_run {
+            self.main_run_delay_end(step, &oid, scope).await?
+        } else {
+            delay_end(&step.parameters, &scope.variables, Utc::now())?
+        };
+
+        // Cancelling the execution drops the step, and the sleep with it
+        if let Ok(remaining) = (end - Utc::now()).to_std() {
+            let mut data = HashMap::from([("wake_at".to_string(), json!(end))]);
+            scope.tag_event_data(&mut data);
+            self.emit(FlowExecutionEvent {
+                id: None,
+                execution_id: execution_id.to_string(),
+                event_type: FlowEventType::StepProgress,
+                step_id: Some(step.id.clone()),
+                message: format!("Waiting {}s", remaining.as_secs()),
+                data,
+                timestamp: Utc::now(),
+            }).await;
+            tokio::time::sleep(remaining).await;
+        }
+
+        if main_run {
+            let _ = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS).update_one(
+                doc! { "_id": oid },
+                doc! { "$unset": { format!("delays.{}", step.id): "" } },
+            ).await;
+        }
+        Ok(json!({ "output": end }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_delay_end_from_seconds_or_until() {
+        let now = Utc::now();
+        let variables = HashMap::from([("poll_seconds".to_string(), json!(600))]);
+
+        let end = delay_end(&params(json!({"seconds": 90})), &variables, now).unwrap();
+        assert_eq!((end - now).num_seconds(), 90);
+        let end = delay_end(&params(json!({"seconds": "0.5"})), &variables, now).unwrap();
+        assert_eq!((end - now).num_milliseconds(), 500);
+        let end = delay_end(&params(json!({"seconds": "{{poll_seconds}}"})), &variables, now).unwrap();
+        assert_eq!((end - now).num_minutes(), 10);
+
+        let end = delay_end(&params(json!({"until": "2030-01-01T09:00:00+01:00"})), &variables, now).unwrap();
+        assert_eq!(end.to_rfc3339(), "2030-01-01T08:00:00+00:00");
+
+        for bad in [json!({}), json!({"seconds": -1}), json!({"seconds": "soon"}), json!({"until": "tomorrow"}),
+                    json!({"seconds": 1, "until": "2030-01-01T00:00:00Z"}), json!({"seconds": 1e300})] {
+            assert!(delay_end(&params(bad.clone()), &variables, now).is_err(), "{} accepted", bad);
+        }
+    }
+
+    #[test]
+    fn test_parameters_checked_unless_templated() {
+        assert!(validate_parameters(&params(json!({"seconds": 600}))).is_ok());
+        assert!(validate_parameters(&params(json!({"until": "{{step_schedule_output}}"}))).is_ok());
+        assert!(validate_parameters(&params(json!({"seconds": "later"}))).is_err());
+        assert!(validate_parameters(&params(json!({}))).is_err());
+        assert!(validate_parameters(&params(json!({"seconds": "{{wait}}", "until": "2030-01-01T00:00:00Z"}))).is_err());
+    }
+}
@@ -1 +1,501 @@
-// TODO: Phase 8 - Bidirectional feedback loop step
+//! Bidirectional feedback loops driven by `EdgeMetadata`.
+//!
+//! When the edge `source -> target` is marked `is_feedback_loop`, the target's
+//! output is treated as a critique of the source's output. The executor loops
+//! back to the source, carrying the critique forward, until the quality
+//! threshold or convergence criteria is met or `max_iterations` is reached.
+//!
+//! A `feedback_loop` step closes a loop with the step that ran before it, reading
+//! `max_iterations`, `quality_threshold` and `convergence_criteria` from its
+//! parameters when the flow has no edge metadata for that edge.
+
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::bson::doc;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::db::collections::FLOW_EXECUTIONS;
+use crate::models::flow::{EdgeMetadata, Flow, FlowStep, FlowStepType};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::flow_service::{ExecutionScope, FlowExecutor};
+use crate::utils::json_path::last_json_object_with;
+
+const DEFAULT_MAX_ITERATIONS: i32 = 25;
+const DEFAULT_QUALITY_THRESHOLD: f64 = 0.8;
+
+/// Convergence criteria keyword: stop once the quality score stops improving
+pub const CONVERGENCE_NO_IMPROVEMENT: &str = "no_improvement";
+
+/// Reviewer's judgement of the source step's output
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedbackVerdict {
+    /// Normalized to 0.0..=1.0
+    pub quality_score: Option<f64>,
+    pub acceptable: Option<bool>,
+    pub feedback: String,
+}
+
+/// Extract score, acceptance and critique from a reviewer step's output.
+/// Accepts a JSON object output, a trailing JSON object in text output,
+/// or a plain-text "score: 8/10" style rating.
+pub fn parse_feedback_verdict(output: &Value) -> FeedbackVerdict {
+    let text = value_to_text(output);
+    let fields = match output {
+        Value::Object(map) => Some(map.clone()),
+        Value::String(s) => last_json_object_with(s, &["quality_score", "score", "acceptable"]),
+        _ => None,
+    };
+
+    match fields {
+        Some(map) => FeedbackVerdict {
+            quality_score: map.get("quality_score")
+                .or_else(|| map.get("score"))
+                .and_then(|v| v.as_f64())
+                .map(normalize_score),
+            acceptable: map.get("acceptable")
+                .or_else(|| map.get("passed"))
+                .and_then(|v| v.as_bool()),
+            feedback: map.get("feedback")
+                .or_else(|| map.get("rationale"))
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .unwrap_or(text),
+        },
+        None => FeedbackVerdict {
+            quality_score: extract_score_from_text(&text),
+            acceptable: None,
+            feedback: text,
+        },
+    }
+}
+
+/// Find the last "score: <number>" in free text
+fn extract_score_from_text(text: &str) -> Option<f64> {
+    let lower = text.to_lowercase();
+    lower.match_indices("score")
+        .filter_map(|(i, _)| {
+            let rest = &lower[i + "score".len()..];
+            let start = rest.find(|c: char| c.is_ascii_digit())?;
+            // Only accept numbers that follow the word closely ("score: 8", "score of 0.9")
+            if start > 6 {
+                return None;
+            }
+            let number: String = rest[start..].chars()
+                .take_while(|c| c.is_ascii_digit() || *c == '.')
+                .collect();
+            number.trim_end_matches('.').parse::<f64>().ok()
+        })
+        .last()
+        .map(normalize_score)
+}
+
+/// Scores out of 10 or 100 are scaled down to 0.0..=1.0
+fn normalize_score(score: f64) -> f64 {
+    let scaled = if score > 10.0 {
+        score / 100.0
+    } else if score > 1.0 {
+        score / 10.0
+    } else {
+        score
+    };
+    scaled.clamp(0.0, 1.0)
+}
+
+fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
+
+/// Decide whether the loop should stop after the latest evaluation.
+/// Expects `edge.current_iteration` and `edge.quality_scores` to already include it.
+pub fn loop_stop_reason(edge: &EdgeMetadata, verdict: &FeedbackVerdict) -> Option<&'static str> {
+    if let (Some(score), Some(threshold)) = (verdict.quality_score, edge.quality_threshold) {
+        if score >= threshold {
+            return Some("quality_threshold_met");
+        }
+    }
+
+    match edge.convergence_criteria.as_deref().map(str::trim) {
+        Some(CONVERGENCE_NO_IMPROVEMENT) => {
+            if let [.., previous, latest] = edge.quality_scores.as_slice() {
+                if latest <= previous {
+                    return Some("convergence_criteria_met");
+                }
+            }
+        }
+        Some(criteria) if !criteria.is_empty() && verdict.acceptable == Some(true) => {
+            return Some("convergence_criteria_met");
+        }
+        _ => {
+            // Without a score there is nothing to compare against the threshold,
+            // so trust the reviewer's explicit approval
+            if verdict.quality_score.is_none() && verdict.acceptable == Some(true) {
+                return Some("accepted");
+            }
+        }
+    }
+
+    if edge.current_iteration >= edge.max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS) {
+        return Some("max_iterations_reached");
+    }
+
+    None
+}
+
+/// Return the feedback edge that `step` closes when reached from `previous_step_id`.
+/// A `feedback_loop` step without edge metadata gets an edge synthesized from its parameters.
+/// Loops that already completed in this execution are not re-entered.
+pub(crate) fn feedback_edge_into(
+    step: &FlowStep,
+    previous_step_id: Option<&str>,
+    scope: &mut ExecutionScope,
+) -> Option<String> {
+    let previous = previous_step_id?;
+
+    let existing = scope.edge_states.iter()
+        .find(|(_, e)| e.is_feedback_loop && e.target_step_id == step.id && e.source_step_id == previous)
+        .map(|(id, e)| (id.clone(), is_loop_completed(e)));
+
+    match existing {
+        Some((_, true)) => None,
+        Some((edge_id, false)) => Some(edge_id),
+        None if step.step_type == FlowStepType::FeedbackLoop => {
+            let edge_id = format!("{}-{}", previous, step.id);
+            scope.edge_states.insert(edge_id.clone(), EdgeMetadata {
+                edge_id: edge_id.clone(),
+                source_step_id: previous.to_string(),
+                target_step_id: step.id.clone(),
+                is_feedback_loop: true,
+                max_iterations: Some(step.parameters.get("max_iterations")
+                    .and_then(|v| v.as_i64())
+                    .map(|v| v as i32)
+                    .unwrap_or(DEFAULT_MAX_ITERATIONS)),
+                quality_threshold: Some(step.parameters.get("quality_threshold")
+                    .and_then(|v| v.as_f64())
+                    .unwrap_or(DEFAULT_QUALITY_THRESHOLD)),
+                convergence_criteria: step.parameters.get("convergence_criteria")
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+                current_iteration: 0,
+                feedback_history: Vec::new(),
+                quality_scores: Vec::new(),
+            });
+            Some(edge_id)
+        }
+        None => None,
+    }
+}
+
+fn is_loop_completed(edge: &EdgeMetadata) -> bool {
+    edge.feedback_history.last()
+        .and_then(|entry| entry.get("action"))
+        .and_then(|v| v.as_str())
+        == Some("complete")
+}
+
+/// Extra instructions appended to a step's task while a feedback loop is running:
+/// revision instructions for the source, review instructions for the target.
+pub(crate) fn feedback_task_context(
+    step: &FlowStep,
+    feedback_edge_id: Option<&str>,
+    scope: &ExecutionScope,
+) -> Option<String> {
+    let mut sections = Vec::new();
+
+    for edge in scope.edge_states.values().filter(|e| e.is_feedback_loop && e.source_step_id == step.id) {
+        let Some(last) = edge.feedback_history.last() else { continue };
+        if last.get("action").and_then(|v| v.as_str()) != Some("iterate") {
+            continue;
+        }
+        let feedback = last.get("target_feedback").map(value_to_text).unwrap_or_default();
+        let previous_output = last.get("source_output").map(value_to_text).unwrap_or_default();
+        sections.push(format!(
+            "## Feedback from review (iteration {})\n{}\n\n## Your previous output\n{}\n\nRevise your previous output to address this feedback.",
+            edge.current_iteration, feedback, previous_output
+        ));
+    }
+
+    if let Some(edge) = feedback_edge_id.and_then(|id| scope.edge_states.get(id)) {
+        let source_output = scope.variables.get(&format!("step_{}_output", edge.source_step_id))
+            .map(value_to_text)
+            .unwrap_or_default();
+        let mut review = format!(
+            "## Output to review (from step '{}')\n{}\n\n## Evaluation\nReview the output above.",
+            edge.source_step_id, source_output
+        );
+        if let Some(ref criteria) = edge.convergence_criteria {
+            review.push_str(&format!(" It is acceptable when: {}.", criteria));
+        }
+        if let Some(threshold) = edge.quality_threshold {
+            review.push_str(&format!(" The required quality score is {}.", threshold));
+        }
+        review.push_str(
+            "\nEnd your response with a JSON object on its own line:\n{\"quality_score\": <0.0-1.0>, \"acceptable\": <true|false>, \"feedback\": \"<specific changes required>\"}",
+        );
+        sections.push(review);
+    }
+
+    if sections.is_empty() {
+        None
+    } else {
+        Some(sections.join("\n\n"))
+    }
+}
+
+impl FlowExecutor {
+    /// A feedback loop step is an LLM review; loop control happens in `advance_feedback_loop`
+    pub(crate) async fn execute_feedback_loop_step(
+        &self,
+        flow: &Flow,
+        step: &FlowStep,
+        execution_id: &str,
+        variables: &HashMap<String, Value>,
+        task_context: Option<&str>,
+    ) -> Result<Value, String> {
+        self.execute_llm_step(flow, step, execution_id, variables, task_context).await
+    }
+
+    /// Record one iteration of the loop closed by `step` and decide where to go next.
+    /// Returns the source step ID when the loop should run again, `None` once it completes.
+    pub(crate) async fn advance_feedback_loop(
+        &self,
+        execution_id: &str,
+        edge_id: &str,
+        step: &FlowStep,
+        result: &Value,
+        scope: &mut ExecutionScope,
+    ) -> Option<String> {
+        let verdict = parse_feedback_verdict(result.get("output").unwrap_or(&Value::Null));
+
+        let edge = scope.edge_states.get_mut(edge_id)?;
+        let source_output = scope.variables
+            .get(&format!("step_{}_output", edge.source_step_id))
+            .cloned()
+            .unwrap_or(Value::Null);
+
+        if edge.current_iteration == 0 {
+            self.emit(FlowExecutionEvent {
+                id: None,
+                execution_id: execution_id.to_string(),
+                event_type: FlowEventType::FeedbackLoopStarted,
+                step_id: Some(step.id.clone()),
+                message: format!("Feedback loop '{}' started", edge_id),
+                data: HashMap::from([
+                    ("edge_id".to_string(), json!(edge_id)),
+                    ("source_step_id".to_string(), json!(edge.source_step_id)),
+                    ("target_step_id".to_string(), json!(edge.target_step_id)),
+                    ("max_iterations".to_string(), json!(edge.max_iterations)),
+                    ("quality_threshold".to_string(), json!(edge.quality_threshold)),
+                ]),
+                timestamp: Utc::now(),
+            }).await;
+        }
+
+        edge.current_iteration += 1;
+        if let Some(score) = verdict.quality_score {
+            edge.quality_scores.push(score);
+        }
+
+        let stop_reason = loop_stop_reason(edge, &verdict);
+        let mut entry = HashMap::from([
+            ("iteration".to_string(), json!(edge.current_iteration)),
+            ("source_output".to_string(), source_output.clone()),
+            ("target_feedback".to_string(), json!(verdict.feedback)),
+            ("quality_score".to_string(), json!(verdict.quality_score)),
+            ("acceptable".to_string(), json!(verdict.acceptable)),
+            ("action".to_string(), json!(if stop_reason.is_some() { "complete" } else { "iterate" })),
+            ("timestamp".to_string(), json!(Utc::now().to_rfc3339())),
+        ]);
+        if stop_reason.is_some() {
+            entry.insert("final_output".to_string(), source_output);
+        }
+        edge.feedback_history.push(entry);
+
+        let edge = edge.clone();
+
+        if let Ok(oid) = ObjectId::parse_str(execution_id) {
+            if let Ok(edge_bson) = bson::to_bson(&edge) {
+                let exec_collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+                let _ = exec_collection.update_one(
+                    doc! { "_id": oid },
+                    doc! { "$set": {
+                        format!("edge_states.{}", edge_id): edge_bson,
+                        "updated_at": bson::DateTime::from_chrono(Utc::now()),
+                    }},
+                ).await;
+            }
+        }
+
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::FeedbackLoopIteration,
+            step_id: Some(step.id.clone()),
+            message: format!(
+                "Feedback loop '{}' iteration {}/{}",
+                edge_id, edge.current_iteration, edge.max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS)
+            ),
+            data: HashMap::from([
+                ("edge_id".to_string(), json!(edge_id)),
+                ("iteration".to_string(), json!(edge.current_iteration)),
+                ("quality_score".to_string(), json!(verdict.quality_score)),
+                ("quality_threshold".to_string(), json!(edge.quality_threshold)),
+                ("acceptable".to_string(), json!(verdict.acceptable)),
+                ("feedback".to_string(), json!(verdict.feedback)),
+            ]),
+            timestamp: Utc::now(),
+        }).await;
+
+        let feedback_var = format!("step_{}_feedback", edge.source_step_id);
+
+        match stop_reason {
+            Some(reason) => {
+                scope.variables.remove(&feedback_var);
+                self.emit(FlowExecutionEvent {
+                    id: None,
+                    execution_id: execution_id.to_string(),
+                    event_type: FlowEventType::FeedbackLoopCompleted,
+                    step_id: Some(step.id.clone()),
+                    message: format!(
+                        "Feedback loop '{}' completed after {} iteration(s): {}",
+                        edge_id, edge.current_iteration, reason
+                    ),
+                    data: HashMap::from([
+                        ("edge_id".to_string(), json!(edge_id)),
+                        ("iterations".to_string(), json!(edge.current_iteration)),
+                        ("reason".to_string(), json!(reason)),
+                        ("quality_scores".to_string(), json!(edge.quality_scores)),
+                    ]),
+                    timestamp: Utc::now(),
+                }).await;
+                None
+            }
+            None => {
+                scope.variables.insert("feedback".to_string(), json!(verdict.feedback));
+                scope.variables.insert("feedback_iteration".to_string(), json!(edge.current_iteration));
+                scope.variables.insert(feedback_var, json!(verdict.feedback));
+                Some(edge.source_step_id)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(threshold: Option<f64>, criteria: Option<&str>, max: i32) -> EdgeMetadata {
+        EdgeMetadata {
+            edge_id: "dev-qa".to_string(),
+            source_step_id: "dev".to_string(),
+            target_step_id: "qa".to_string(),
+            is_feedback_loop: true,
+            max_iterations: Some(max),
+            quality_threshold: threshold,
+            convergence_criteria: criteria.map(String::from),
+            current_iteration: 1,
+            feedback_history: Vec::new(),
+            quality_scores: Vec::new(),
+        }
+    }
+
+    fn verdict(score: Option<f64>, acceptable: Option<bool>) -> FeedbackVerdict {
+        FeedbackVerdict { quality_score: score, acceptable, feedback: String::new() }
+    }
+
+    #[test]
+    fn test_parse_verdict_from_trailing_json() {
+        let output = json!("Tests fail on empty input.\n{\"quality_score\": 0.6, \"acceptable\": false, \"feedback\": \"Handle empty input\"}");
+        let v = parse_feedback_verdict(&output);
+        assert_eq!(v.quality_score, Some(0.6));
+        assert_eq!(v.acceptable, Some(false));
+        assert_eq!(v.feedback, "Handle empty input");
+    }
+
+    #[test]
+    fn test_parse_verdict_from_object_and_text() {
+        let v = parse_feedback_verdict(&json!({"score": 9, "passed": true, "rationale": "Good"}));
+        assert_eq!(v.quality_score, Some(0.9));
+        assert_eq!(v.acceptable, Some(true));
+        assert_eq!(v.feedback, "Good");
+
+        let v = parse_feedback_verdict(&json!("Mostly fine. Quality score: 7/10"));
+        assert_eq!(v.quality_score, Some(0.7));
+        assert_eq!(v.acceptable, None);
+        assert_eq!(v.feedback, "Mostly fine. Quality score: 7/10");
+
+        let v = parse_feedback_verdict(&json!("No rating here"));
+        assert_eq!(v.quality_score, None);
+    }
+
+    #[test]
+    fn test_loop_stop_reason() {
+        let e = edge(Some(0.9), None, 5);
+        assert_eq!(loop_stop_reason(&e, &verdict(Some(0.95), None)), Some("quality_threshold_met"));
+        assert_eq!(loop_stop_reason(&e, &verdict(Some(0.5), Some(true))), None);
+        assert_eq!(loop_stop_reason(&e, &verdict(None, Some(true))), Some("accepted"));
+
+        let e = edge(Some(0.9), Some("QA approves"), 5);
+        assert_eq!(loop_stop_reason(&e, &verdict(Some(0.5), Some(true))), Some("convergence_criteria_met"));
+
+        let mut e = edge(None, Some(CONVERGENCE_NO_IMPROVEMENT), 5);
+        e.quality_scores = vec![0.6, 0.6];
+        assert_eq!(loop_stop_reason(&e, &verdict(Some(0.6), None)), Some("convergence_criteria_met"));
+
+        let mut e = edge(Some(0.9), None, 3);
+        e.current_iteration = 3;
+        assert_eq!(loop_stop_reason(&e, &verdict(Some(0.5), None)), Some("max_iterations_reached"));
+    }
+
+    #[test]
+    fn test_feedback_edge_and_task_context() {
+        let mut scope = ExecutionScope::new(
+            HashMap::from([("step_dev_output".to_string(), json!("fn main() {}"))]),
+            HashMap::from([("dev-qa".to_string(), edge(Some(0.9), None, 5))]),
+        );
+        let qa = FlowStep {
+            id: "qa".to_string(),
+            agent_id: None,
+            name: "QA".to_string(),
+            description: None,
+            step_type: FlowStepType::Llm,
+            parameters: HashMap::new(),
+            next_steps: Vec::new(),
+            condition: None,
+            timeout_seconds: Some(300),
+            retry_count: 0,
+            position: HashMap::new(),
+            agent_overrides: None,
+        };
+
+        assert_eq!(feedback_edge_into(&qa, Some("other"), &mut scope), None);
+        assert_eq!(feedback_edge_into(&qa, Some("dev"), &mut scope), Some("dev-qa".to_string()));
+
+        let context = feedback_task_context(&qa, Some("dev-qa"), &scope).unwrap();
+        assert!(context.contains("fn main() {}"));
+        assert!(context.contains("quality_score"));
+
+        // The source step gets the critique once the loop asks for another iteration
+        let mut dev = qa.clone();
+        dev.id = "dev".to_string();
+        scope.edge_states.get_mut("dev-qa").unwrap().feedback_history.push(HashMap::from([
+            ("target_feedback".to_string(), json!("Add tests")),
+            ("action".to_string(), json!("iterate")),
+        ]));
+        let context = feedback_task_context(&dev, None, &scope).unwrap();
+        assert!(context.contains("Add tests"));
+
+        // A feedback_loop step synthesizes its edge from parameters
+        let mut review = qa.clone();
+        review.id = "review".to_string();
+        review.step_type = FlowStepType::FeedbackLoop;
+        review.parameters.insert("max_iterations".to_string(), json!(3));
+        let edge_id = feedback_edge_into(&review, Some("dev"), &mut scope).unwrap();
+        assert_eq!(edge_id, "dev-review");
+        assert_eq!(scope.edge_states[&edge_id].max_iterations, Some(3));
+    }
+}
@@ -0,0 +1,315 @@
+//! Human input step: pauses the execution until a person submits values.
+//!
+//! Parameters:
+//! - `prompt`: what to ask, templated (defaults to the step description or name)
+//! - `fields`: the form, in the same shape as flow `inputs` (`type`, `required`,
+//!   `default`, `description`, `enum`, `pattern`). Strings are templated and a
+//!   lone `{{var}}` passes the variable's JSON value, so
+//!   `"enum": "{{step_plan_output}}"` offers the options an earlier step
+//!   proposed.
+//!
+//! While waiting, the form is listed in the execution's `pending_inputs` and the
+//! execution is `awaiting_input`. Values sent to `POST /api/executions/{id}/input`
+//! are validated against the fields; accepted values become variables of the same
+//! name and the step output. The step's `timeout_seconds` bounds the wait (none
+//! when 0).
+
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::bson::doc;
+use mongodb::Collection;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+use crate::db::collections::FLOW_EXECUTIONS;
+use crate::error::AppError;
+use crate::models::flow::{FlowInput, FlowStep, HumanInputSubmission, PendingInput};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::execution_control::{next_signal, ControlSignal};
+use crate::services::flow_inputs;
+use crate::services::flow_service::{resolve_value_variables, resolve_variables, ExecutionScope, FlowExecutor, FlowService};
+
+#[derive(Debug, Clone)]
+pub struct HumanInputConfig {
+    pub prompt: String,
+    pub fields: HashMap<String, FlowInput>,
+}
+
+impl HumanInputConfig {
+    /// Resolve the prompt and fields against the execution's variables and check the form
+    pub fn from_step(step: &FlowStep, variables: &HashMap<String, Value>) -> Result<Self, String> {
+        let fields = match step.parameters.get("fields") {
+            Some(fields) => serde_json::from_value::<HashMap<String, FlowInput>>(resolve_value_variables(fields, variables))
+                .map_err(|e| format!("Invalid human input fields: {}", e))?,
+            None => HashMap::new(),
+        };
+        if fields.is_empty() {
+            return Err("Human input step requires at least one field".to_string());
+        }
+        let errors = flow_inputs::validate_schema(&fields);
+        if !errors.is_empty() {
+            let problems: Vec<String> = errors.iter().map(|e| format!("{} {}", e.field, e.message)).collect();
+            return Err(format!("Invalid human input fields: {}", problems.join("; ")));
+        }
+
+        let prompt = step.parameters.get("prompt").and_then(|v| v.as_str())
+            .or(step.description.as_deref())
+            .unwrap_or(&step.name);
+        Ok(Self { prompt: resolve_variables(prompt, variables), fields })
+    }
+
+    /// Check the parameters when the flow is saved. Fields that take a value
+    /// from a variable are only checked when the step runs.
+    pub fn validate_parameters(parameters: &HashMap<String, Value>) -> Result<(), String> {
+        let fields = match parameters.get("fields") {
+            Some(Value::Object(fields)) if !fields.is_empty() => fields,
+            Some(Value::Object(_)) | None => return Err("Human input step requires at least one field".to_string()),
+            Some(_) => return Err("fields must be an object of field name to schema".to_string()),
+        };
+        let static_fields: HashMap<String, FlowInput> = fields.iter()
+            .filter(|(_, spec)| !spec.to_string().contains("{{") && !spec.to_string().contains("${"))
+            .map(|(name, spec)| {
+                serde_json::from_value(spec.clone())
+                    .map(|spec| (name.clone(), spec))
+                    .map_err(|e| format!("Invalid field '{}': {}", name, e))
+            })
+            .collect::<Result<_, _>>()?;
+        let errors = flow_inputs::validate_schema(&static_fields);
+        if let Some(error) = errors.first() {
+            return Err(format!("Field '{}' {}", error.field, error.message));
+        }
+        Ok(())
+    }
+}
+
+impl FlowExecutor {
+    pub(crate) async fn execute_human_input_step(
+        &self,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &ExecutionScope,
+    ) -> Result<Value, String> {
+        let config = HumanInputConfig::from_step(step, &scope.variables)?;
+        let oid = ObjectId::parse_str(execution_id).map_err(|e| e.to_string())?;
+        let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+
+        let wait = step.timeout_seconds.filter(|secs| *secs > 0)
+            .map(|secs| std::time::Duration::from_secs(secs as u64));
+        let now = Utc::now();
+        let pending = PendingInput {
+            step_id: step.id.clone(),
+            prompt: config.prompt.clone(),
+            fields: config.fields.clone(),
+            requested_at: now,
+            expires_at: wait.and_then(|w| chrono::Duration::from_std(w).ok()).map(|w| now + w),
+        };
+        // Subscribed before the request is visible, so no submission is missed
+        let mut signals = self.control(execution_id).subscribe();
+        let pending_bson = bson::to_bson(&pending).map_err(|e| e.to_string())?;
+        collection.update_one(
+            doc! { "_id": oid },
+            doc! { "$set": {
+                format!("pending_inputs.{}", step.id): pending_bson,
+                "status": "awaiting_input",
+                "updated_at": bson::DateTime::from_chrono(now),
+            }},
+        ).await.map_err(|e| format!("Failed to request input: {}", e))?;
+
+        let mut data = HashMap::from([
+            ("prompt".to_string(), json!(pending.prompt)),
+            ("fields".to_string(), json!(pending.fields)),
+            ("expires_at".to_string(), json!(pending.expires_at)),
+        ]);
+        scope.tag_event_data(&mut data);
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::InputRequired,
+            step_id: Some(step.id.clone()),
+            message: format!("Input required for step '{}': {}", step.name, pending.prompt),
+            data,
+            timestamp: Utc::now(),
+        }).await;
+
+        // Wait for the submission; cancelling the execution drops this step.
+        // One may already be recorded if the backend restarted after it arrived.
+        let deadline = wait.map(|w| tokio::time::Instant::now() + w);
+        let mut submission = recorded_submission(&collection, &oid, &step.id).await;
+        let values = loop {
+            if let Some(values) = submission.take() {
+                break values;
+            }
+            let Some(signal) = next_signal(&mut signals, deadline).await else {
+                self.service.withdraw_input_request(execution_id, &step.id).await;
+                return Err(format!("Timed out waiting for input after {}s", wait.unwrap_or_default().as_secs()));
+            };
+            submission = match signal {
+                Ok(ControlSignal::InputSubmitted { step_id, values }) if step_id == step.id => Some(values),
+                Ok(_) => None,
+                Err(broadcast::error::RecvError::Closed) => return Err("Execution is no longer running".to_string()),
+                Err(broadcast::error::RecvError::Lagged(_)) => recorded_submission(&collection, &oid, &step.id).await,
+            };
+        };
+
+        let _ = collection.update_one(
+            doc! { "_id": oid },
+            doc! { "$unset": { format!("input_submissions.{}", step.id): "" } },
+        ).await;
+        Ok(json!({
+            "output": values,
+            "mapped_variables": values,
+        }))
+    }
+}
+
+/// Values submitted for the step, as recorded on the execution
+async fn recorded_submission(
+    collection: &Collection<bson::Document>,
+    oid: &ObjectId,
+    step_id: &str,
+) -> Option<HashMap<String, Value>> {
+    let exec_doc = collection.find_one(doc! { "_id": oid }).await.ok().flatten()?;
+    let values = exec_doc.get_document("input_submissions").ok()?.get_document(step_id).ok()?;
+    bson::from_document(values.clone()).ok()
+}
+
+impl FlowService {
+    /// Validate values for a waiting human input step and hand them to it.
+    /// Returns the step ID and the accepted values.
+    pub async fn submit_input(
+        &self,
+        execution_id: &str,
+        user_id: &str,
+        submission: HumanInputSubmission,
+    ) -> Result<(String, HashMap<String, Value>), AppError> {
+        let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let oid = ObjectId::parse_str(execution_id)
+            .map_err(|_| AppError::BadRequest("Invalid execution ID".to_string()))?;
+
+        let exec_doc = collection
+            .find_one(doc! { "_id": oid, "user_id": user_id })
+            .await?
+            .ok_or_else(|| AppError::NotFound("Execution not found".to_string()))?;
+        let mut pending: HashMap<String, PendingInput> = exec_doc.get_document("pending_inputs").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default();
+
+        let step_id = match submission.step_id {
+            Some(step_id) => step_id,
+            None if pending.len() == 1 => pending.keys().next().cloned().unwrap_or_default(),
+            None if pending.is_empty() => return Err(AppError::Conflict("Execution is not waiting for input".to_string())),
+            None => {
+                let mut waiting: Vec<&String> = pending.keys().collect();
+                waiting.sort();
+                return Err(AppError::BadRequest(format!(
+                    "Several steps are waiting for input ({}); set step_id",
+                    waiting.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")
+                )));
+            }
+        };
+        let request = pending.remove(&step_id)
+            .ok_or_else(|| AppError::Conflict(format!("Step '{}' is not waiting for input", step_id)))?;
+        let values = flow_inputs::validate_inputs(&request.fields, &submission.values)
+            .map_err(AppError::InvalidInput)?;
+
+        // Only the first submission for a request is taken
+        let values_bson = bson::to_bson(&values).map_err(|e| AppError::Internal(e.to_string()))?;
+        let now = bson::DateTime::from_chrono(Utc::now());
+        let accepted = collection.update_one(
+            doc! { "_id": oid, format!("pending_inputs.{}", step_id): { "$exists": true } },
+            doc! {
+                "$set": { format!("input_submissions.{}", step_id): values_bson, "updated_at": now },
+                "$unset": { format!("pending_inputs.{}", step_id): "" },
+            },
+        ).await?.modified_count > 0;
+        if !accepted {
+            return Err(AppError::Conflict(format!("Step '{}' is not waiting for input", step_id)));
+        }
+        self.resume_if_no_input_pending(&oid).await;
+        self.controls.send(execution_id, ControlSignal::InputSubmitted { step_id: step_id.clone(), values: values.clone() });
+
+        self.emit_event(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::InputReceived,
+            step_id: Some(step_id.clone()),
+            message: format!("Input received for step '{}'", step_id),
+            data: HashMap::from([("values".to_string(), json!(values))]),
+            timestamp: Utc::now(),
+        }).await;
+
+        Ok((step_id, values))
+    }
+
+    /// Drop a step's request for input once it stops waiting
+    async fn withdraw_input_request(&self, execution_id: &str, step_id: &str) {
+        let Ok(oid) = ObjectId::parse_str(execution_id) else { return };
+        let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let _ = collection.update_one(
+            doc! { "_id": oid },
+            doc! { "$unset": { format!("pending_inputs.{}", step_id): "" } },
+        ).await;
+        self.resume_if_no_input_pending(&oid).await;
+    }
+
+    /// Go back to `running` when no other branch is still waiting for input
+    async fn resume_if_no_input_pending(&self, oid: &ObjectId) {
+        let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let _ = collection.update_one(
+            doc! { "_id": oid, "status": "awaiting_input", "pending_inputs": {} },
+            doc! { "$set": { "status": "running", "updated_at": bson::DateTime::from_chrono(Utc::now()) } },
+        ).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(parameters: Value) -> FlowStep {
+        serde_json::from_value(json!({
+            "id": "ask", "name": "Ask", "type": "human_input", "parameters": parameters,
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_fields_resolve_from_variables() {
+        let step = step(json!({
+            "prompt": "Which fix for {{issue}}?",
+            "fields": {
+                "choice": {"type": "string", "required": true, "enum": "{{step_plan_output}}"},
+                "note": {"type": "string"},
+            },
+        }));
+        let variables = HashMap::from([
+            ("issue".to_string(), json!("#42")),
+            ("step_plan_output".to_string(), json!(["patch", "revert"])),
+        ]);
+        let config = HumanInputConfig::from_step(&step, &variables).unwrap();
+        assert_eq!(config.prompt, "Which fix for #42?");
+        assert_eq!(config.fields["choice"].allowed_values, Some(vec![json!("patch"), json!("revert")]));
+
+        let values = HashMap::from([("choice".to_string(), json!("revert"))]);
+        assert!(flow_inputs::validate_inputs(&config.fields, &values).is_ok());
+        let values = HashMap::from([("choice".to_string(), json!("rewrite"))]);
+        assert!(flow_inputs::validate_inputs(&config.fields, &values).is_err());
+    }
+
+    #[test]
+    fn test_validate_parameters() {
+        let parameters = |v: Value| serde_json::from_value::<HashMap<String, Value>>(v).unwrap();
+        assert!(HumanInputConfig::validate_parameters(&parameters(json!({}))).is_err());
+        assert!(HumanInputConfig::validate_parameters(&parameters(json!({"fields": {}}))).is_err());
+        assert!(HumanInputConfig::validate_parameters(&parameters(json!({
+            "fields": {"path": {"type": "string", "pattern": "^src/"}},
+        }))).is_ok());
+        assert!(HumanInputConfig::validate_parameters(&parameters(json!({
+            "fields": {"count": {"type": "integer", "pattern": "^1"}},
+        }))).is_err());
+        // Checked at run time
+        assert!(HumanInputConfig::validate_parameters(&parameters(json!({
+            "fields": {"choice": {"enum": "{{options}}"}},
+        }))).is_ok());
+    }
+}
@@ -0,0 +1,318 @@
+//! Map step: runs a body sub-graph once per item of a JSON array.
+//!
+//! Parameters:
+//! - `items`: the array, as a variable name or dotted path such as
+//!   `step_list_output.files` (optionally wrapped in `{{ }}`), or a literal array.
+//!   A string holding a JSON array, like an LLM reply, is parsed.
+//! - `body_step_id`: first step of the body; each run ends at a step with no
+//!   successor or at the map step's own next step
+//! - `item_variable` / `index_variable`: names the current item and its index
+//!   get in the body's scope (default `item` and `item_index`)
+//! - `concurrency`: how many items run at once (default 1); items using the
+//!   same MCP connection share its session
+//! - `on_error`: `"fail_fast"` (default) stops at the first failed item and
+//!   cancels the others; `"collect"` runs every item and reports failures in
+//!   the result
+//!
+//! Each item runs in its own forked scope, so its variables don't leak into the
+//! flow; the step output is the list of per-item outputs (the output of each
+//! run's last step, `null` for failed items). Events from the body carry the
+//! `item_index`. Body steps keep their own time limits; the map step has none
+//! over all items.
+
+use chrono::Utc;
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::models::flow::{Flow, FlowStep};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::flow_executor::step_handlers::parallel_step::reachable_steps;
+use crate::services::flow_service::{resolve_value_variables, ExecutionScope, FlowExecutor};
+use crate::utils::json_path::get_path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapErrorMode {
+    FailFast,
+    Collect,
+}
+
+#[derive(Debug, Clone)]
+pub struct MapConfig {
+    pub items: Value,
+    pub body_step_id: String,
+    pub item_variable: String,
+    pub index_variable: String,
+    pub concurrency: usize,
+    pub on_error: MapErrorMode,
+}
+
+impl MapConfig {
+    pub fn from_parameters(parameters: &HashMap<String, Value>) -> Result<Self, String> {
+        let items = parameters.get("items")
+            .filter(|v| v.is_array() || v.as_str().is_some_and(|s| !s.trim().is_empty()))
+            .cloned()
+            .ok_or("Map step requires an items parameter (a variable path or an array)")?;
+        let string_param = |key: &str| parameters.get(key).and_then(|v| v.as_str()).filter(|s| !s.trim().is_empty());
+
+        let body_step_id = string_param("body_step_id")
+            .ok_or("Map step requires body_step_id parameter")?
+            .to_string();
+        let item_variable = string_param("item_variable").unwrap_or("item").to_string();
+        let index_variable = string_param("index_variable").unwrap_or("item_index").to_string();
+
+        let concurrency = match parameters.get("concurrency") {
+            None | Some(Value::Null) => 1,
+            Some(value) => match value.as_u64() {
+                Some(n) if n >= 1 => n as usize,
+                _ => return Err("concurrency must be a positive integer".to_string()),
+            },
+        };
+
+        let on_error = match string_param("on_error").unwrap_or("fail_fast") {
+            "fail_fast" => MapErrorMode::FailFast,
+            "collect" => MapErrorMode::Collect,
+            other => return Err(format!("Unknown on_error '{}', expected 'fail_fast' or 'collect'", other)),
+        };
+
+        Ok(Self { items, body_step_id, item_variable, index_variable, concurrency, on_error })
+    }
+
+    /// The array to map over, looked up in `variables`
+    pub fn resolve_items(&self, variables: &HashMap<String, Value>) -> Result<Vec<Value>, String> {
+        let path = match &self.items {
+            Value::Array(items) => {
+                return Ok(items.iter().map(|item| resolve_value_variables(item, variables)).collect());
+            }
+            Value::String(path) => path,
+            _ => return Err("items must be a variable path or an array".to_string()),
+        };
+
+        let trimmed = path.trim();
+        let path = trimmed.strip_prefix("{{").and_then(|p| p.strip_suffix("}}"))
+            .or_else(|| trimmed.strip_prefix("${").and_then(|p| p.strip_suffix('}')))
+            .unwrap_or(trimmed)
+            .trim();
+        let root = Value::Object(variables.iter().map(|(k, v)| (k.clone(), v.clone())).collect());
+        match get_path(&root, path) {
+            Some(Value::Array(items)) => Ok(items.clone()),
+            Some(Value::String(text)) => match serde_json::from_str::<Value>(text.trim()) {
+                Ok(Value::Array(items)) => Ok(items),
+                _ => Err(format!("items '{}' is a string that does not hold a JSON array", path)),
+            },
+            Some(other) => Err(format!("items '{}' is not an array (got {})", path, json_type(other))),
+            None => Err(format!("items '{}' is not defined", path)),
+        }
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+impl FlowExecutor {
+    pub(crate) async fn execute_map_step(
+        &self,
+        flow: &Flow,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &ExecutionScope,
+    ) -> Result<Value, String> {
+        let config = MapConfig::from_parameters(&step.parameters)?;
+        let items = config.resolve_items(&scope.variables)?;
+        let total = items.len();
+        let stop_at = step.next_steps.first().map(String::as_str);
+
+        let mut progress_data = HashMap::from([
+            ("total".to_string(), json!(total)),
+            ("concurrency".to_string(), json!(config.concurrency)),
+            ("body_step_id".to_string(), json!(config.body_step_id)),
+        ]);
+        scope.tag_event_data(&mut progress_data);
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::StepProgress,
+            step_id: Some(step.id.clone()),
+            message: format!("Mapping over {} items", total),
+            data: progress_data,
+            timestamp: Utc::now(),
+        }).await;
+
+        let run_item = |index: usize, item: Value| {
+            let mut item_scope = scope.fork(&format!("{}[{}]", step.id, index));
+            item_scope.item_index = Some(index);
+            item_scope.variables.insert(config.item_variable.clone(), item);
+            item_scope.variables.insert(config.index_variable.clone(), json!(index));
+            let body_step_id = config.body_step_id.as_str();
+            async move {
+                let result = self.run_steps(flow, execution_id, body_step_id, stop_at, &mut item_scope).await;
+                let output = result.map(|()| {
+                    item_scope.completed_steps.last()
+                        .and_then(|last| item_scope.variables.get(&format!("step_{}_output", last)))
+                        .cloned()
+                        .unwrap_or(Value::Null)
+                });
+                (index, output.map_err(|e| e.to_string()))
+            }
+        };
+
+        let mut queue = items.into_iter().enumerate();
+        let mut running: FuturesUnordered<_> = queue.by_ref().take(config.concurrency)
+            .map(|(index, item)| run_item(index, item))
+            .collect();
+        let mut results: Vec<Option<Result<Value, String>>> = vec![None; total];
+        let (mut completed, mut failed) = (0, 0);
+
+        while let Some((index, result)) = running.next().await {
+            let status = if result.is_ok() { "completed" } else { "failed" };
+            let error = result.as_ref().err().cloned();
+            if result.is_ok() { completed += 1 } else { failed += 1 }
+            results[index] = Some(result);
+
+            let mut item_data = HashMap::from([
+                ("item_index".to_string(), json!(index)),
+                ("status".to_string(), json!(status)),
+                ("error".to_string(), json!(error)),
+                ("completed".to_string(), json!(completed)),
+                ("failed".to_string(), json!(failed)),
+                ("total".to_string(), json!(total)),
+            ]);
+            scope.tag_event_data(&mut item_data);
+            self.emit(FlowExecutionEvent {
+                id: None,
+                execution_id: execution_id.to_string(),
+                event_type: FlowEventType::StepProgress,
+                step_id: Some(step.id.clone()),
+                message: format!("Item {} {} ({}/{} done)", index, status, completed + failed, total),
+                data: item_data,
+                timestamp: Utc::now(),
+            }).await;
+
+            if let (Some(error), MapErrorMode::FailFast) = (error, config.on_error) {
+                // Dropping the other runs cancels items still in progress,
+                // whose body steps are then recorded as cancelled
+                if !running.is_empty() {
+                    drop(running);
+                    let body = reachable_steps(flow, &[config.body_step_id.as_str()], stop_at);
+                    self.cancel_running_steps(execution_id, Some(&body)).await;
+                }
+                return Err(format!("Item {} failed: {}", index, error));
+            }
+            if let Some((index, item)) = queue.next() {
+                running.push(run_item(index, item));
+            }
+        }
+
+        let mut outputs = Vec::with_capacity(total);
+        let mut item_results = Vec::with_capacity(total);
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Some(Ok(output)) => {
+                    item_results.push(json!({"index": index, "status": "completed", "output": output}));
+                    outputs.push(output);
+                }
+                Some(Err(error)) => {
+                    item_results.push(json!({"index": index, "status": "failed", "error": error}));
+                    outputs.push(Value::Null);
+                }
+                None => unreachable!("every item runs when errors are collected"),
+            }
+        }
+
+        Ok(json!({
+            "output": outputs,
+            "items": item_results,
+            "total": total,
+            "completed": completed,
+            "failed": failed,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::services::mcp_session_manager::McpSessionManager;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    fn config(value: Value) -> MapConfig {
+        MapConfig::from_parameters(&serde_json::from_value(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_config_defaults_and_errors() {
+        let map = config(json!({"items": "files", "body_step_id": "review"}));
+        assert_eq!(map.item_variable, "item");
+        assert_eq!(map.index_variable, "item_index");
+        assert_eq!(map.concurrency, 1);
+        assert_eq!(map.on_error, MapErrorMode::FailFast);
+
+        let map = config(json!({"items": "files", "body_step_id": "review", "concurrency": 4, "on_error": "collect"}));
+        assert_eq!(map.concurrency, 4);
+        assert_eq!(map.on_error, MapErrorMode::Collect);
+
+        for bad in [
+            json!({"body_step_id": "review"}),
+            json!({"items": "files"}),
+            json!({"items": "files", "body_step_id": "review", "concurrency": 0}),
+            json!({"items": "files", "body_step_id": "review", "on_error": "ignore"}),
+        ] {
+            let params: HashMap<String, Value> = serde_json::from_value(bad.clone()).unwrap();
+            assert!(MapConfig::from_parameters(&params).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_resolve_items() {
+        let variables = HashMap::from([
+            ("step_list_output".to_string(), json!({"files": ["a.rs", "b.rs"]})),
+            ("reply".to_string(), json!("[1, 2, 3]")),
+            ("issue".to_string(), json!("#12")),
+            ("count".to_string(), json!(3)),
+        ]);
+
+        let items = |value: Value| config(json!({"items": value, "body_step_id": "b"})).resolve_items(&variables);
+        assert_eq!(items(json!("step_list_output.files")).unwrap(), vec![json!("a.rs"), json!("b.rs")]);
+        assert_eq!(items(json!("{{ step_list_output.files }}")).unwrap().len(), 2);
+        assert_eq!(items(json!("reply")).unwrap(), vec![json!(1), json!(2), json!(3)]);
+        assert_eq!(items(json!(["{{issue}}", "other"])).unwrap(), vec![json!("#12"), json!("other")]);
+        assert!(items(json!("count")).unwrap_err().contains("not an array"));
+        assert!(items(json!("missing")).unwrap_err().contains("not defined"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_items_share_an_mcp_connection() {
+        let mcp = Arc::new(McpSessionManager::new());
+        let stats = mcp.connect_fake("search", std::time::Duration::from_millis(200)).await;
+        let executor = FlowExecutor::offline(mcp).await;
+
+        let flow: Flow = serde_json::from_value(json!({
+            "user_id": "u",
+            "name": "f",
+            "start_step_id": "each",
+            "steps": [
+                {"id": "each", "name": "Each", "type": "map", "parameters": {
+                    "items": [1, 2, 3, 4], "body_step_id": "lookup", "concurrency": 3,
+                }},
+                {"id": "lookup", "name": "Lookup", "type": "tool", "parameters": {
+                    "connection_id": "search", "tool_name": "lookup", "arguments": {},
+                }},
+            ],
+        })).unwrap();
+        let result = executor.execute_map_step(&flow, &flow.steps[0], "offline", &ExecutionScope::default()).await.unwrap();
+        assert_eq!(result["completed"], 4);
+        assert_eq!(result["failed"], 0);
+        assert_eq!(stats.calls.load(Ordering::SeqCst), 4);
+        assert_eq!(stats.max_in_flight.load(Ordering::SeqCst), 3);
+    }
+}
@@ -5,3 +5,12 @@ pub mod condition_step;
 pub mod parallel_step;
 pub mod approval_step;
 pub mod feedback_loop;
+pub mod quality_check_step;
+pub mod webhook_step;
+pub mod switch_step;
+pub mod structured_output;
+pub mod sub_flow_step;
+pub mod map_step;
+pub mod human_input_step;
+pub mod delay_step;
+pub mod wait_for_signal_step;
@@ -1 +1,438 @@
-// TODO: Phase 8 - Parallel execution step (tokio::JoinSet)
+//! Parallel execution step: runs every branch in `next_steps` concurrently,
+//! each in its own variable scope, and joins them before continuing.
+//!
+//! Parameters:
+//! - `join_step_id`: step where branches converge (inferred when omitted)
+//! - `join_mode`: `"all"` (default), `"first"` or `"n_of_m"`
+//! - `join_count`: number of branches that must succeed for `"n_of_m"`
+
+use chrono::Utc;
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::models::flow::{Flow, FlowStep};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::flow_service::{ExecutionScope, FlowExecutor};
+
+/// How many branches must finish before the parallel step joins
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinMode {
+    /// Wait for every branch; any failure fails the step
+    All,
+    /// Continue as soon as one branch succeeds, cancelling the rest
+    First,
+    /// Continue once N branches succeed, cancelling the rest
+    NOfM(usize),
+}
+
+impl JoinMode {
+    pub fn from_parameters(
+        parameters: &HashMap<String, Value>,
+        branch_count: usize,
+    ) -> Result<Self, String> {
+        let mode = parameters.get("join_mode").and_then(|v| v.as_str()).unwrap_or("all");
+        match mode {
+            "all" => Ok(JoinMode::All),
+            "first" => Ok(JoinMode::First),
+            "n_of_m" => {
+                let n = parameters.get("join_count")
+                    .and_then(|v| v.as_u64())
+                    .ok_or("join_mode 'n_of_m' requires a numeric join_count")? as usize;
+                if n == 0 || n > branch_count {
+                    return Err(format!(
+                        "join_count must be between 1 and {} (number of branches), got {}",
+                        branch_count, n
+                    ));
+                }
+                Ok(JoinMode::NOfM(n))
+            }
+            other => Err(format!("Unknown join_mode '{}'", other)),
+        }
+    }
+
+    /// Number of successful branches needed to join
+    pub fn required(&self, branch_count: usize) -> usize {
+        match self {
+            JoinMode::All => branch_count,
+            JoinMode::First => 1,
+            JoinMode::NOfM(n) => *n,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JoinMode::All => "all",
+            JoinMode::First => "first",
+            JoinMode::NOfM(_) => "n_of_m",
+        }
+    }
+}
+
+/// Find the nearest step reachable from every branch, i.e. where the branches converge.
+/// Ties are broken by the order of steps in the flow.
+pub fn find_join_step(flow: &Flow, branches: &[String]) -> Option<String> {
+    if branches.is_empty() {
+        return None;
+    }
+
+    let step_map: HashMap<&str, &FlowStep> = flow.steps.iter()
+        .map(|s| (s.id.as_str(), s))
+        .collect();
+
+    // Shortest distance from each branch start to every reachable step
+    let distances: Vec<HashMap<&str, usize>> = branches.iter().map(|branch| {
+        let mut dist: HashMap<&str, usize> = HashMap::new();
+        let mut queue = VecDeque::from([(branch.as_str(), 0usize)]);
+        while let Some((id, d)) = queue.pop_front() {
+            if dist.contains_key(id) {
+                continue;
+            }
+            dist.insert(id, d);
+            if let Some(step) = step_map.get(id) {
+                for next in &step.next_steps {
+                    queue.push_back((next.as_str(), d + 1));
+                }
+            }
+        }
+        dist
+    }).collect();
+
+    let branch_ids: HashSet<&str> = branches.iter().map(String::as_str).collect();
+
+    flow.steps.iter()
+        .filter(|s| !branch_ids.contains(s.id.as_str()))
+        .filter_map(|s| {
+            let furthest = distances.iter()
+                .map(|d| d.get(s.id.as_str()).copied())
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
+                .max()?;
+            Some((furthest, s.id.clone()))
+        })
+        .min_by_key(|(furthest, _)| *furthest)
+        .map(|(_, id)| id)
+}
+
+/// Steps a run from `starts` can reach before `stop_at`
+pub(crate) fn reachable_steps(flow: &Flow, starts: &[&str], stop_at: Option<&str>) -> HashSet<String> {
+    let step_map: HashMap<&str, &FlowStep> = flow.steps.iter()
+        .map(|s| (s.id.as_str(), s))
+        .collect();
+    let mut reached = HashSet::new();
+    let mut queue: VecDeque<&str> = starts.iter().copied().collect();
+    while let Some(id) = queue.pop_front() {
+        if Some(id) == stop_at || !reached.insert(id.to_string()) {
+            continue;
+        }
+        if let Some(step) = step_map.get(id) {
+            queue.extend(step.next_steps.iter().map(String::as_str));
+        }
+    }
+    reached
+}
+
+/// Merge the variables a branch produced back into the parent scope.
+/// Only keys the branch added or changed relative to `base` are copied.
+pub(crate) fn merge_branch_scope(
+    parent: &mut ExecutionScope,
+    branch: &ExecutionScope,
+    base: &HashMap<String, Value>,
+) {
+    for (key, value) in &branch.variables {
+        if base.get(key) != Some(value) {
+            parent.variables.insert(key.clone(), value.clone());
+        }
+    }
+    for step_id in &branch.completed_steps {
+        parent.mark_completed(step_id);
+    }
+    // Feedback loops that ran inside the branch carry their state back out
+    for (edge_id, edge) in &branch.edge_states {
+        if branch.completed_steps.contains(&edge.target_step_id) {
+            parent.edge_states.insert(edge_id.clone(), edge.clone());
+        }
+    }
+}
+
+impl FlowExecutor {
+    pub(crate) async fn execute_parallel_step(
+        &self,
+        flow: &Flow,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &mut ExecutionScope,
+    ) -> Result<Value, String> {
+        let branches = &step.next_steps;
+        if branches.is_empty() {
+            return Err("Parallel step requires at least one branch in next_steps".to_string());
+        }
+
+        let mode = JoinMode::from_parameters(&step.parameters, branches.len())?;
+        let join_step_id = step.parameters.get("join_step_id")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .or_else(|| find_join_step(flow, branches));
+
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::StepProgress,
+            step_id: Some(step.id.clone()),
+            message: format!("Starting {} parallel branches", branches.len()),
+            data: HashMap::from([
+                ("branches".to_string(), json!(branches)),
+                ("join_mode".to_string(), json!(mode.as_str())),
+                ("join_step_id".to_string(), json!(join_step_id)),
+            ]),
+            timestamp: Utc::now(),
+        }).await;
+
+        let base_variables = scope.variables.clone();
+        let join = join_step_id.as_deref();
+
+        let mut pending: FuturesUnordered<_> = branches.iter().map(|branch_id| {
+            let mut branch_scope = scope.fork(branch_id);
+            async move {
+                let result = self.run_steps(flow, execution_id, branch_id, join, &mut branch_scope).await;
+                (branch_id.clone(), result, branch_scope)
+            }
+        }).collect();
+
+        let required = mode.required(branches.len());
+        let mut succeeded: Vec<(String, ExecutionScope)> = Vec::new();
+        let mut failed: Vec<(String, String)> = Vec::new();
+
+        while let Some((branch_id, result, branch_scope)) = pending.next().await {
+            match result {
+                Ok(()) => succeeded.push((branch_id, branch_scope)),
+                Err(e) => failed.push((branch_id, e.to_string())),
+            }
+            if succeeded.len() >= required || failed.len() > branches.len() - required {
+                break;
+            }
+        }
+        // Dropping the remaining futures cancels branches that are still running
+        drop(pending);
+
+        let cancelled: Vec<&String> = branches.iter()
+            .filter(|b| !succeeded.iter().any(|(id, _)| id == *b) && !failed.iter().any(|(id, _)| id == *b))
+            .collect();
+
+        // Their steps were dropped mid-run, so their results still say running
+        if !cancelled.is_empty() {
+            let starts: Vec<&str> = cancelled.iter().map(|b| b.as_str()).collect();
+            let dropped = reachable_steps(flow, &starts, join);
+            self.cancel_running_steps(execution_id, Some(&dropped)).await;
+        }
+        for branch_id in &cancelled {
+            self.emit(FlowExecutionEvent {
+                id: None,
+                execution_id: execution_id.to_string(),
+                event_type: FlowEventType::StepProgress,
+                step_id: Some(step.id.clone()),
+                message: format!("Branch '{}' cancelled", branch_id),
+                data: HashMap::from([("branch_id".to_string(), json!(branch_id))]),
+                timestamp: Utc::now(),
+            }).await;
+        }
+
+        if succeeded.len() < required {
+            let errors: Vec<String> = failed.iter()
+                .map(|(id, e)| format!("{}: {}", id, e))
+                .collect();
+            return Err(format!(
+                "{} of {} required branches succeeded ({})",
+                succeeded.len(), required, errors.join("; ")
+            ));
+        }
+
+        // Merge in flow order so results are deterministic
+        let mut outputs = serde_json::Map::new();
+        let mut summary = serde_json::Map::new();
+        for branch_id in branches {
+            if let Some((_, branch_scope)) = succeeded.iter().find(|(id, _)| id == branch_id) {
+                merge_branch_scope(scope, branch_scope, &base_variables);
+                let output = branch_scope.completed_steps.last()
+                    .and_then(|last| branch_scope.variables.get(&format!("step_{}_output", last)))
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                outputs.insert(branch_id.clone(), output);
+                summary.insert(branch_id.clone(), json!({
+                    "status": "completed",
+                    "completed_steps": branch_scope.completed_steps,
+                }));
+            } else if let Some((_, error)) = failed.iter().find(|(id, _)| id == branch_id) {
+                summary.insert(branch_id.clone(), json!({"status": "failed", "error": error}));
+            } else {
+                summary.insert(branch_id.clone(), json!({"status": "cancelled"}));
+            }
+        }
+
+        Ok(json!({
+            "output": outputs,
+            "branches": summary,
+            "join_mode": mode.as_str(),
+            "join_step_id": join_step_id,
+            "next_step_id": join_step_id,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::flow::FlowStepType;
+    use crate::services::mcp_session_manager::McpSessionManager;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    fn step(id: &str, next: &[&str]) -> FlowStep {
+        FlowStep {
+            id: id.to_string(),
+            agent_id: None,
+            name: id.to_string(),
+            description: None,
+            step_type: FlowStepType::Llm,
+            parameters: HashMap::new(),
+            next_steps: next.iter().map(|s| s.to_string()).collect(),
+            condition: None,
+            timeout_seconds: Some(300),
+            retry_count: 0,
+            position: HashMap::new(),
+            agent_overrides: None,
+        }
+    }
+
+    fn flow(steps: Vec<FlowStep>) -> Flow {
+        Flow {
+            id: None,
+            user_id: "u".to_string(),
+            name: "f".to_string(),
+            description: None,
+            start_step_id: steps[0].id.clone(),
+            steps,
+            variables: HashMap::new(),
+            inputs: HashMap::new(),
+            metadata: HashMap::new(),
+            edge_metadata: HashMap::new(),
+            is_active: true,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn tool_step(id: &str, next: &[&str]) -> FlowStep {
+        FlowStep {
+            step_type: FlowStepType::Tool,
+            parameters: HashMap::from([
+                ("connection_id".to_string(), json!("search")),
+                ("tool_name".to_string(), json!("lookup")),
+            ]),
+            ..step(id, next)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_branches_share_an_mcp_connection() {
+        let mcp = Arc::new(McpSessionManager::new());
+        let stats = mcp.connect_fake("search", std::time::Duration::from_millis(200)).await;
+        let executor = FlowExecutor::offline(mcp).await;
+
+        let mut fan = step("fan", &["a", "b"]);
+        fan.step_type = FlowStepType::Parallel;
+        let mut join = step("join", &[]);
+        join.step_type = FlowStepType::Condition;
+        let flow = flow(vec![fan, tool_step("a", &["join"]), tool_step("b", &["join"]), join]);
+
+        let mut scope = ExecutionScope::default();
+        executor.run_steps(&flow, "offline", "fan", None, &mut scope).await.unwrap();
+
+        for branch in ["a", "b"] {
+            assert_eq!(scope.variables[&format!("step_{}_output", branch)]["content"][0]["text"], "ok");
+        }
+        assert_eq!(stats.max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_join_mode_parsing() {
+        let params = HashMap::new();
+        assert_eq!(JoinMode::from_parameters(&params, 3).unwrap(), JoinMode::All);
+
+        let params = HashMap::from([("join_mode".to_string(), json!("first"))]);
+        assert_eq!(JoinMode::from_parameters(&params, 3).unwrap(), JoinMode::First);
+
+        let params = HashMap::from([
+            ("join_mode".to_string(), json!("n_of_m")),
+            ("join_count".to_string(), json!(2)),
+        ]);
+        let mode = JoinMode::from_parameters(&params, 3).unwrap();
+        assert_eq!(mode, JoinMode::NOfM(2));
+        assert_eq!(mode.required(3), 2);
+
+        let params = HashMap::from([
+            ("join_mode".to_string(), json!("n_of_m")),
+            ("join_count".to_string(), json!(4)),
+        ]);
+        assert!(JoinMode::from_parameters(&params, 3).is_err());
+
+        let params = HashMap::from([("join_mode".to_string(), json!("some"))]);
+        assert!(JoinMode::from_parameters(&params, 3).is_err());
+    }
+
+    #[test]
+    fn test_find_join_step() {
+        // fan -> review, test -> lint -> merge ; review -> merge
+        let f = flow(vec![
+            step("fan", &["review", "test"]),
+            step("review", &["merge"]),
+            step("test", &["lint"]),
+            step("lint", &["merge"]),
+            step("merge", &["done"]),
+            step("done", &[]),
+        ]);
+        let branches = vec!["review".to_string(), "test".to_string()];
+        assert_eq!(find_join_step(&f, &branches), Some("merge".to_string()));
+
+        // Branches that never converge have no join point
+        let f = flow(vec![
+            step("fan", &["a", "b"]),
+            step("a", &[]),
+            step("b", &[]),
+        ]);
+        let branches = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(find_join_step(&f, &branches), None);
+    }
+
+    #[test]
+    fn test_reachable_steps_stop_at_the_join() {
+        let f = flow(vec![
+            step("fan", &["review", "test"]),
+            step("review", &["merge"]),
+            step("test", &["lint"]),
+            step("lint", &["test", "merge"]),
+            step("merge", &["done"]),
+            step("done", &[]),
+        ]);
+        let reached = reachable_steps(&f, &["test"], Some("merge"));
+        assert_eq!(reached, HashSet::from(["test".to_string(), "lint".to_string()]));
+        assert_eq!(reachable_steps(&f, &["review"], None).len(), 3);
+    }
+
+    #[test]
+    fn test_merge_branch_scope() {
+        let base = HashMap::from([("shared".to_string(), json!("original"))]);
+        let mut parent = ExecutionScope::new(base.clone(), HashMap::new());
+
+        let mut branch = parent.fork("review");
+        branch.variables.insert("step_review_output".to_string(), json!("LGTM"));
+        branch.completed_steps.push("review".to_string());
+
+        merge_branch_scope(&mut parent, &branch, &base);
+
+        assert_eq!(parent.variables.get("step_review_output"), Some(&json!("LGTM")));
+        assert_eq!(parent.variables.get("shared"), Some(&json!("original")));
+        assert_eq!(parent.completed_steps, vec!["review".to_string()]);
+    }
+}
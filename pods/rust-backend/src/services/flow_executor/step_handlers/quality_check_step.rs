@@ -0,0 +1,162 @@
+//! Quality check step: an LLM judge scores an earlier step's output against a rubric.
+//!
+//! Parameters:
+//! - `rubric`: scoring criteria (supports `{{var}}` placeholders)
+//! - `target_step_id`: step whose output is scored (defaults to the previous step)
+//! - `judge_agent_id` / `judge_llm_id`: who scores it (defaults to the step's `agent_id`);
+//!   a judge LLM must belong to the flow's owner
+//! - `threshold`: minimum score to pass, 0.0-1.0 (default 0.8)
+//!
+//! Passing continues to `next_steps[0]`, failing to `next_steps[1]`. Without a fail
+//! branch the step fails, unless it is the reviewer of a feedback loop, which then
+//! decides whether to iterate.
+
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::models::flow::{Flow, FlowStep};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::flow_executor::step_handlers::feedback_loop::parse_feedback_verdict;
+use crate::services::flow_service::{resolve_variables, ExecutionScope, FlowExecutor};
+
+const DEFAULT_PASS_THRESHOLD: f64 = 0.8;
+
+const JUDGE_SYSTEM_PROMPT: &str = "You are an impartial quality reviewer. Score work strictly against the rubric you are given.";
+
+/// Build the prompt sent to the judge
+pub fn build_judge_prompt(rubric: &str, output: &str) -> String {
+    format!(
+        "## Rubric\n{}\n\n## Output to evaluate\n{}\n\n## Instructions\nScore the output against the rubric. Respond with only a JSON object:\n{{\"score\": <0.0-1.0>, \"rationale\": \"<why this score>\"}}",
+        rubric, output
+    )
+}
+
+impl FlowExecutor {
+    pub(crate) async fn execute_quality_check_step(
+        &self,
+        flow: &Flow,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &mut ExecutionScope,
+    ) -> Result<Value, String> {
+        let rubric = step.parameters.get("rubric")
+            .and_then(|v| v.as_str())
+            .map(|r| resolve_variables(r, &scope.variables))
+            .ok_or("Quality check step requires a rubric parameter")?;
+
+        let target_step_id = step.parameters.get("target_step_id")
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .or_else(|| scope.completed_steps.last().cloned())
+            .ok_or("Quality check step has no target_step_id and no previous step to evaluate")?;
+
+        let output = match scope.variables.get(&format!("step_{}_output", target_step_id)) {
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => serde_json::to_string(other).unwrap_or_default(),
+            None => return Err(format!("No output found for step '{}'", target_step_id)),
+        };
+
+        let threshold = step.parameters.get("threshold")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(DEFAULT_PASS_THRESHOLD);
+
+        let prompt = build_judge_prompt(&rubric, &output);
+        let mut client = self.agent_client(flow, step);
+
+        let judge_llm_id = step.parameters.get("judge_llm_id").and_then(|v| v.as_str());
+        let judge_agent_id = step.parameters.get("judge_agent_id")
+            .and_then(|v| v.as_str())
+            .or(step.agent_id.as_deref());
+
+        let judge_result = match (judge_agent_id, judge_llm_id) {
+            (_, Some(llm_id)) => client.execute_llm_prompt(llm_id, Some(JUDGE_SYSTEM_PROMPT), &prompt).await,
+            (Some(agent_id), None) => {
+                let callback = self.agent_event_callback(execution_id, &step.id);
+                client.execute_agent_step(agent_id, &step.name, &json!({"task": prompt}), None, None, Some(callback)).await
+            }
+            (None, None) => return Err("Quality check step requires judge_agent_id, judge_llm_id or agent_id".to_string()),
+        };
+
+        if judge_result.get("success").and_then(|v| v.as_bool()) != Some(true) {
+            return Err(judge_result.get("error").and_then(|v| v.as_str()).unwrap_or("Judge call failed").to_string());
+        }
+
+        let verdict = parse_feedback_verdict(judge_result.get("content").unwrap_or(&Value::Null));
+        let score = verdict.quality_score
+            .ok_or("Judge response did not contain a score")?;
+        let passed = score >= threshold;
+
+        // Feedback loops refining the scored step see this score in their history
+        for edge in scope.edge_states.values_mut()
+            .filter(|e| e.is_feedback_loop && e.source_step_id == target_step_id && e.target_step_id != step.id)
+        {
+            edge.quality_scores.push(score);
+        }
+
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: if passed { FlowEventType::QualityCheckPassed } else { FlowEventType::QualityCheckFailed },
+            step_id: Some(step.id.clone()),
+            message: format!(
+                "Quality check {} for step '{}': score {:.2} (threshold {:.2})",
+                if passed { "passed" } else { "failed" }, target_step_id, score, threshold
+            ),
+            data: HashMap::from([
+                ("target_step_id".to_string(), json!(target_step_id)),
+                ("quality_score".to_string(), json!(score)),
+                ("threshold".to_string(), json!(threshold)),
+                ("passed".to_string(), json!(passed)),
+                ("rationale".to_string(), json!(verdict.feedback)),
+            ]),
+            timestamp: Utc::now(),
+        }).await;
+
+        let next_step = if passed {
+            step.next_steps.first().cloned()
+        } else {
+            step.next_steps.get(1).cloned()
+        };
+
+        let reviews_feedback_loop = scope.edge_states.values()
+            .any(|e| e.is_feedback_loop && e.target_step_id == step.id);
+        if !passed && next_step.is_none() && !reviews_feedback_loop {
+            return Err(format!(
+                "Quality check failed: score {:.2} below threshold {:.2}: {}",
+                score, threshold, verdict.feedback
+            ));
+        }
+
+        Ok(json!({
+            "output": {
+                "quality_score": score,
+                "passed": passed,
+                "rationale": verdict.feedback,
+                "target_step_id": target_step_id,
+            },
+            "quality_score": score,
+            "passed": passed,
+            "threshold": threshold,
+            "judge_result": judge_result,
+            "next_step_id": next_step,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_judge_prompt_and_response_parsing() {
+        let prompt = build_judge_prompt("Covers edge cases", "fn add(a, b) { a + b }");
+        assert!(prompt.contains("Covers edge cases"));
+        assert!(prompt.contains("fn add(a, b)"));
+        assert!(prompt.contains("\"score\""));
+
+        let verdict = parse_feedback_verdict(&json!("```json\n{\"score\": 0.75, \"rationale\": \"Misses overflow\"}\n```"));
+        assert_eq!(verdict.quality_score, Some(0.75));
+        assert_eq!(verdict.feedback, "Misses overflow");
+    }
+}
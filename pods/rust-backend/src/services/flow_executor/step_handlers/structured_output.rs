@@ -0,0 +1,258 @@
+//! Structured outputs for LLM steps.
+//!
+//! Parameters:
+//! - `output_schema`: JSON Schema the step's output must match
+//! - `output_mapping`: map of variable name to a dotted path in the output
+//! - `output_retries`: how many times to re-ask the agent after a reply that
+//!   doesn't match the schema (default 2)
+//!
+//! The schema is appended to the task, and providers with a JSON mode
+//! (OpenAI-compatible APIs) are asked for JSON output. Replies are parsed
+//! leniently (code fences, surrounding prose), scalar types are repaired where
+//! the intent is clear, and the result is validated. On a mismatch the agent is
+//! re-asked with the validation errors. The validated value becomes the step
+//! output; mapped fields missing from it are set to null.
+
+use chrono::Utc;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::models::flow::FlowStep;
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::agent_api_client::message_formatter::LLMMessage;
+use crate::services::agent_api_client::AgentApiClient;
+use crate::services::flow_service::FlowExecutor;
+use crate::utils::json_path::get_path;
+use crate::utils::json_schema;
+
+const DEFAULT_OUTPUT_RETRIES: u32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct StructuredOutput {
+    pub schema: Value,
+    pub mapping: Map<String, Value>,
+    pub retries: u32,
+}
+
+impl StructuredOutput {
+    /// `None` when the step declares no output schema
+    pub fn from_parameters(parameters: &HashMap<String, Value>) -> Result<Option<Self>, String> {
+        let Some(schema) = parameters.get("output_schema").filter(|s| !s.is_null()) else {
+            return Ok(None);
+        };
+        json_schema::check_schema(schema).map_err(|e| format!("Invalid output_schema: {}", e))?;
+
+        let mapping = match parameters.get("output_mapping") {
+            None | Some(Value::Null) => Map::new(),
+            Some(Value::Object(mapping)) => {
+                if let Some((name, _)) = mapping.iter().find(|(_, path)| !path.is_string()) {
+                    return Err(format!("output_mapping for '{}' must be a path string", name));
+                }
+                mapping.clone()
+            }
+            Some(_) => return Err("output_mapping must be an object".to_string()),
+        };
+
+        let retries = parameters.get("output_retries")
+            .and_then(|v| v.as_u64())
+            .map(|r| r as u32)
+            .unwrap_or(DEFAULT_OUTPUT_RETRIES);
+
+        Ok(Some(Self { schema: schema.clone(), mapping, retries }))
+    }
+
+    /// The task with the output format appended
+    pub fn instructions(&self, task: &str) -> String {
+        format!(
+            "{}\n\n## Output format\nRespond with only a JSON value matching this JSON Schema, with no other text:\n{}",
+            task,
+            serde_json::to_string_pretty(&self.schema).unwrap_or_default()
+        )
+    }
+
+    /// Follow-up asking the agent to fix a reply that didn't match the schema
+    pub fn correction_prompt(&self, errors: &[String]) -> String {
+        format!(
+            "Your reply did not match the required JSON Schema:\n- {}\n\nRespond again with only the corrected JSON, with no other text.",
+            errors.join("\n- ")
+        )
+    }
+
+    /// Parse, repair and validate a reply
+    pub fn parse(&self, reply: &str) -> Result<Value, Vec<String>> {
+        let value = extract_json(reply).ok_or_else(|| vec!["reply is not valid JSON".to_string()])?;
+        let value = json_schema::coerce(value, &self.schema);
+        let errors = json_schema::validate(&value, &self.schema);
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn map_variables(&self, output: &Value) -> HashMap<String, Value> {
+        self.mapping.iter()
+            .map(|(name, path)| {
+                let value = path.as_str().and_then(|p| get_path(output, p)).cloned().unwrap_or(Value::Null);
+                (name.clone(), value)
+            })
+            .collect()
+    }
+}
+
+/// Find the JSON in a reply: the whole text, a fenced code block, or the first
+/// object or array embedded in prose
+pub fn extract_json(text: &str) -> Option<Value> {
+    let trimmed = text.trim();
+    if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+        return Some(value);
+    }
+
+    if let Some(start) = trimmed.find("```") {
+        let body = &trimmed[start + 3..];
+        let body = body.trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+        if let Some(end) = body.find("```") {
+            if let Ok(value) = serde_json::from_str::<Value>(body[..end].trim()) {
+                return Some(value);
+            }
+        }
+    }
+
+    trimmed.char_indices()
+        .filter(|(_, c)| *c == '{' || *c == '[')
+        .find_map(|(i, _)| {
+            let mut stream = serde_json::Deserializer::from_str(&trimmed[i..]).into_iter::<Value>();
+            match stream.next() {
+                Some(Ok(value @ (Value::Object(_) | Value::Array(_)))) => Some(value),
+                _ => None,
+            }
+        })
+}
+
+impl FlowExecutor {
+    /// Turn an LLM step's reply into validated structured output, re-asking the
+    /// agent while it doesn't match the schema
+    pub(crate) async fn apply_structured_output(
+        &self,
+        step: &FlowStep,
+        execution_id: &str,
+        structured: &StructuredOutput,
+        task: &str,
+        client: &mut AgentApiClient,
+        step_result: &mut Value,
+    ) -> Result<(), String> {
+        let agent_id = step.agent_id.as_deref().ok_or("LLM step requires agent_id")?;
+        let mut reply = step_result["output"].as_str().unwrap_or_default().to_string();
+        let mut history = vec![LLMMessage::user(&structured.instructions(task))];
+        let mut reasks = 0;
+
+        loop {
+            let errors = match structured.parse(&reply) {
+                Ok(output) => {
+                    let mapped = structured.map_variables(&output);
+                    step_result["raw_output"] = json!(reply);
+                    step_result["output"] = output;
+                    step_result["mapped_variables"] = json!(mapped);
+                    step_result["output_reasks"] = json!(reasks);
+                    return Ok(());
+                }
+                Err(errors) if reasks >= structured.retries => {
+                    return Err(format!(
+                        "Output did not match the schema after {} re-asks: {}", reasks, errors.join("; ")
+                    ));
+                }
+                Err(errors) => errors,
+            };
+
+            reasks += 1;
+            self.emit(FlowExecutionEvent {
+                id: None,
+                execution_id: execution_id.to_string(),
+                event_type: FlowEventType::StepProgress,
+                step_id: Some(step.id.clone()),
+                message: format!(
+                    "Output did not match the schema, re-asking ({}/{})", reasks, structured.retries
+                ),
+                data: HashMap::from([
+                    ("errors".to_string(), json!(errors)),
+                    ("attempt".to_string(), json!(reasks)),
+                ]),
+                timestamp: Utc::now(),
+            }).await;
+
+            history.push(LLMMessage::assistant(&reply, None));
+            let correction = structured.correction_prompt(&errors);
+            let params = json!({"task": correction, "output_schema": structured.schema});
+            let result = client.execute_agent_step(
+                agent_id,
+                &step.name,
+                &params,
+                step.agent_overrides.as_ref(),
+                Some(history.clone()),
+                Some(self.agent_event_callback(execution_id, &step.id)),
+            ).await;
+
+            if result.get("success").and_then(|v| v.as_bool()) != Some(true) {
+                return Err(result.get("error").and_then(|v| v.as_str()).unwrap_or("LLM step failed").to_string());
+            }
+            history.push(LLMMessage::user(&correction));
+            reply = result.get("content").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+            step_result["agent_result"] = result;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn structured() -> StructuredOutput {
+        let params: HashMap<String, Value> = serde_json::from_value(json!({
+            "output_schema": {
+                "type": "object",
+                "required": ["severity", "files"],
+                "properties": {
+                    "severity": {"type": "integer", "minimum": 1, "maximum": 5},
+                    "files": {"type": "array", "items": {"type": "string"}},
+                    "summary": {"type": "string"},
+                },
+            },
+            "output_mapping": {"severity": "severity", "first_file": "files.0", "summary": "summary"},
+        })).unwrap();
+        StructuredOutput::from_parameters(&params).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_extract_json_from_prose_and_fences() {
+        assert_eq!(extract_json("{\"a\": 1}"), Some(json!({"a": 1})));
+        assert_eq!(extract_json("Here you go:\n```json\n{\"a\": [1, 2]}\n```\nThanks"), Some(json!({"a": [1, 2]})));
+        assert_eq!(extract_json("The result is {\"ok\": true}."), Some(json!({"ok": true})));
+        assert_eq!(extract_json("no json here"), None);
+    }
+
+    #[test]
+    fn test_parse_repairs_and_maps() {
+        let structured = structured();
+        let output = structured.parse("```json\n{\"severity\": \"3\", \"files\": \"src/main.rs\"}\n```").unwrap();
+        assert_eq!(output, json!({"severity": 3, "files": ["src/main.rs"]}));
+
+        let mapped = structured.map_variables(&output);
+        assert_eq!(mapped["severity"], json!(3));
+        assert_eq!(mapped["first_file"], json!("src/main.rs"));
+        assert_eq!(mapped["summary"], Value::Null);
+
+        let errors = structured.parse("{\"severity\": 9}").unwrap_err();
+        assert_eq!(errors, vec!["$: missing required field 'files'", "$.severity: must be <= 5"]);
+        assert!(structured.parse("I could not do it").is_err());
+    }
+
+    #[test]
+    fn test_from_parameters() {
+        assert!(StructuredOutput::from_parameters(&HashMap::new()).unwrap().is_none());
+
+        let bad: HashMap<String, Value> = serde_json::from_value(json!({
+            "output_schema": {"type": "object"}, "output_mapping": {"x": 1},
+        })).unwrap();
+        assert!(StructuredOutput::from_parameters(&bad).is_err());
+    }
+}
@@ -0,0 +1,377 @@
+//! Sub-flow step: runs another saved flow as a single step and waits for it.
+//!
+//! Parameters:
+//! - `flow_id` or `flow_name`: the flow to run, one of the same user's flows
+//! - `inputs`: map of the child flow's input name to a value; strings are
+//!   templated, and a lone `{{var}}` passes the variable's JSON value
+//! - `output_mapping`: map of variable name to a dotted path in the child's
+//!   final variables, e.g. `"verdict": "step_qa_output.verdict"`
+//!
+//! The child runs as its own execution, linked to the parent through
+//! `parent_execution_id`, `parent_step_id` and `ancestor_execution_ids` and
+//! listed in the parent's `child_execution_ids`. Its events are stored under its
+//! own ID and forwarded live to the parent's subscribers as `step_progress`
+//! events of this step, with the original type in `child_event_type`.
+//!
+//! Cancelling the parent cancels the child. The child's steps keep their own
+//! time limits; the sub-flow step has none over the whole run. Nesting is
+//! limited to `MAX_SUB_FLOW_DEPTH` levels, which stops flows that call
+//! themselves. The step output is the output of the child's last completed step.
+
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::bson::doc;
+use mongodb::Collection;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::db::collections::FLOW_EXECUTIONS;
+use crate::error::AppError;
+use crate::models::flow::{Flow, FlowStep};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::execution_control::ExecutionControl;
+use crate::services::flow_inputs;
+use crate::services::flow_service::{resolve_value_variables, ExecutionScope, FlowExecutor, StepRunError};
+use crate::utils::json_path::get_path;
+
+/// Deepest allowed chain of flows started by sub-flow steps
+pub const MAX_SUB_FLOW_DEPTH: i32 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlowRef {
+    Id(String),
+    Name(String),
+}
+
+impl std::fmt::Display for FlowRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlowRef::Id(id) => write!(f, "flow with ID '{}'", id),
+            FlowRef::Name(name) => write!(f, "flow '{}'", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SubFlowConfig {
+    pub flow: FlowRef,
+    pub inputs: Map<String, Value>,
+    pub output_mapping: Map<String, Value>,
+}
+
+impl SubFlowConfig {
+    pub fn from_parameters(parameters: &HashMap<String, Value>) -> Result<Self, String> {
+        let param = |key: &str| parameters.get(key).and_then(|v| v.as_str()).filter(|s| !s.trim().is_empty());
+        let flow = match (param("flow_id"), param("flow_name")) {
+            (Some(id), _) => FlowRef::Id(id.to_string()),
+            (None, Some(name)) => FlowRef::Name(name.to_string()),
+            (None, None) => return Err("Sub-flow step requires flow_id or flow_name parameter".to_string()),
+        };
+
+        let object = |key: &str| match parameters.get(key) {
+            None | Some(Value::Null) => Ok(Map::new()),
+            Some(Value::Object(map)) => Ok(map.clone()),
+            Some(_) => Err(format!("{} must be an object", key)),
+        };
+        let inputs = object("inputs")?;
+        let output_mapping = object("output_mapping")?;
+        if let Some((name, _)) = output_mapping.iter().find(|(_, path)| !path.is_string()) {
+            return Err(format!("output_mapping for '{}' must be a path string", name));
+        }
+
+        Ok(Self { flow, inputs, output_mapping })
+    }
+
+    /// Pick variables out of the child's final variables; missing paths map to null
+    pub fn map_outputs(&self, variables: &Value) -> HashMap<String, Value> {
+        self.output_mapping.iter()
+            .map(|(name, path)| {
+                let value = path.as_str().and_then(|p| get_path(variables, p)).cloned().unwrap_or(Value::Null);
+                (name.clone(), value)
+            })
+            .collect()
+    }
+}
+
+/// Where a sub-flow execution sits in the execution tree
+#[derive(Debug, Clone)]
+pub(crate) struct ParentExecution {
+    pub execution_id: String,
+    pub step_id: String,
+    /// Executions above the child, outermost first, ending with the parent
+    pub ancestor_execution_ids: Vec<String>,
+    /// Nesting level of the child
+    pub depth: i32,
+}
+
+impl ParentExecution {
+    /// Link for a child started by `step_id` of the execution stored as `parent_doc`
+    pub fn new(execution_id: &str, step_id: &str, parent_doc: Option<&bson::Document>) -> Self {
+        let mut ancestor_execution_ids: Vec<String> = parent_doc
+            .and_then(|d| d.get_array("ancestor_execution_ids").ok())
+            .map(|ids| ids.iter().filter_map(|id| id.as_str().map(String::from)).collect())
+            .unwrap_or_default();
+        ancestor_execution_ids.push(execution_id.to_string());
+        let parent_depth = parent_doc.and_then(|d| d.get_i32("depth").ok()).unwrap_or(0);
+
+        Self {
+            execution_id: execution_id.to_string(),
+            step_id: step_id.to_string(),
+            ancestor_execution_ids,
+            depth: parent_depth + 1,
+        }
+    }
+
+    /// Fields stored on the child's execution document
+    pub fn link_fields(&self) -> bson::Document {
+        doc! {
+            "parent_execution_id": &self.execution_id,
+            "parent_step_id": &self.step_id,
+            "ancestor_execution_ids": &self.ancestor_execution_ids,
+            "depth": self.depth,
+        }
+    }
+}
+
+/// Cancels a sub-flow execution (and its own sub-flows) if the step waiting on
+/// it is dropped first, e.g. when the step times out
+struct CancelOnDrop {
+    executions: Collection<bson::Document>,
+    execution_id: String,
+    control: ExecutionControl,
+    armed: bool,
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        self.control.cancel();
+        let (Ok(oid), Ok(runtime)) = (ObjectId::parse_str(&self.execution_id), tokio::runtime::Handle::try_current()) else {
+            return;
+        };
+        let executions = self.executions.clone();
+        let execution_id = self.execution_id.clone();
+        runtime.spawn(async move {
+            let _ = executions.update_many(
+                doc! { "$or": [{ "_id": oid }, { "ancestor_execution_ids": &execution_id }] },
+                doc! { "$set": { "is_cancellation_requested": true, "updated_at": bson::DateTime::from_chrono(Utc::now()) } },
+            ).await;
+        });
+    }
+}
+
+impl FlowExecutor {
+    pub(crate) async fn execute_sub_flow_step(
+        &self,
+        flow: &Flow,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &ExecutionScope,
+    ) -> Result<Value, String> {
+        let config = SubFlowConfig::from_parameters(&step.parameters)?;
+        let child = match &config.flow {
+            FlowRef::Id(id) => self.service.load_flow(id, &flow.user_id).await,
+            FlowRef::Name(name) => self.service.load_flow_by_name(name, &flow.user_id).await,
+        }.map_err(|e| match e {
+            AppError::NotFound(_) => format!("Sub-flow {} not found", config.flow),
+            other => other.to_string(),
+        })?;
+        let child_flow_id = child.id.clone().unwrap_or_default();
+        let child_name = child.name.clone();
+
+        let executions = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let parent_oid = ObjectId::parse_str(execution_id).ok();
+        let parent_doc = match parent_oid {
+            Some(oid) => executions.find_one(doc! { "_id": oid }).await.ok().flatten(),
+            None => None,
+        };
+        let parent = ParentExecution::new(execution_id, &step.id, parent_doc.as_ref());
+        if parent.depth > MAX_SUB_FLOW_DEPTH {
+            return Err(format!(
+                "Sub-flow nesting limit of {} reached when starting flow '{}'; check for flows that call each other",
+                MAX_SUB_FLOW_DEPTH, child_name
+            ));
+        }
+
+        let inputs: HashMap<String, Value> = config.inputs.iter()
+            .map(|(name, value)| (name.clone(), resolve_value_variables(value, &scope.variables)))
+            .collect();
+        let input_data = flow_inputs::validate_inputs(&child.inputs, &inputs).map_err(|errors| {
+            let details: Vec<String> = errors.iter().map(|e| format!("{}: {}", e.field, e.message)).collect();
+            format!("Invalid inputs for flow '{}': {}", child_name, details.join("; "))
+        })?;
+
+        let child_id = self.service
+            .create_execution(&child_flow_id, &child, &input_data, &HashMap::new(), parent.link_fields()).await
+            .map_err(|e| e.to_string())?;
+        if let Some(oid) = parent_oid {
+            let _ = executions.update_one(
+                doc! { "_id": oid },
+                doc! {
+                    "$addToSet": { "child_execution_ids": &child_id },
+                    "$set": { "updated_at": bson::DateTime::from_chrono(Utc::now()) },
+                },
+            ).await;
+        }
+
+        let mut events = self.subscribe(&child_id).await;
+        let mut started_data = HashMap::from([
+            ("child_execution_id".to_string(), json!(child_id)),
+            ("flow_id".to_string(), json!(child_flow_id)),
+            ("flow_name".to_string(), json!(child_name)),
+            ("depth".to_string(), json!(parent.depth)),
+        ]);
+        scope.tag_event_data(&mut started_data);
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::StepProgress,
+            step_id: Some(step.id.clone()),
+            message: format!("Running flow '{}' as execution {}", child_name, child_id),
+            data: started_data,
+            timestamp: Utc::now(),
+        }).await;
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: child_id.clone(),
+            event_type: FlowEventType::ExecutionStarted,
+            step_id: None,
+            message: format!("Flow '{}' execution started by step '{}'", child_name, step.name),
+            data: HashMap::from([
+                ("parent_execution_id".to_string(), json!(execution_id)),
+                ("parent_step_id".to_string(), json!(step.id)),
+            ]),
+            timestamp: Utc::now(),
+        }).await;
+
+        // The child runs in its own task so it can record its own end state when
+        // this step is dropped and the guard cancels it
+        // Cancelling the parent cancels the child through its control handle
+        let control = self.service.controls.register(&child_id, Some(execution_id));
+        let mut guard = CancelOnDrop { executions, execution_id: child_id.clone(), control, armed: true };
+        let executor = self.fork();
+        let run_id = child_id.clone();
+        let start_step_id = child.start_step_id.clone();
+        let child_scope = ExecutionScope::new(input_data, child.edge_metadata.clone());
+        let mut run = tokio::spawn(async move {
+            let mut scope = child_scope;
+            let outcome = executor.run_steps(&child, &run_id, &start_step_id, None, &mut scope).await;
+            executor.finish_execution(&run_id, &outcome, &scope).await;
+            executor.service.controls.remove(&run_id);
+            (outcome, scope)
+        });
+
+        let mut forwarding = true;
+        let joined = loop {
+            tokio::select! {
+                joined = &mut run => break joined,
+                received = events.recv(), if forwarding => match received {
+                    Ok(event) => self.forward_child_event(execution_id, &step.id, event).await,
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => forwarding = false,
+                },
+            }
+        };
+        while let Ok(event) = events.try_recv() {
+            self.forward_child_event(execution_id, &step.id, event).await;
+        }
+        guard.armed = false;
+
+        let (outcome, child_scope) = joined
+            .map_err(|e| format!("Sub-flow execution {} stopped unexpectedly: {}", child_id, e))?;
+        match outcome {
+            Ok(()) => {
+                let output = child_scope.completed_steps.last()
+                    .and_then(|id| child_scope.variables.get(&format!("step_{}_output", id)))
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                let variables = Value::Object(child_scope.variables.into_iter().collect());
+                Ok(json!({
+                    "output": output,
+                    "child_execution_id": child_id,
+                    "flow_id": child_flow_id,
+                    "flow_name": child_name,
+                    "completed_steps": child_scope.completed_steps,
+                    "mapped_variables": config.map_outputs(&variables),
+                }))
+            }
+            Err(StepRunError::Cancelled { .. }) => Err(format!("Sub-flow execution {} was cancelled", child_id)),
+            Err(error) => Err(format!("Sub-flow '{}' failed: {}", child_name, error)),
+        }
+    }
+
+    /// Pass a child execution's event on to the parent's live subscribers. Events
+    /// forwarded from deeper sub-flows keep their original child fields.
+    async fn forward_child_event(&self, execution_id: &str, step_id: &str, event: FlowExecutionEvent) {
+        let mut data = event.data;
+        data.entry("child_execution_id".to_string()).or_insert(json!(event.execution_id));
+        data.entry("child_event_type".to_string()).or_insert(json!(event.event_type));
+        if let Some(child_step_id) = event.step_id {
+            data.entry("child_step_id".to_string()).or_insert(json!(child_step_id));
+        }
+
+        self.broadcast(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::StepProgress,
+            step_id: Some(step_id.to_string()),
+            message: event.message,
+            data,
+            timestamp: event.timestamp,
+        }).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_config_from_parameters() {
+        let config = SubFlowConfig::from_parameters(&params(json!({
+            "flow_name": "analyze-implement-qa",
+            "inputs": {"issue_id": "{{issue_id}}"},
+            "output_mapping": {"verdict": "step_qa_output.verdict"},
+        }))).unwrap();
+        assert_eq!(config.flow, FlowRef::Name("analyze-implement-qa".to_string()));
+        assert_eq!(config.inputs["issue_id"], json!("{{issue_id}}"));
+
+        let config = SubFlowConfig::from_parameters(&params(json!({"flow_id": "abc", "flow_name": "ignored"}))).unwrap();
+        assert_eq!(config.flow, FlowRef::Id("abc".to_string()));
+
+        assert!(SubFlowConfig::from_parameters(&HashMap::new()).is_err());
+        assert!(SubFlowConfig::from_parameters(&params(json!({"flow_id": "abc", "inputs": ["x"]}))).is_err());
+        assert!(SubFlowConfig::from_parameters(&params(json!({"flow_id": "abc", "output_mapping": {"x": 1}}))).is_err());
+    }
+
+    #[test]
+    fn test_map_outputs() {
+        let config = SubFlowConfig::from_parameters(&params(json!({
+            "flow_id": "abc",
+            "output_mapping": {"verdict": "step_qa_output.verdict", "missing": "step_nope_output"},
+        }))).unwrap();
+        let mapped = config.map_outputs(&json!({"step_qa_output": {"verdict": "pass"}}));
+        assert_eq!(mapped["verdict"], json!("pass"));
+        assert_eq!(mapped["missing"], Value::Null);
+    }
+
+    #[test]
+    fn test_parent_link_extends_ancestry() {
+        let top = ParentExecution::new("exec-1", "review", None);
+        assert_eq!(top.depth, 1);
+        assert_eq!(top.ancestor_execution_ids, vec!["exec-1"]);
+
+        let child_doc = top.link_fields();
+        let nested = ParentExecution::new("exec-2", "qa", Some(&child_doc));
+        assert_eq!(nested.depth, 2);
+        assert_eq!(nested.ancestor_execution_ids, vec!["exec-1", "exec-2"]);
+        assert_eq!(nested.link_fields().get_str("parent_execution_id").unwrap(), "exec-2");
+    }
+}
@@ -0,0 +1,388 @@
+//! Switch step: routes to one of several labeled branches.
+//!
+//! Parameters:
+//! - `cases`: ordered list of `{"label", "expression", "target_step_id", "description"}`
+//! - `default_step_id`: target when no case matches
+//! - `mode`: `expression` (default) or `llm`
+//! - `value`: expression compared against each case label (for cases without an expression)
+//! - `input`: text the LLM router classifies (defaults to the previous step's output)
+//! - `instructions`: extra guidance for the LLM router
+//! - `router_llm_id`: classify with a bare LLM instead of the step's agent; it must
+//!   belong to the flow's owner
+//!
+//! In expression mode the first case whose expression holds (or whose label equals
+//! `value`) wins. In LLM mode the agent picks one of the case labels.
+
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::models::flow::{Flow, FlowStep};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::flow_executor::expression;
+use crate::services::flow_service::{resolve_variables, ExecutionScope, FlowExecutor};
+use crate::utils::json_path::last_json_object_with;
+
+const ROUTER_SYSTEM_PROMPT: &str = "You are a router. Classify the input into exactly one of the given categories.";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SwitchCase {
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub expression: Option<String>,
+    #[serde(alias = "target")]
+    pub target_step_id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwitchMode {
+    Expression,
+    Llm,
+}
+
+impl SwitchMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwitchMode::Expression => "expression",
+            SwitchMode::Llm => "llm",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SwitchConfig {
+    pub mode: SwitchMode,
+    pub cases: Vec<SwitchCase>,
+    pub default_step_id: Option<String>,
+    pub value: Option<String>,
+}
+
+/// The branch a switch step picked
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchChoice {
+    /// Index into `cases`, `None` for the default branch
+    pub case_index: Option<usize>,
+    pub label: Option<String>,
+    pub target_step_id: String,
+    pub reason: String,
+}
+
+impl SwitchConfig {
+    /// Parse and check the step parameters, including every case expression
+    pub fn from_parameters(parameters: &HashMap<String, Value>) -> Result<Self, String> {
+        let mode = match parameters.get("mode").and_then(|v| v.as_str()) {
+            None | Some("expression") => SwitchMode::Expression,
+            Some("llm") => SwitchMode::Llm,
+            Some(other) => return Err(format!("Unknown switch mode '{}' (expected 'expression' or 'llm')", other)),
+        };
+
+        let cases: Vec<SwitchCase> = match parameters.get("cases") {
+            Some(cases) => serde_json::from_value(cases.clone())
+                .map_err(|e| format!("Invalid switch cases: {}", e))?,
+            None => Vec::new(),
+        };
+        if cases.is_empty() {
+            return Err("Switch step requires at least one case".to_string());
+        }
+
+        let value = parameters.get("value").and_then(|v| v.as_str()).map(String::from);
+        if let Some(ref value) = value {
+            expression::validate(value).map_err(|e| format!("Invalid switch value '{}': {}", value, e))?;
+        }
+
+        for (i, case) in cases.iter().enumerate() {
+            if case.target_step_id.trim().is_empty() {
+                return Err(format!("Switch case {} has no target_step_id", i + 1));
+            }
+            match mode {
+                SwitchMode::Llm if case.label.is_none() => {
+                    return Err(format!("Switch case {} needs a label in llm mode", i + 1));
+                }
+                SwitchMode::Expression => match (&case.expression, &case.label) {
+                    (Some(expr), _) => expression::validate(expr)
+                        .map_err(|e| format!("Invalid expression in switch case {}: {}", i + 1, e))?,
+                    (None, Some(_)) if value.is_some() => {}
+                    (None, Some(_)) => {
+                        return Err(format!("Switch case {} has only a label; set the switch 'value' to compare it against", i + 1));
+                    }
+                    (None, None) => return Err(format!("Switch case {} needs an expression or a label", i + 1)),
+                },
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            mode,
+            cases,
+            default_step_id: parameters.get("default_step_id")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .map(String::from),
+            value,
+        })
+    }
+
+    fn default_branch(&self, reason: String) -> Option<BranchChoice> {
+        self.default_step_id.as_ref().map(|target| BranchChoice {
+            case_index: None,
+            label: Some("default".to_string()),
+            target_step_id: target.clone(),
+            reason,
+        })
+    }
+
+    /// Pick the first case whose expression holds or whose label equals `value`
+    pub fn select_by_expression(&self, variables: &HashMap<String, Value>) -> Result<Option<BranchChoice>, String> {
+        let context = expression::build_context(variables);
+        let value = match self.value {
+            Some(ref source) => Some(expression::evaluate(&expression::parse(source)?, &context)?),
+            None => None,
+        };
+
+        for (i, case) in self.cases.iter().enumerate() {
+            let (matched, reason) = match (&case.expression, &case.label, &value) {
+                (Some(source), _, _) => {
+                    let result = expression::evaluate(&expression::parse(source)?, &context)?;
+                    (expression::is_truthy(&result), format!("Expression '{}' matched", source))
+                }
+                (None, Some(label), Some(value)) => (
+                    expression::loose_eq(value, &json!(label)),
+                    format!("Value matched label '{}'", label),
+                ),
+                _ => (false, String::new()),
+            };
+            if matched {
+                return Ok(Some(BranchChoice {
+                    case_index: Some(i),
+                    label: case.label.clone(),
+                    target_step_id: case.target_step_id.clone(),
+                    reason,
+                }));
+            }
+        }
+
+        Ok(self.default_branch("No case matched".to_string()))
+    }
+}
+
+/// Build the classification prompt for LLM router mode
+pub fn build_router_prompt(cases: &[SwitchCase], input: &str, instructions: Option<&str>) -> String {
+    let categories: Vec<String> = cases.iter()
+        .filter_map(|case| {
+            let label = case.label.as_deref()?;
+            Some(match case.description {
+                Some(ref description) => format!("- {}: {}", label, description),
+                None => format!("- {}", label),
+            })
+        })
+        .collect();
+
+    let mut prompt = format!("## Categories\n{}\n\n## Input\n{}\n\n", categories.join("\n"), input);
+    if let Some(instructions) = instructions {
+        prompt.push_str(&format!("## Instructions\n{}\n\n", instructions));
+    }
+    prompt.push_str("Choose the single best category. Respond with only a JSON object:\n{\"label\": \"<category>\", \"reason\": \"<why>\"}");
+    prompt
+}
+
+/// Read the router's reply: a JSON `{"label", "reason"}` object, or a bare label.
+/// Returns the matching case index and the stated reason.
+pub fn parse_router_response(text: &str, cases: &[SwitchCase]) -> Option<(usize, String)> {
+    let find_case = |label: &str| {
+        let label = label.trim().to_lowercase();
+        cases.iter().position(|c| c.label.as_deref().is_some_and(|l| l.to_lowercase() == label))
+    };
+
+    if let Some(map) = last_json_object_with(text, &["label"]) {
+        let label = map.get("label").and_then(|v| v.as_str())?;
+        let reason = map.get("reason").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        return find_case(label).map(|i| (i, reason));
+    }
+
+    find_case(text.trim().trim_matches(|c: char| c == '"' || c == '.'))
+        .map(|i| (i, String::new()))
+}
+
+impl FlowExecutor {
+    pub(crate) async fn execute_switch_step(
+        &self,
+        flow: &Flow,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &mut ExecutionScope,
+    ) -> Result<Value, String> {
+        let config = SwitchConfig::from_parameters(&step.parameters)?;
+
+        let (choice, router_result) = match config.mode {
+            SwitchMode::Expression => (config.select_by_expression(&scope.variables)?, None),
+            SwitchMode::Llm => {
+                let (choice, result) = self.route_with_llm(flow, step, execution_id, &config, scope).await?;
+                (choice, Some(result))
+            }
+        };
+
+        let choice = choice.ok_or_else(|| format!(
+            "Switch step '{}' matched no case and has no default_step_id", step.name
+        ))?;
+
+        let data = HashMap::from([
+            ("mode".to_string(), json!(config.mode.as_str())),
+            ("branch".to_string(), json!(choice.label)),
+            ("case_index".to_string(), json!(choice.case_index)),
+            ("target_step_id".to_string(), json!(choice.target_step_id)),
+            ("reason".to_string(), json!(choice.reason)),
+        ]);
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::BranchSelected,
+            step_id: Some(step.id.clone()),
+            message: format!(
+                "Switch '{}' routed to '{}' ({})",
+                step.name, choice.target_step_id, choice.label.as_deref().unwrap_or("unlabeled case")
+            ),
+            data,
+            timestamp: Utc::now(),
+        }).await;
+
+        Ok(json!({
+            "output": {
+                "branch": choice.label,
+                "target_step_id": choice.target_step_id,
+                "reason": choice.reason,
+            },
+            "mode": config.mode.as_str(),
+            "branch": choice.label,
+            "case_index": choice.case_index,
+            "reason": choice.reason,
+            "router_result": router_result,
+            "next_step_id": choice.target_step_id,
+        }))
+    }
+
+    /// Ask the step's agent (or `router_llm_id`) to pick one of the labeled cases
+    async fn route_with_llm(
+        &self,
+        flow: &Flow,
+        step: &FlowStep,
+        execution_id: &str,
+        config: &SwitchConfig,
+        scope: &ExecutionScope,
+    ) -> Result<(Option<BranchChoice>, Value), String> {
+        let input = match step.parameters.get("input").and_then(|v| v.as_str()) {
+            Some(template) => resolve_variables(template, &scope.variables),
+            None => match scope.completed_steps.last()
+                .and_then(|id| scope.variables.get(&format!("step_{}_output", id)))
+            {
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => serde_json::to_string(other).unwrap_or_default(),
+                None => return Err("LLM router has no input: set the 'input' parameter".to_string()),
+            },
+        };
+        let instructions = step.parameters.get("instructions")
+            .and_then(|v| v.as_str())
+            .map(|i| resolve_variables(i, &scope.variables));
+
+        let prompt = build_router_prompt(&config.cases, &input, instructions.as_deref());
+        let mut client = self.agent_client(flow, step);
+
+        let router_llm_id = step.parameters.get("router_llm_id").and_then(|v| v.as_str());
+        let result = match (router_llm_id, step.agent_id.as_deref()) {
+            (Some(llm_id), _) => client.execute_llm_prompt(llm_id, Some(ROUTER_SYSTEM_PROMPT), &prompt).await,
+            (None, Some(agent_id)) => {
+                let callback = self.agent_event_callback(execution_id, &step.id);
+                client.execute_agent_step(agent_id, &step.name, &json!({"task": prompt}), None, None, Some(callback)).await
+            }
+            (None, None) => return Err("LLM router requires agent_id or router_llm_id".to_string()),
+        };
+
+        if result.get("success").and_then(|v| v.as_bool()) != Some(true) {
+            return Err(result.get("error").and_then(|v| v.as_str()).unwrap_or("Router call failed").to_string());
+        }
+
+        let content = result.get("content").and_then(|v| v.as_str()).unwrap_or_default();
+        let choice = match parse_router_response(content, &config.cases) {
+            Some((i, reason)) => Some(BranchChoice {
+                case_index: Some(i),
+                label: config.cases[i].label.clone(),
+                target_step_id: config.cases[i].target_step_id.clone(),
+                reason,
+            }),
+            None => config.default_branch(format!("Router reply did not name a known label: {}", content.trim())),
+        };
+
+        Ok((choice, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_expression_cases_and_default() {
+        let config = SwitchConfig::from_parameters(&params(json!({
+            "cases": [
+                {"label": "high", "expression": "score >= 0.8", "target_step_id": "ship"},
+                {"label": "medium", "expression": "score >= 0.5", "target_step_id": "revise"},
+            ],
+            "default_step_id": "rewrite",
+        }))).unwrap();
+
+        let pick = |score: f64| config.select_by_expression(&HashMap::from([("score".to_string(), json!(score))]))
+            .unwrap().unwrap();
+        assert_eq!(pick(0.9).target_step_id, "ship");
+        assert_eq!(pick(0.6).target_step_id, "revise");
+        assert_eq!(pick(0.6).case_index, Some(1));
+        assert_eq!(pick(0.1).target_step_id, "rewrite");
+        assert_eq!(pick(0.1).case_index, None);
+
+        let by_label = SwitchConfig::from_parameters(&params(json!({
+            "value": "steps.triage.output.kind",
+            "cases": [{"label": "bug", "target_step_id": "fix"}, {"label": "feature", "target_step_id": "plan"}],
+        }))).unwrap();
+        let vars = HashMap::from([("step_triage_output".to_string(), json!({"kind": "feature"}))]);
+        assert_eq!(by_label.select_by_expression(&vars).unwrap().unwrap().target_step_id, "plan");
+        let vars = HashMap::from([("step_triage_output".to_string(), json!({"kind": "question"}))]);
+        assert_eq!(by_label.select_by_expression(&vars).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_switch_parameters() {
+        assert!(SwitchConfig::from_parameters(&params(json!({}))).is_err());
+        assert!(SwitchConfig::from_parameters(&params(json!({
+            "cases": [{"expression": "score >", "target_step_id": "a"}],
+        }))).is_err());
+        assert!(SwitchConfig::from_parameters(&params(json!({
+            "cases": [{"label": "bug", "target_step_id": "a"}],
+        }))).is_err());
+        assert!(SwitchConfig::from_parameters(&params(json!({
+            "mode": "llm", "cases": [{"target_step_id": "a"}],
+        }))).is_err());
+    }
+
+    #[test]
+    fn test_router_prompt_and_response() {
+        let cases: Vec<SwitchCase> = serde_json::from_value(json!([
+            {"label": "bug", "description": "Something is broken", "target_step_id": "fix"},
+            {"label": "feature", "target_step_id": "plan"},
+        ])).unwrap();
+
+        let prompt = build_router_prompt(&cases, "App crashes on login", None);
+        assert!(prompt.contains("- bug: Something is broken"));
+        assert!(prompt.contains("App crashes on login"));
+
+        let reply = "```json\n{\"label\": \"Bug\", \"reason\": \"crash report\"}\n```";
+        assert_eq!(parse_router_response(reply, &cases), Some((0, "crash report".to_string())));
+        assert_eq!(parse_router_response("feature", &cases), Some((1, String::new())));
+        assert_eq!(parse_router_response("{\"label\": \"question\"}", &cases), None);
+    }
+}
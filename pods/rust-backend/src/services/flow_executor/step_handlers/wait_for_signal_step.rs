@@ -0,0 +1,300 @@
+//! Wait-for-signal step: waits until an external system posts a matching signal.
+//!
+//! Parameters:
+//! - `signal`: name of the signal, templated
+//! - `correlation_id`: value the signal must carry, templated (defaults to the
+//!   execution ID), e.g. `"{{step_start_build_output}}"` for a CI build ID
+//! - `timeout_step_id`: step to continue at when the wait times out; without it
+//!   a timeout fails the step
+//!
+//! While waiting, the step is listed in the execution's `signal_waits`. A
+//! `POST /api/signals/{signal}` with `{"correlation_id": ..., "payload": ...}`
+//! from the execution's owner resumes every step waiting for that signal and
+//! correlation ID, and the payload becomes the step output. Signals nobody is
+//! waiting for are rejected rather than kept. The step's `timeout_seconds`
+//! bounds the wait (none when 0); on a timeout the output is null and
+//! `timed_out` is set.
+
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::bson::doc;
+use mongodb::Collection;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+use crate::db::collections::FLOW_EXECUTIONS;
+use crate::error::AppError;
+use crate::models::flow::{FlowStep, SignalDelivery, SignalWait};
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::execution_control::{next_signal, ControlSignal};
+use crate::services::flow_service::{resolve_variables, ExecutionScope, FlowExecutor, FlowService};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalWaitConfig {
+    pub signal: String,
+    pub correlation_id: String,
+    pub timeout_step_id: Option<String>,
+}
+
+impl SignalWaitConfig {
+    /// Resolve the signal and correlation ID against the execution's variables
+    pub fn from_step(step: &FlowStep, execution_id: &str, variables: &HashMap<String, Value>) -> Result<Self, String> {
+        Self::validate_parameters(&step.parameters)?;
+        let param = |key: &str| step.parameters.get(key).and_then(|v| v.as_str()).filter(|s| !s.trim().is_empty());
+
+        let signal = resolve_variables(param("signal").unwrap_or_default(), variables);
+        let correlation_id = param("correlation_id")
+            .map(|id| resolve_variables(id, variables))
+            .unwrap_or_else(|| execution_id.to_string());
+        if signal.trim().is_empty() || correlation_id.trim().is_empty() {
+            return Err("signal and correlation_id must not resolve to empty values".to_string());
+        }
+        Ok(Self {
+            signal,
+            correlation_id,
+            timeout_step_id: param("timeout_step_id").map(String::from),
+        })
+    }
+
+    /// Check the parameters when the flow is saved
+    pub fn validate_parameters(parameters: &HashMap<String, Value>) -> Result<(), String> {
+        match parameters.get("signal") {
+            Some(Value::String(signal)) if !signal.trim().is_empty() => {}
+            Some(_) => return Err("signal must be a non-empty string".to_string()),
+            None => return Err("Wait-for-signal step requires a signal parameter".to_string()),
+        }
+        for key in ["correlation_id", "timeout_step_id"] {
+            if parameters.get(key).is_some_and(|v| !v.is_string()) {
+                return Err(format!("{} must be a string", key));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FlowExecutor {
+    pub(crate) async fn execute_wait_for_signal_step(
+        &self,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &ExecutionScope,
+    ) -> Result<Value, String> {
+        let config = SignalWaitConfig::from_step(step, execution_id, &scope.variables)?;
+        let oid = ObjectId::parse_str(execution_id).map_err(|e| e.to_string())?;
+        let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+
+        let wait = step.timeout_seconds.filter(|secs| *secs > 0)
+            .map(|secs| std::time::Duration::from_secs(secs as u64));
+        let now = Utc::now();
+        let request = SignalWait {
+            step_id: step.id.clone(),
+            signal: config.signal.clone(),
+            correlation_id: config.correlation_id.clone(),
+            requested_at: now,
+            expires_at: wait.and_then(|w| chrono::Duration::from_std(w).ok()).map(|w| now + w),
+        };
+        // Subscribed before the wait is visible, so no signal is missed
+        let mut signals = self.control(execution_id).subscribe();
+        let request_bson = bson::to_bson(&request).map_err(|e| e.to_string())?;
+        // A wait left by an interrupted run of this step is replaced
+        collection.update_one(doc! { "_id": oid }, doc! { "$pull": { "signal_waits": { "step_id": &step.id } } })
+            .await.map_err(|e| format!("Failed to wait for signal: {}", e))?;
+        collection.update_one(
+            doc! { "_id": oid },
+            doc! {
+                "$push": { "signal_waits": request_bson },
+                "$set": { "updated_at": bson::DateTime::from_chrono(now) },
+            },
+        ).await.map_err(|e| format!("Failed to wait for signal: {}", e))?;
+
+        let mut data = HashMap::from([
+            ("signal".to_string(), json!(request.signal)),
+            ("correlation_id".to_string(), json!(request.correlation_id)),
+            ("expires_at".to_string(), json!(request.expires_at)),
+        ]);
+        scope.tag_event_data(&mut data);
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::SignalWaiting,
+            step_id: Some(step.id.clone()),
+            message: format!("Step '{}' waiting for signal '{}' ({})", step.name, request.signal, request.correlation_id),
+            data,
+            timestamp: Utc::now(),
+        }).await;
+
+        // One may already be recorded if the backend restarted after it arrived
+        let deadline = wait.map(|w| tokio::time::Instant::now() + w);
+        let mut delivered = recorded_delivery(&collection, &oid, &step.id).await;
+        let payload = loop {
+            if let Some(payload) = delivered.take() {
+                break payload;
+            }
+            let Some(signal) = next_signal(&mut signals, deadline).await else {
+                let _ = collection.update_one(
+                    doc! { "_id": oid },
+                    doc! { "$pull": { "signal_waits": { "step_id": &step.id } } },
+                ).await;
+                // The signal may have been taken just before the wait was withdrawn
+                if let Some(payload) = recorded_delivery(&collection, &oid, &step.id).await {
+                    break payload;
+                }
+                let waited = wait.unwrap_or_default().as_secs();
+                return match config.timeout_step_id {
+                    Some(timeout_step_id) => Ok(json!({
+                        "output": null,
+                        "timed_out": true,
+                        "next_step_id": timeout_step_id,
+                    })),
+                    None => Err(format!("Timed out waiting for signal '{}' after {}s", config.signal, waited)),
+                };
+            };
+            delivered = match signal {
+                Ok(ControlSignal::SignalReceived { step_id, payload }) if step_id == step.id => Some(payload),
+                Ok(_) => None,
+                Err(broadcast::error::RecvError::Closed) => return Err("Execution is no longer running".to_string()),
+                Err(broadcast::error::RecvError::Lagged(_)) => recorded_delivery(&collection, &oid, &step.id).await,
+            };
+        };
+
+        let _ = collection.update_one(
+            doc! { "_id": oid },
+            doc! { "$unset": { format!("signal_deliveries.{}", step.id): "" } },
+        ).await;
+        Ok(json!({
+            "output": payload,
+            "signal": config.signal,
+            "correlation_id": config.correlation_id,
+        }))
+    }
+}
+
+/// Payload delivered to the step, as recorded on the execution
+async fn recorded_delivery(collection: &Collection<bson::Document>, oid: &ObjectId, step_id: &str) -> Option<Value> {
+    let exec_doc = collection.find_one(doc! { "_id": oid }).await.ok().flatten()?;
+    let payload = exec_doc.get_document("signal_deliveries").ok()?.get(step_id)?;
+    bson::from_bson(payload.clone()).ok()
+}
+
+impl FlowService {
+    /// Hand a signal to every step of the user's executions waiting for it.
+    /// Returns the execution and step IDs that received it.
+    pub async fn deliver_signal(
+        &self,
+        user_id: &str,
+        signal: &str,
+        delivery: SignalDelivery,
+    ) -> Result<Vec<(String, String)>, AppError> {
+        let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let wanted = doc! { "signal": signal, "correlation_id": &delivery.correlation_id };
+        let mut cursor = collection
+            .find(doc! { "user_id": user_id, "signal_waits": { "$elemMatch": wanted.clone() } })
+            .projection(doc! { "signal_waits": 1 })
+            .await?;
+        let mut waiting = Vec::new();
+        while cursor.advance().await? {
+            let exec_doc = cursor.deserialize_current()?;
+            let Ok(oid) = exec_doc.get_object_id("_id") else { continue };
+            let waits: Vec<SignalWait> = exec_doc.get_array("signal_waits").ok()
+                .map(|arr| arr.iter().filter_map(|d| bson::from_bson(d.clone()).ok()).collect())
+                .unwrap_or_default();
+            for wait in waits {
+                if wait.signal == signal && wait.correlation_id == delivery.correlation_id {
+                    waiting.push((oid, wait.step_id));
+                }
+            }
+        }
+
+        let payload = bson::to_bson(&delivery.payload).map_err(|e| AppError::Internal(e.to_string()))?;
+        let mut delivered = Vec::new();
+        for (oid, step_id) in waiting {
+            // Taking the wait off the list claims it, so each step gets one signal
+            let mut claim = wanted.clone();
+            claim.insert("step_id", &step_id);
+            let claimed = collection.update_one(
+                doc! { "_id": oid, "signal_waits": { "$elemMatch": claim } },
+                doc! {
+                    "$pull": { "signal_waits": { "step_id": &step_id } },
+                    "$set": {
+                        format!("signal_deliveries.{}", step_id): payload.clone(),
+                        "updated_at": bson::DateTime::from_chrono(Utc::now()),
+                    },
+                },
+            ).await?.modified_count > 0;
+            if !claimed {
+                continue;
+            }
+
+            let execution_id = oid.to_hex();
+            self.controls.send(&execution_id, ControlSignal::SignalReceived {
+                step_id: step_id.clone(),
+                payload: delivery.payload.clone(),
+            });
+            self.emit_event(FlowExecutionEvent {
+                id: None,
+                execution_id: execution_id.clone(),
+                event_type: FlowEventType::SignalReceived,
+                step_id: Some(step_id.clone()),
+                message: format!("Signal '{}' received for step '{}'", signal, step_id),
+                data: HashMap::from([
+                    ("signal".to_string(), json!(signal)),
+                    ("correlation_id".to_string(), json!(delivery.correlation_id)),
+                    ("payload".to_string(), delivery.payload.clone()),
+                ]),
+                timestamp: Utc::now(),
+            }).await;
+            delivered.push((execution_id, step_id));
+        }
+
+        if delivered.is_empty() {
+            return Err(AppError::NotFound(format!(
+                "No execution is waiting for signal '{}' with correlation ID '{}'",
+                signal, delivery.correlation_id
+            )));
+        }
+        Ok(delivered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(parameters: Value) -> FlowStep {
+        serde_json::from_value(json!({
+            "id": "wait_build", "name": "Wait for build", "type": "wait_for_signal", "parameters": parameters,
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_config_resolves_from_variables() {
+        let variables = HashMap::from([("step_start_build_output".to_string(), json!("build-881"))]);
+        let config = SignalWaitConfig::from_step(&step(json!({
+            "signal": "ci_finished",
+            "correlation_id": "{{step_start_build_output}}",
+            "timeout_step_id": "report_timeout",
+        })), "exec-1", &variables).unwrap();
+        assert_eq!(config, SignalWaitConfig {
+            signal: "ci_finished".to_string(),
+            correlation_id: "build-881".to_string(),
+            timeout_step_id: Some("report_timeout".to_string()),
+        });
+
+        // Correlated by execution ID by default
+        let config = SignalWaitConfig::from_step(&step(json!({"signal": "ci_finished"})), "exec-1", &variables).unwrap();
+        assert_eq!(config.correlation_id, "exec-1");
+        assert_eq!(config.timeout_step_id, None);
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        for parameters in [json!({}), json!({"signal": ""}), json!({"signal": 5}), json!({"signal": "s", "correlation_id": 7})] {
+            let step = step(parameters.clone());
+            assert!(SignalWaitConfig::validate_parameters(&step.parameters).is_err(), "{} accepted", parameters);
+        }
+        let blank = step(json!({"signal": "{{missing}}", "correlation_id": "{{empty}}"}));
+        let variables = HashMap::from([("empty".to_string(), json!(""))]);
+        assert!(SignalWaitConfig::from_step(&blank, "exec-1", &variables).is_err());
+    }
+}
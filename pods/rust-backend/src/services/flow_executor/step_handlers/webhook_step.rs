@@ -0,0 +1,263 @@
+//! Webhook step: calls an external HTTP service between other steps.
+//!
+//! Parameters:
+//! - `url`: request URL (supports `{{var}}` placeholders)
+//! - `method`: HTTP method (default `POST`)
+//! - `headers`: map of header name to templated value
+//! - `body`: JSON body; strings inside it are templated
+//! - `expected_status`: status code or list of codes (default: any 2xx)
+//! - `timeout_seconds`: request timeout (default 30)
+//! - `response_mapping`: map of variable name to a dotted path in the JSON response
+
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::models::flow::FlowStep;
+use crate::models::flow_events::{FlowEventType, FlowExecutionEvent};
+use crate::services::flow_service::{resolve_value_variables, resolve_variables, ExecutionScope, FlowExecutor};
+use crate::utils::json_path::get_path;
+
+const DEFAULT_WEBHOOK_TIMEOUT_SECS: u64 = 30;
+
+/// Fully resolved outbound request for a webhook step
+#[derive(Debug, Clone)]
+pub struct WebhookRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Value>,
+    pub timeout: Duration,
+    /// Accepted status codes; empty means any 2xx
+    pub expected_status: Vec<u16>,
+}
+
+impl WebhookRequest {
+    pub fn from_parameters(
+        parameters: &HashMap<String, Value>,
+        variables: &HashMap<String, Value>,
+    ) -> Result<Self, String> {
+        let url = parameters.get("url")
+            .and_then(|v| v.as_str())
+            .map(|u| resolve_variables(u, variables))
+            .filter(|u| !u.trim().is_empty())
+            .ok_or("Webhook step requires url parameter")?;
+
+        let method_str = parameters.get("method").and_then(|v| v.as_str()).unwrap_or("POST");
+        let method = Method::from_bytes(method_str.to_uppercase().as_bytes())
+            .map_err(|_| format!("Invalid HTTP method '{}'", method_str))?;
+
+        let headers = parameters.get("headers")
+            .and_then(|v| v.as_object())
+            .map(|map| map.iter().map(|(name, value)| {
+                let value = match value {
+                    Value::String(s) => resolve_variables(s, variables),
+                    other => other.to_string(),
+                };
+                (name.clone(), value)
+            }).collect())
+            .unwrap_or_default();
+
+        let body = parameters.get("body")
+            .filter(|b| !b.is_null())
+            .map(|b| resolve_value_variables(b, variables));
+
+        let timeout = Duration::from_secs(
+            parameters.get("timeout_seconds")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(DEFAULT_WEBHOOK_TIMEOUT_SECS),
+        );
+
+        let expected_status = match parameters.get("expected_status") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(codes)) => codes.iter()
+                .map(|c| c.as_u64().map(|c| c as u16).ok_or("expected_status must contain status codes"))
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(code) => vec![code.as_u64().ok_or("expected_status must be a status code")? as u16],
+        };
+
+        Ok(Self { method, url, headers, body, timeout, expected_status })
+    }
+
+    fn accepts(&self, status: u16) -> bool {
+        if self.expected_status.is_empty() {
+            (200..300).contains(&status)
+        } else {
+            self.expected_status.contains(&status)
+        }
+    }
+}
+
+/// Send the request and return the status and the response body
+/// (parsed as JSON when possible, otherwise as a string).
+pub async fn send_webhook(
+    client: &reqwest::Client,
+    request: &WebhookRequest,
+) -> Result<(u16, Value), String> {
+    let mut builder = client.request(request.method.clone(), &request.url)
+        .timeout(request.timeout);
+
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+
+    builder = match &request.body {
+        Some(Value::String(text)) => builder.body(text.clone()),
+        Some(body) => builder.json(body),
+        None => builder,
+    };
+
+    let response = builder.send().await.map_err(|e| {
+        if e.is_timeout() {
+            format!("Webhook request timed out after {}s", request.timeout.as_secs())
+        } else {
+            format!("Webhook request failed: {}", e)
+        }
+    })?;
+
+    let status = response.status().as_u16();
+    let text = response.text().await
+        .map_err(|e| format!("Failed to read webhook response: {}", e))?;
+    let body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+
+    if !request.accepts(status) {
+        let snippet: String = match &body {
+            Value::String(s) => s.chars().take(500).collect(),
+            other => other.to_string().chars().take(500).collect(),
+        };
+        return Err(format!("Webhook returned unexpected status {}: {}", status, snippet));
+    }
+
+    Ok((status, body))
+}
+
+/// Pick fields out of a JSON response according to `response_mapping`
+pub fn map_response(
+    body: &Value,
+    mapping: &serde_json::Map<String, Value>,
+) -> Result<HashMap<String, Value>, String> {
+    mapping.iter().map(|(variable, path)| {
+        let path = path.as_str()
+            .ok_or_else(|| format!("response_mapping for '{}' must be a path string", variable))?;
+        let value = get_path(body, path)
+            .ok_or_else(|| format!("Field '{}' not found in webhook response", path))?;
+        Ok((variable.clone(), value.clone()))
+    }).collect()
+}
+
+impl FlowExecutor {
+    pub(crate) async fn execute_webhook_step(
+        &self,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &ExecutionScope,
+    ) -> Result<Value, String> {
+        let request = WebhookRequest::from_parameters(&step.parameters, &scope.variables)?;
+
+        self.emit(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.to_string(),
+            event_type: FlowEventType::StepProgress,
+            step_id: Some(step.id.clone()),
+            message: format!("Calling webhook {} {}", request.method, request.url),
+            data: HashMap::from([
+                ("method".to_string(), json!(request.method.as_str())),
+                ("url".to_string(), json!(request.url)),
+            ]),
+            timestamp: Utc::now(),
+        }).await;
+
+        let client = reqwest::Client::new();
+        let (status, body) = send_webhook(&client, &request).await?;
+
+        let mapped = match step.parameters.get("response_mapping").and_then(|v| v.as_object()) {
+            Some(mapping) => map_response(&body, mapping)?,
+            None => HashMap::new(),
+        };
+
+        Ok(json!({
+            "output": body,
+            "status_code": status,
+            "mapped_variables": mapped,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::post, Json, Router};
+
+    /// Start a mock HTTP server on a random local port
+    async fn mock_server(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_request_from_parameters() {
+        let params: HashMap<String, Value> = serde_json::from_value(json!({
+            "url": "http://tickets.local/issues/{{issue_id}}",
+            "method": "put",
+            "headers": {"Authorization": "Bearer {{token}}"},
+            "body": {"title": "Fix {{issue_id}}", "labels": "{{labels}}"},
+            "expected_status": [200, 204],
+        })).unwrap();
+        let vars = HashMap::from([
+            ("issue_id".to_string(), json!("42")),
+            ("token".to_string(), json!("secret")),
+            ("labels".to_string(), json!(["bug"])),
+        ]);
+
+        let request = WebhookRequest::from_parameters(&params, &vars).unwrap();
+        assert_eq!(request.method, Method::PUT);
+        assert_eq!(request.url, "http://tickets.local/issues/42");
+        assert_eq!(request.headers, vec![("Authorization".to_string(), "Bearer secret".to_string())]);
+        assert_eq!(request.body, Some(json!({"title": "Fix 42", "labels": ["bug"]})));
+        assert!(request.accepts(204));
+        assert!(!request.accepts(201));
+
+        assert!(WebhookRequest::from_parameters(&HashMap::new(), &vars).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_webhook_against_mock_server() {
+        let router = Router::new()
+            .route("/build", post(|Json(body): Json<Value>| async move {
+                (StatusCode::CREATED, Json(json!({"build": {"id": 17, "branch": body["branch"]}})))
+            }))
+            .route("/fail", post(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "boom") }));
+        let base = mock_server(router).await;
+
+        let params: HashMap<String, Value> = serde_json::from_value(json!({
+            "url": format!("{}/build", base),
+            "body": {"branch": "{{branch}}"},
+            "expected_status": 201,
+        })).unwrap();
+        let vars = HashMap::from([("branch".to_string(), json!("main"))]);
+        let request = WebhookRequest::from_parameters(&params, &vars).unwrap();
+
+        let client = reqwest::Client::new();
+        let (status, body) = send_webhook(&client, &request).await.unwrap();
+        assert_eq!(status, 201);
+
+        let mapping = json!({"build_id": "build.id", "build_branch": "build.branch"});
+        let mapped = map_response(&body, mapping.as_object().unwrap()).unwrap();
+        assert_eq!(mapped["build_id"], json!(17));
+        assert_eq!(mapped["build_branch"], json!("main"));
+
+        let missing = json!({"x": "build.missing"});
+        assert!(map_response(&body, missing.as_object().unwrap()).is_err());
+
+        let mut failing = request.clone();
+        failing.url = format!("{}/fail", base);
+        let err = send_webhook(&client, &failing).await.unwrap_err();
+        assert!(err.contains("500"));
+    }
+}
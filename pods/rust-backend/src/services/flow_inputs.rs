@@ -0,0 +1,319 @@
+//! Typed flow inputs: validation of execution input against `Flow.inputs`, and
+//! the `pod run` usage line generated from the same schema.
+//!
+//! Values are coerced where the intent is unambiguous, since the CLI sends
+//! `pod run flow count 3` as a number and web forms send everything as strings:
+//! `"3"` is accepted for an integer, `3` for a string, `"true"` for a boolean
+//! and a JSON string for an array or object. `pattern` is matched against
+//! string values anywhere in the string; anchor it with `^...$` for a full match.
+
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::error::FieldError;
+use crate::models::flow::{FlowInput, FlowInputType};
+
+/// Validate input against the schema and fill in defaults. Flows without a
+/// schema accept any input unchanged.
+pub fn validate_inputs(
+    schema: &HashMap<String, FlowInput>,
+    input: &HashMap<String, Value>,
+) -> Result<HashMap<String, Value>, Vec<FieldError>> {
+    if schema.is_empty() {
+        return Ok(input.clone());
+    }
+
+    let mut errors = Vec::new();
+    let mut validated = HashMap::new();
+
+    let mut unknown: Vec<&String> = input.keys().filter(|k| !schema.contains_key(*k)).collect();
+    unknown.sort();
+    for name in unknown {
+        errors.push(field_error(name, "is not a declared input"));
+    }
+
+    for (name, spec) in sorted(schema) {
+        match input.get(name).filter(|v| !v.is_null()) {
+            Some(value) => match check_value(spec, value) {
+                Ok(value) => {
+                    validated.insert(name.clone(), value);
+                }
+                Err(message) => errors.push(field_error(name, &message)),
+            },
+            None => match &spec.default {
+                Some(default) => {
+                    validated.insert(name.clone(), default.clone());
+                }
+                None if spec.required => errors.push(field_error(name, "is required")),
+                None => {}
+            },
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(validated)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Split submitted values into the flow's declared inputs and plain variables
+pub fn split_inputs(
+    schema: &HashMap<String, FlowInput>,
+    values: impl IntoIterator<Item = (String, Value)>,
+) -> (HashMap<String, Value>, HashMap<String, Value>) {
+    values.into_iter().partition(|(name, _)| schema.contains_key(name))
+}
+
+/// Problems with the schema itself, reported when a flow is saved
+pub fn validate_schema(schema: &HashMap<String, FlowInput>) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    for (name, spec) in sorted(schema) {
+        if let Some(pattern) = &spec.pattern {
+            if spec.input_type != FlowInputType::String {
+                errors.push(field_error(name, "pattern only applies to string inputs"));
+            } else if let Err(e) = Regex::new(pattern) {
+                errors.push(field_error(name, &format!("invalid pattern: {}", e)));
+            }
+        }
+        for allowed in spec.allowed_values.iter().flatten() {
+            if let Err(e) = coerce(spec.input_type, allowed) {
+                errors.push(field_error(name, &format!("enum value {}: {}", allowed, e)));
+            }
+        }
+        if let Some(default) = &spec.default {
+            if let Err(e) = check_value(spec, default) {
+                errors.push(field_error(name, &format!("default {}", e)));
+            }
+        }
+    }
+    errors
+}
+
+/// Coerce one value to the input's type and check its constraints
+fn check_value(spec: &FlowInput, value: &Value) -> Result<Value, String> {
+    let value = coerce(spec.input_type, value)?;
+
+    if let Some(allowed) = &spec.allowed_values {
+        let matches = allowed.iter().any(|a| coerce(spec.input_type, a).is_ok_and(|a| a == value));
+        if !matches {
+            let options: Vec<String> = allowed.iter().map(display_value).collect();
+            return Err(format!("must be one of: {}", options.join(", ")));
+        }
+    }
+
+    if let (Some(pattern), Value::String(text)) = (&spec.pattern, &value) {
+        let regex = Regex::new(pattern).map_err(|e| format!("has an invalid pattern: {}", e))?;
+        if !regex.is_match(text) {
+            return Err(format!("must match pattern {}", pattern));
+        }
+    }
+
+    Ok(value)
+}
+
+fn coerce(input_type: FlowInputType, value: &Value) -> Result<Value, String> {
+    let invalid = || Err(format!("must be {}", type_description(input_type)));
+    match (input_type, value) {
+        (FlowInputType::String, Value::String(_)) => Ok(value.clone()),
+        (FlowInputType::String, Value::Number(_) | Value::Bool(_)) => Ok(json!(display_value(value))),
+
+        (FlowInputType::Integer, Value::Number(n)) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => Ok(json!(i)),
+            (None, Some(f)) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => Ok(json!(f as i64)),
+            _ => invalid(),
+        },
+        (FlowInputType::Integer, Value::String(s)) => s.trim().parse::<i64>().map(|i| json!(i)).or_else(|_| invalid()),
+
+        (FlowInputType::Number, Value::Number(_)) => Ok(value.clone()),
+        (FlowInputType::Number, Value::String(s)) => match s.trim().parse::<f64>() {
+            Ok(f) if f.is_finite() => Ok(json!(f)),
+            _ => invalid(),
+        },
+
+        (FlowInputType::Boolean, Value::Bool(_)) => Ok(value.clone()),
+        (FlowInputType::Boolean, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" => Ok(json!(true)),
+            "false" => Ok(json!(false)),
+            _ => invalid(),
+        },
+
+        (FlowInputType::Array, Value::Array(_)) | (FlowInputType::Object, Value::Object(_)) => Ok(value.clone()),
+        (FlowInputType::Array | FlowInputType::Object, Value::String(s)) => match serde_json::from_str::<Value>(s) {
+            Ok(parsed @ Value::Array(_)) if input_type == FlowInputType::Array => Ok(parsed),
+            Ok(parsed @ Value::Object(_)) if input_type == FlowInputType::Object => Ok(parsed),
+            _ => invalid(),
+        },
+
+        _ => invalid(),
+    }
+}
+
+fn type_description(input_type: FlowInputType) -> &'static str {
+    match input_type {
+        FlowInputType::String => "a string",
+        FlowInputType::Integer => "an integer",
+        FlowInputType::Number => "a number",
+        FlowInputType::Boolean => "true or false",
+        FlowInputType::Array => "a JSON array",
+        FlowInputType::Object => "a JSON object",
+    }
+}
+
+fn type_name(input_type: FlowInputType) -> &'static str {
+    match input_type {
+        FlowInputType::String => "string",
+        FlowInputType::Integer => "integer",
+        FlowInputType::Number => "number",
+        FlowInputType::Boolean => "boolean",
+        FlowInputType::Array => "array",
+        FlowInputType::Object => "object",
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn field_error(field: &str, message: &str) -> FieldError {
+    FieldError { field: field.to_string(), message: message.to_string() }
+}
+
+/// Required inputs first, then alphabetical
+fn sorted(schema: &HashMap<String, FlowInput>) -> Vec<(&String, &FlowInput)> {
+    let mut entries: Vec<_> = schema.iter().collect();
+    entries.sort_by(|(a_name, a), (b_name, b)| b.required.cmp(&a.required).then(a_name.cmp(b_name)));
+    entries
+}
+
+/// `pod run` usage and an example invocation. Optional inputs and inputs with a
+/// default are shown in brackets.
+pub fn run_usage(flow_name: &str, schema: &HashMap<String, FlowInput>) -> (String, String) {
+    let mut command = format!("pod run {}", flow_name);
+    let mut example = command.clone();
+
+    for (name, spec) in sorted(schema) {
+        let placeholder = match &spec.allowed_values {
+            Some(values) if !values.is_empty() => {
+                values.iter().map(display_value).collect::<Vec<_>>().join("|")
+            }
+            _ => type_name(spec.input_type).to_string(),
+        };
+        if spec.required && spec.default.is_none() {
+            command.push_str(&format!(" {} <{}>", name, placeholder));
+
+            let sample = spec.allowed_values.as_ref()
+                .and_then(|values| values.first())
+                .map(display_value)
+                .unwrap_or_else(|| sample_value(spec.input_type).to_string());
+            example.push_str(&format!(" {} {}", name, shell_quote(&sample)));
+        } else {
+            command.push_str(&format!(" [{} <{}>]", name, placeholder));
+        }
+    }
+
+    (command, example)
+}
+
+/// Per-input help entries, in usage order
+pub fn describe_inputs(schema: &HashMap<String, FlowInput>) -> Vec<Value> {
+    sorted(schema).into_iter().map(|(name, spec)| json!({
+        "name": name,
+        "type": type_name(spec.input_type),
+        "required": spec.required && spec.default.is_none(),
+        "default": spec.default,
+        "description": spec.description,
+        "enum": spec.allowed_values,
+        "pattern": spec.pattern,
+    })).collect()
+}
+
+fn sample_value(input_type: FlowInputType) -> &'static str {
+    match input_type {
+        FlowInputType::String => "value",
+        FlowInputType::Integer => "1",
+        FlowInputType::Number => "1.5",
+        FlowInputType::Boolean => "true",
+        FlowInputType::Array => "[]",
+        FlowInputType::Object => "{}",
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "-_.,/:@".contains(c)) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> HashMap<String, FlowInput> {
+        serde_json::from_value(json!({
+            "issue_id": {"type": "integer", "required": true, "description": "Issue to fix"},
+            "priority": {"type": "string", "enum": ["low", "high"], "default": "low"},
+            "branch": {"type": "string", "pattern": "^[a-z0-9/-]+$"},
+            "dry_run": {"type": "boolean"},
+            "labels": {"type": "array"},
+        })).unwrap()
+    }
+
+    fn input(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_coerces_and_fills_defaults() {
+        let validated = validate_inputs(&schema(), &input(json!({
+            "issue_id": "42", "dry_run": "true", "labels": "[\"bug\"]", "branch": "fix/42",
+        }))).unwrap();
+
+        assert_eq!(validated["issue_id"], json!(42));
+        assert_eq!(validated["dry_run"], json!(true));
+        assert_eq!(validated["labels"], json!(["bug"]));
+        assert_eq!(validated["priority"], json!("low"));
+        assert_eq!(validated["branch"], json!("fix/42"));
+    }
+
+    #[test]
+    fn test_reports_field_level_errors() {
+        let errors = validate_inputs(&schema(), &input(json!({
+            "priority": "urgent", "branch": "Fix 42", "dry_run": "maybe", "extra": 1,
+        }))).unwrap_err();
+
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["extra", "issue_id", "branch", "dry_run", "priority"]);
+        assert_eq!(errors[1].message, "is required");
+        assert_eq!(errors[4].message, "must be one of: low, high");
+
+        assert!(validate_inputs(&HashMap::new(), &input(json!({"anything": 1}))).is_ok());
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        let bad: HashMap<String, FlowInput> = serde_json::from_value(json!({
+            "count": {"type": "integer", "default": "many"},
+            "name": {"type": "string", "pattern": "("},
+        })).unwrap();
+        let fields: Vec<String> = validate_schema(&bad).into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["count", "name"]);
+        assert!(validate_schema(&schema()).is_empty());
+    }
+
+    #[test]
+    fn test_run_usage() {
+        let (command, example) = run_usage("fix-issue", &schema());
+        assert_eq!(
+            command,
+            "pod run fix-issue issue_id <integer> [branch <string>] [dry_run <boolean>] [labels <array>] [priority <low|high>]"
+        );
+        assert_eq!(example, "pod run fix-issue issue_id 1");
+    }
+}
@@ -0,0 +1,344 @@
+//! Cron schedules that start flow executions.
+//!
+//! A background task checks the `flow_schedules` collection and starts an
+//! execution (through the queue) for each schedule whose `next_run_at` has
+//! passed. A fire time more than [`MISFIRE_GRACE`] old was missed, typically
+//! because the backend was down; the schedule's catch-up policy decides whether
+//! missed runs are dropped, collapsed into one execution or each started.
+//!
+//! Expressions are standard 5-field cron (`min hour day month weekday`, with
+//! Sunday as 0 or 7), 6/7-field expressions with seconds first and an optional
+//! year, or macros such as `@daily`, read in the schedule's IANA timezone.
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use mongodb::bson::doc;
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use crate::db::collections::*;
+use crate::error::AppError;
+use crate::models::flow::{ExecutionTrigger, FlowExecutionCreate};
+use crate::models::schedule::CatchUpPolicy;
+use crate::services::flow_service::FlowService;
+
+/// How often the scheduler looks for due schedules
+const SCHEDULER_TICK: std::time::Duration = std::time::Duration::from_secs(30);
+/// How late a fire time can be handled and still count as on time
+pub const MISFIRE_GRACE: Duration = Duration::minutes(2);
+/// Most missed runs `run_all` starts after downtime (the most recent ones)
+pub const MAX_CATCH_UP_RUNS: usize = 24;
+/// Bound on the fire times scanned when working out missed runs
+const MAX_MISSED_SCAN: usize = 100_000;
+
+const WEEKDAYS: [&str; 8] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+
+/// A parsed cron expression and the timezone it is read in
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    schedule: cron::Schedule,
+    timezone: Tz,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str, timezone: &str) -> Result<Self, String> {
+        let timezone = Tz::from_str(timezone.trim())
+            .map_err(|_| format!("Unknown timezone '{}'", timezone))?;
+        let schedule = cron::Schedule::from_str(&normalize_expression(expression)?)
+            .map_err(|e| format!("Invalid cron expression '{}': {}", expression, e))?;
+        Ok(Self { schedule, timezone })
+    }
+
+    /// First fire time strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedule.after(&after.with_timezone(&self.timezone))
+            .next()
+            .map(|t| t.with_timezone(&Utc))
+    }
+
+    /// Fire times from `from` (inclusive) up to `until` (exclusive)
+    fn times_between(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let first = self.next_after(from - Duration::nanoseconds(1));
+        std::iter::successors(first, move |t| self.next_after(*t))
+            .take_while(move |t| *t < until)
+    }
+
+    /// Work out which executions to start now for a schedule due at `next_run_at`
+    pub fn plan(&self, next_run_at: DateTime<Utc>, now: DateTime<Utc>, catch_up: CatchUpPolicy) -> RunPlan {
+        if next_run_at > now {
+            return RunPlan { fire: Vec::new(), skipped: 0, next_run_at: Some(next_run_at) };
+        }
+
+        let window_start = next_run_at.max(now - MISFIRE_GRACE);
+        let on_time = self.times_between(window_start, now + Duration::nanoseconds(1)).last();
+
+        // Keep only the most recent missed runs that `run_all` could start
+        let mut missed: VecDeque<DateTime<Utc>> = VecDeque::new();
+        let mut missed_count = 0;
+        for time in self.times_between(next_run_at, window_start).take(MAX_MISSED_SCAN) {
+            missed_count += 1;
+            if missed.len() == MAX_CATCH_UP_RUNS {
+                missed.pop_front();
+            }
+            missed.push_back(time);
+        }
+
+        let mut fire: Vec<DateTime<Utc>> = match catch_up {
+            CatchUpPolicy::Skip => Vec::new(),
+            CatchUpPolicy::RunOnce if on_time.is_some() => Vec::new(),
+            CatchUpPolicy::RunOnce => missed.back().copied().into_iter().collect(),
+            CatchUpPolicy::RunAll => missed.into_iter().collect(),
+        };
+        let skipped = missed_count - fire.len();
+        fire.extend(on_time);
+
+        RunPlan { fire, skipped, next_run_at: self.next_after(now) }
+    }
+}
+
+/// Executions a due schedule starts now
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunPlan {
+    /// Fire times to start an execution for, oldest first
+    pub fire: Vec<DateTime<Utc>>,
+    /// Missed runs dropped by the catch-up policy
+    pub skipped: usize,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+/// Turn a standard 5-field expression into the seconds-first form the `cron`
+/// crate reads, with its weekday numbering (1 = Sunday) avoided by using names
+pub fn normalize_expression(expression: &str) -> Result<String, String> {
+    let expression = expression.trim();
+    if expression.starts_with('@') {
+        return Ok(expression.to_string());
+    }
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    match fields.len() {
+        5 => {
+            let weekdays = fields[4].split(',')
+                .map(weekday_names)
+                .collect::<Result<Vec<_>, _>>()?
+                .join(",");
+            Ok(format!("0 {} {} {} {} {}", fields[0], fields[1], fields[2], fields[3], weekdays))
+        }
+        6 | 7 => Ok(fields.join(" ")),
+        n => Err(format!("Cron expression '{}' has {} fields, expected 5 (or 6-7 with seconds)", expression, n)),
+    }
+}
+
+/// Rewrite one weekday list item (`1-5`, `0/2`, `7`, `MON`) using day names
+fn weekday_names(item: &str) -> Result<String, String> {
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => (range, Some(step)),
+        None => (item, None),
+    };
+    let name = |day: &str| -> Result<String, String> {
+        match day.parse::<usize>() {
+            Ok(n) => WEEKDAYS.get(n).map(|d| d.to_string())
+                .ok_or_else(|| format!("Weekday '{}' is out of range 0-7", day)),
+            Err(_) => Ok(day.to_string()),
+        }
+    };
+    let range = match range.split_once('-') {
+        // Sunday as 7 ends a range after Saturday
+        Some((start, "7")) => format!("{}-SAT,SUN", name(start)?),
+        Some((start, end)) => format!("{}-{}", name(start)?, name(end)?),
+        None => name(range)?,
+    };
+    Ok(match step {
+        Some(step) => format!("{}/{}", range, step),
+        None => range,
+    })
+}
+
+impl FlowService {
+    /// Start due schedules' executions every [`SCHEDULER_TICK`]
+    pub fn start_scheduler(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = service.fire_due_schedules().await {
+                    tracing::error!("Failed to run due schedules: {}", e);
+                }
+                tokio::time::sleep(SCHEDULER_TICK).await;
+            }
+        });
+    }
+
+    /// Start executions for every enabled schedule whose next run has passed
+    pub(crate) async fn fire_due_schedules(&self) -> Result<(), AppError> {
+        let collection = self.db().collection::<bson::Document>(FLOW_SCHEDULES);
+        let now = Utc::now();
+        let mut cursor = collection
+            .find(doc! { "enabled": true, "next_run_at": { "$lte": bson::DateTime::from_chrono(now) } })
+            .await?;
+        let mut due = Vec::new();
+        while cursor.advance().await? {
+            due.push(cursor.deserialize_current()?);
+        }
+
+        for schedule_doc in due {
+            if let Err(e) = self.fire_schedule(&schedule_doc, now).await {
+                tracing::warn!(schedule_id = ?schedule_doc.get_object_id("_id").ok(), error = %e, "Schedule run failed");
+            }
+        }
+        Ok(())
+    }
+
+    async fn fire_schedule(&self, schedule_doc: &bson::Document, now: DateTime<Utc>) -> Result<(), AppError> {
+        let collection = self.db().collection::<bson::Document>(FLOW_SCHEDULES);
+        let oid = schedule_doc.get_object_id("_id").map_err(|e| AppError::Internal(e.to_string()))?;
+        let schedule_id = oid.to_hex();
+        let next_run_at = schedule_doc.get_datetime("next_run_at").map_err(|e| AppError::Internal(e.to_string()))?;
+        let catch_up: CatchUpPolicy = schedule_doc.get_str("catch_up").ok()
+            .and_then(|p| serde_json::from_value(serde_json::json!(p)).ok())
+            .unwrap_or_default();
+
+        let plan = match CronSchedule::parse(
+            schedule_doc.get_str("cron").unwrap_or_default(),
+            schedule_doc.get_str("timezone").unwrap_or("UTC"),
+        ) {
+            Ok(cron) => cron.plan(next_run_at.to_chrono(), now, catch_up),
+            Err(e) => {
+                // Stop retrying an expression that no longer parses
+                collection.update_one(
+                    doc! { "_id": oid },
+                    doc! { "$set": { "next_run_at": bson::Bson::Null, "last_error": e, "updated_at": bson::DateTime::from_chrono(now) } },
+                ).await?;
+                return Ok(());
+            }
+        };
+
+        // Claim the run by moving next_run_at, so a schedule fires once even if
+        // two passes overlap
+        let claimed = collection.update_one(
+            doc! { "_id": oid, "next_run_at": next_run_at },
+            doc! {
+                "$set": {
+                    "next_run_at": plan.next_run_at.map(bson::DateTime::from_chrono),
+                    "updated_at": bson::DateTime::from_chrono(now),
+                },
+                "$inc": { "skipped_runs": plan.skipped as i64 },
+            },
+        ).await?;
+        if claimed.modified_count == 0 {
+            return Ok(());
+        }
+
+        let flow_id = schedule_doc.get_str("flow_id").unwrap_or_default();
+        let user_id = schedule_doc.get_str("user_id").unwrap_or_default();
+        let field = |key: &str| -> std::collections::HashMap<String, serde_json::Value> {
+            schedule_doc.get_document(key).ok()
+                .and_then(|d| bson::from_document(d.clone()).ok())
+                .unwrap_or_default()
+        };
+        let priority = schedule_doc.get_i32("priority").ok();
+
+        for scheduled_for in plan.fire {
+            let trigger = ExecutionTrigger::Schedule { schedule_id: schedule_id.clone(), scheduled_for };
+            let request = FlowExecutionCreate {
+                input_data: field("input_data"),
+                variables: field("variables"),
+                priority,
+                ..Default::default()
+            };
+            let result = self.execute_flow(flow_id, user_id, request, trigger).await;
+            let mut set = doc! { "last_run_at": bson::DateTime::from_chrono(Utc::now()) };
+            match result {
+                Ok(admitted) => {
+                    tracing::info!(schedule_id = %schedule_id, execution_id = %admitted.execution_id, "Schedule started an execution");
+                    set.insert("last_execution_id", admitted.execution_id);
+                    set.insert("last_error", bson::Bson::Null);
+                }
+                Err(e) => {
+                    tracing::warn!(schedule_id = %schedule_id, error = %e, "Schedule could not start an execution");
+                    set.insert("last_error", e.to_string());
+                }
+            }
+            collection.update_one(doc! { "_id": oid }, doc! { "$set": set, "$inc": { "run_count": 1 } }).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 2, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_normalize_expression() {
+        assert_eq!(normalize_expression("30 2 * * *").unwrap(), "0 30 2 * * *");
+        assert_eq!(normalize_expression("0 9 * * 1-5").unwrap(), "0 0 9 * * MON-FRI");
+        assert_eq!(normalize_expression("0 9 * * 0,6").unwrap(), "0 0 9 * * SUN,SAT");
+        assert_eq!(normalize_expression("0 9 * * 5-7").unwrap(), "0 0 9 * * FRI-SAT,SUN");
+        assert_eq!(normalize_expression("0 9 * * MON").unwrap(), "0 0 9 * * MON");
+        assert_eq!(normalize_expression("0 0 9 * * *").unwrap(), "0 0 9 * * *");
+        assert_eq!(normalize_expression("@daily").unwrap(), "@daily");
+        assert!(normalize_expression("* * *").is_err());
+        assert!(normalize_expression("0 9 * * 8").is_err());
+    }
+
+    #[test]
+    fn test_next_run_in_timezone() {
+        // 2026-03-02 is a Monday; 09:00 in Madrid is 08:00 UTC in winter
+        let cron = CronSchedule::parse("0 9 * * 1", "Europe/Madrid").unwrap();
+        assert_eq!(cron.next_after(at(0, 0)), Some(at(8, 0)));
+        assert_eq!(cron.next_after(at(8, 0)), Some(at(8, 0) + Duration::days(7)));
+
+        assert!(CronSchedule::parse("0 9 * * 1", "Mars/Olympus").is_err());
+        assert!(CronSchedule::parse("61 9 * * *", "UTC").is_err());
+    }
+
+    #[test]
+    fn test_plan_on_time_run() {
+        let cron = CronSchedule::parse("0 * * * *", "UTC").unwrap();
+        let plan = cron.plan(at(10, 0), at(10, 0) + Duration::seconds(20), CatchUpPolicy::Skip);
+        assert_eq!(plan.fire, vec![at(10, 0)]);
+        assert_eq!(plan.skipped, 0);
+        assert_eq!(plan.next_run_at, Some(at(11, 0)));
+
+        let plan = cron.plan(at(11, 0), at(10, 30), CatchUpPolicy::Skip);
+        assert!(plan.fire.is_empty());
+        assert_eq!(plan.next_run_at, Some(at(11, 0)));
+    }
+
+    #[test]
+    fn test_plan_catch_up_after_downtime() {
+        // Hourly schedule due at 03:00, backend back at 07:30: 03:00-07:00 were missed
+        let cron = CronSchedule::parse("0 * * * *", "UTC").unwrap();
+        let now = at(7, 30);
+
+        let plan = cron.plan(at(3, 0), now, CatchUpPolicy::Skip);
+        assert!(plan.fire.is_empty());
+        assert_eq!(plan.skipped, 5);
+        assert_eq!(plan.next_run_at, Some(at(8, 0)));
+
+        let plan = cron.plan(at(3, 0), now, CatchUpPolicy::RunOnce);
+        assert_eq!(plan.fire, vec![at(7, 0)]);
+        assert_eq!(plan.skipped, 4);
+
+        let plan = cron.plan(at(3, 0), now, CatchUpPolicy::RunAll);
+        assert_eq!(plan.fire, vec![at(3, 0), at(4, 0), at(5, 0), at(6, 0), at(7, 0)]);
+        assert_eq!(plan.skipped, 0);
+
+        // Back right at a fire time: it runs on time and run_once adds nothing
+        let plan = cron.plan(at(3, 0), at(7, 0) + Duration::seconds(5), CatchUpPolicy::RunOnce);
+        assert_eq!(plan.fire, vec![at(7, 0)]);
+        assert_eq!(plan.skipped, 4);
+    }
+
+    #[test]
+    fn test_run_all_is_capped() {
+        let cron = CronSchedule::parse("0 * * * *", "UTC").unwrap();
+        let plan = cron.plan(at(0, 0) - Duration::days(3), at(0, 30), CatchUpPolicy::RunAll);
+        assert_eq!(plan.fire.len(), MAX_CATCH_UP_RUNS);
+        assert_eq!(plan.fire.last(), Some(&at(0, 0)));
+        assert_eq!(plan.skipped, 73 - MAX_CATCH_UP_RUNS);
+    }
+}
@@ -2,8 +2,10 @@ use bson::oid::ObjectId;
 use chrono::Utc;
 use mongodb::bson::doc;
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 
 use crate::db::collections::*;
@@ -11,20 +13,42 @@ use crate::error::AppError;
 use crate::models::flow::*;
 use crate::models::flow_events::*;
 use crate::services::agent_api_client::AgentApiClient;
-use crate::services::mcp_session_manager::McpSessionManager;
+use crate::services::flow_executor::debugger::check_breakpoints;
+use crate::services::flow_executor::expression;
+use crate::services::flow_executor::retry::RetryPolicy;
+use crate::services::flow_executor::step_handlers::feedback_loop::{feedback_edge_into, feedback_task_context};
+use crate::services::flow_executor::step_handlers::structured_output::StructuredOutput;
+use crate::services::execution_queue::{
+    execution_priority, select_ready, ActiveExecution, AdmittedExecution, ConcurrencyPolicy, ExecutionQueue,
+    QueueLimits, QueuedExecution,
+};
+use crate::services::execution_control::{ExecutionControl, ExecutionControls};
+use crate::services::execution_rerun::{apply_parameter_overrides, restore_state};
+use crate::services::flow_inputs;
+use crate::services::flow_validator;
+use crate::services::mcp_session_manager::{McpSessionManager, DEFAULT_TOOL_CALL_TIMEOUT};
 use crate::auth::encryption::FernetCipher;
 
 /// Event channel type for SSE subscribers
 pub type EventSender = broadcast::Sender<FlowExecutionEvent>;
 pub type EventReceiver = broadcast::Receiver<FlowExecutionEvent>;
 
-/// Flow execution service - manages flow executions and event broadcasting
+/// How often the queue dispatcher looks for startable executions without
+/// being woken
+const QUEUE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Flow execution service - manages flow executions and event broadcasting.
+/// Clones share the event channels, the execution queue and the control handles.
+#[derive(Clone)]
 pub struct FlowService {
     mongo_client: mongodb::Client,
     cipher: FernetCipher,
     mcp_manager: Arc<McpSessionManager>,
     /// Map of execution_id -> event channel sender
     event_channels: Arc<tokio::sync::RwLock<HashMap<String, EventSender>>>,
+    queue: Arc<ExecutionQueue>,
+    /// Cancellation and decision signals of the executions running here
+    pub(crate) controls: Arc<ExecutionControls>,
 }
 
 impl FlowService {
@@ -32,16 +56,19 @@ impl FlowService {
         mongo_client: mongodb::Client,
         cipher: FernetCipher,
         mcp_manager: Arc<McpSessionManager>,
+        queue_limits: QueueLimits,
     ) -> Self {
         Self {
             mongo_client,
             cipher,
             mcp_manager,
             event_channels: Arc::new(tokio::sync::RwLock::new(HashMap::new())),
+            queue: Arc::new(ExecutionQueue::new(queue_limits)),
+            controls: Arc::new(ExecutionControls::default()),
         }
     }
 
-    fn db(&self) -> mongodb::Database {
+    pub(crate) fn db(&self) -> mongodb::Database {
         self.mongo_client.database(DB_NAME)
     }
 
@@ -68,76 +95,355 @@ impl FlowService {
         }
     }
 
-    /// Execute a flow
+    /// Queue an execution of flow `flow_id`; it starts right away when the
+    /// concurrency limits allow. A request naming another flow is rejected.
     pub async fn execute_flow(
         &self,
         flow_id: &str,
         user_id: &str,
-        input_data: HashMap<String, Value>,
-        variables: HashMap<String, Value>,
-    ) -> Result<String, AppError> {
-        // Load flow
-        let flow_collection = self.db().collection::<bson::Document>(FLOWS);
-        let flow_doc = if let Ok(oid) = ObjectId::parse_str(flow_id) {
-            flow_collection.find_one(doc! { "_id": oid, "user_id": user_id }).await?
-        } else {
-            flow_collection.find_one(doc! { "_id": flow_id, "user_id": user_id }).await?
+        request: FlowExecutionCreate,
+        trigger: ExecutionTrigger,
+    ) -> Result<AdmittedExecution, AppError> {
+        let FlowExecutionCreate { flow_id: requested_flow_id, input_data, variables, priority, debug } = request;
+        let flow = self.load_flow(flow_id, user_id).await?;
+        if let Some(requested) = requested_flow_id.filter(|id| id != flow_id && flow.id.as_ref() != Some(id)) {
+            return Err(AppError::BadRequest(format!(
+                "flow_id '{}' does not match the flow being executed ('{}')", requested, flow_id
+            )));
+        }
+        let input_data = flow_inputs::validate_inputs(&flow.inputs, &input_data)
+            .map_err(AppError::InvalidInput)?;
+        if let Some(ref debug) = debug {
+            check_breakpoints(&flow, debug)?;
+        }
+
+        // Validated inputs are available to templates alongside the variables
+        let mut scope_variables = variables.clone();
+        scope_variables.extend(input_data.clone());
+        let scope = ExecutionScope::new(scope_variables, flow.edge_metadata.clone());
+        let start = ExecutionCheckpoint::capture(&flow.start_step_id, None, &scope);
+
+        let execution_id = {
+            let _queue = self.queue.dispatch_lock.lock().await;
+            let mut fields = self.queue_fields(flow_id, &flow, priority, &start).await?;
+            fields.insert("trigger", bson::to_bson(&trigger).map_err(|e| AppError::Internal(e.to_string()))?);
+            if let Some(ref debug) = debug {
+                fields.insert("debug", bson::to_bson(debug).map_err(|e| AppError::Internal(e.to_string()))?);
+            }
+            self.create_execution(flow_id, &flow, &input_data, &variables, fields).await?
         };
 
-        let flow_doc = flow_doc.ok_or_else(|| AppError::NotFound("Flow not found".to_string()))?;
+        self.emit_event(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.clone(),
+            event_type: FlowEventType::ExecutionQueued,
+            step_id: None,
+            message: format!("Flow '{}' execution queued", flow.name),
+            data: HashMap::from([
+                ("priority".to_string(), json!(execution_priority(&flow, priority))),
+                ("trigger".to_string(), json!(trigger)),
+            ]),
+            timestamp: Utc::now(),
+        }).await;
+
+        self.admit(&execution_id).await
+    }
+
+    /// Fields that put a new execution in the queue, to start at `start`.
+    /// Callers hold the dispatch lock until the execution is inserted, so two
+    /// requests can't both pass the `skip_if_running` check.
+    async fn queue_fields(
+        &self,
+        flow_id: &str,
+        flow: &Flow,
+        priority: Option<i32>,
+        start: &ExecutionCheckpoint,
+    ) -> Result<bson::Document, AppError> {
+        let policy = ConcurrencyPolicy::from_flow(flow);
+        if policy == ConcurrencyPolicy::SkipIfRunning {
+            let active = self.db().collection::<bson::Document>(FLOW_EXECUTIONS)
+                .count_documents(doc! {
+                    "flow_id": flow_id,
+                    "status": { "$in": ["queued", "running", "awaiting_input", "paused", "waiting"] },
+                    "parent_execution_id": null,
+                })
+                .await?;
+            if active > 0 {
+                return Err(AppError::Conflict(format!(
+                    "Flow '{}' already has an execution queued or running and skips new ones meanwhile",
+                    flow.name
+                )));
+            }
+        }
+
+        Ok(doc! {
+            "status": "queued",
+            "priority": execution_priority(flow, priority),
+            "concurrency_policy": policy.as_str(),
+            "queued_at": bson::DateTime::from_chrono(Utc::now()),
+            "start_time": bson::Bson::Null,
+            "checkpoint": bson::to_bson(start).map_err(|e| AppError::Internal(e.to_string()))?,
+        })
+    }
+
+    /// Run a dispatch pass, then report whether `execution_id` started
+    async fn admit(&self, execution_id: &str) -> Result<AdmittedExecution, AppError> {
+        self.dispatch_queued().await?;
+
+        let oid = ObjectId::parse_str(execution_id)
+            .map_err(|_| AppError::Internal("Invalid execution ID".to_string()))?;
+        let exec_doc = self.db().collection::<bson::Document>(FLOW_EXECUTIONS)
+            .find_one(doc! { "_id": oid })
+            .await?
+            .ok_or_else(|| AppError::NotFound("Execution not found".to_string()))?;
+        let status = serde_json::from_value(json!(exec_doc.get_str("status").unwrap_or("queued")))
+            .unwrap_or(FlowExecutionStatus::Queued);
+
+        Ok(AdmittedExecution {
+            execution_id: execution_id.to_string(),
+            status,
+            queue_position: self.queue_position(&exec_doc).await?,
+        })
+    }
+
+    /// 1-based position of a queued execution in dispatch order, `None` once
+    /// it has left the queue
+    pub async fn queue_position(&self, exec_doc: &bson::Document) -> Result<Option<u64>, AppError> {
+        if exec_doc.get_str("status") != Ok("queued") {
+            return Ok(None);
+        }
+        let (Ok(oid), Ok(queued_at)) = (exec_doc.get_object_id("_id"), exec_doc.get_datetime("queued_at")) else {
+            return Ok(None);
+        };
+        let priority = exec_doc.get_i32("priority").unwrap_or(0);
+
+        let ahead = self.db().collection::<bson::Document>(FLOW_EXECUTIONS)
+            .count_documents(doc! {
+                "status": "queued",
+                "$or": [
+                    { "priority": { "$gt": priority } },
+                    { "priority": priority, "queued_at": { "$lt": queued_at } },
+                    { "priority": priority, "queued_at": queued_at, "_id": { "$lt": oid } },
+                ],
+            })
+            .await?;
+        Ok(Some(ahead + 1))
+    }
+
+    /// Start the queued executions the concurrency limits and flow policies allow
+    pub(crate) async fn dispatch_queued(&self) -> Result<(), AppError> {
+        let _dispatching = self.queue.dispatch_lock.lock().await;
+        let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let fields = |doc: &bson::Document, key: &str| doc.get_str(key).unwrap_or_default().to_string();
+
+        // Executions whose delay is over go back in the queue
+        let now = bson::DateTime::from_chrono(Utc::now());
+        collection.update_many(
+            doc! { "status": "waiting", "wake_at": { "$lte": now } },
+            doc! { "$set": { "status": "queued", "queued_at": now, "updated_at": now } },
+        ).await?;
+
+        // Sub-flow executions run in their parent's slot; executions awaiting
+        // input or paused keep theirs
+        let mut cursor = collection
+            .find(doc! { "status": { "$in": ["running", "awaiting_input", "paused"] }, "parent_execution_id": null })
+            .projection(doc! { "user_id": 1, "flow_id": 1 })
+            .await?;
+        let mut running = Vec::new();
+        while cursor.advance().await? {
+            let doc = cursor.deserialize_current()?;
+            running.push(ActiveExecution { user_id: fields(&doc, "user_id"), flow_id: fields(&doc, "flow_id") });
+        }
+
+        let mut cursor = collection
+            .find(doc! { "status": "queued" })
+            .projection(doc! { "user_id": 1, "flow_id": 1, "concurrency_policy": 1 })
+            .sort(doc! { "priority": -1, "queued_at": 1, "_id": 1 })
+            .await?;
+        let mut queued = Vec::new();
+        while cursor.advance().await? {
+            let doc = cursor.deserialize_current()?;
+            let Ok(oid) = doc.get_object_id("_id") else { continue };
+            queued.push(QueuedExecution {
+                execution_id: oid.to_hex(),
+                user_id: fields(&doc, "user_id"),
+                flow_id: fields(&doc, "flow_id"),
+                policy: ConcurrencyPolicy::parse(doc.get_str("concurrency_policy").unwrap_or_default()),
+            });
+        }
+
+        for execution_id in select_ready(&running, &queued, self.queue.limits) {
+            let Ok(oid) = ObjectId::parse_str(&execution_id) else { continue };
+            let now = bson::DateTime::from_chrono(Utc::now());
+            // Claiming only a still-queued execution skips ones cancelled meanwhile.
+            // One woken from a delay keeps its start time.
+            let claimed = collection
+                .find_one_and_update(
+                    doc! { "_id": oid, "status": "queued" },
+                    vec![doc! { "$set": {
+                        "status": "running",
+                        "start_time": { "$ifNull": ["$start_time", now] },
+                        "updated_at": now,
+                    } }],
+                )
+                .return_document(mongodb::options::ReturnDocument::After)
+                .await?;
+            if let Some(exec_doc) = claimed {
+                self.start_queued(exec_doc).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Start a claimed execution at the checkpoint it was queued with
+    async fn start_queued(&self, exec_doc: bson::Document) {
+        let Ok(oid) = exec_doc.get_object_id("_id") else { return };
+        let execution_id = oid.to_hex();
+        let checkpoint = exec_doc.get_document("checkpoint").ok()
+            .and_then(|c| bson::from_document::<ExecutionCheckpoint>(c.clone()).ok());
+
+        let (flow, checkpoint) = match (self.execution_flow(&exec_doc).await, checkpoint) {
+            (Ok(flow), Some(checkpoint)) => (flow, checkpoint),
+            (flow, _) => {
+                let reason = flow.err().map(|e| e.to_string()).unwrap_or_else(|| "no start state was saved".to_string());
+                let error = format!("Execution could not start: {}", reason);
+                tracing::warn!(execution_id = %execution_id, error = %error, "Failing queued execution");
+                let now = bson::DateTime::from_chrono(Utc::now());
+                let _ = self.db().collection::<bson::Document>(FLOW_EXECUTIONS).update_one(
+                    doc! { "_id": oid },
+                    doc! { "$set": { "status": "failed", "error": &error, "end_time": now, "updated_at": now } },
+                ).await;
+                self.emit_event(FlowExecutionEvent {
+                    id: None,
+                    execution_id,
+                    event_type: FlowEventType::ExecutionFailed,
+                    step_id: None,
+                    message: error,
+                    data: HashMap::new(),
+                    timestamp: Utc::now(),
+                }).await;
+                return;
+            }
+        };
+
+        let mut data = HashMap::new();
+        if let Ok(queued_at) = exec_doc.get_datetime("queued_at") {
+            data.insert("queued_ms".to_string(), json!((Utc::now() - queued_at.to_chrono()).num_milliseconds()));
+        }
+        let (event_type, message) = if exec_doc.contains_key("wake_at") {
+            let _ = self.db().collection::<bson::Document>(FLOW_EXECUTIONS)
+                .update_one(doc! { "_id": oid }, doc! { "$unset": { "wake_at": "" } })
+                .await;
+            data.insert("reason".to_string(), json!("delay_elapsed"));
+            (FlowEventType::ExecutionResumed, format!("Execution resumed at step '{}' after its delay", checkpoint.step_id))
+        } else {
+            (FlowEventType::ExecutionStarted, format!("Flow '{}' execution started", flow.name))
+        };
+        self.emit_event(FlowExecutionEvent {
+            id: None,
+            execution_id: execution_id.clone(),
+            event_type,
+            step_id: Some(checkpoint.step_id.clone()),
+            message,
+            data,
+            timestamp: Utc::now(),
+        }).await;
 
-        // Remove date fields before deserializing — bson::DateTime can't map to chrono directly
-        let mut flow_doc_clean = flow_doc.clone();
-        flow_doc_clean.remove("created_at");
-        flow_doc_clean.remove("updated_at");
+        let start_step_id = checkpoint.step_id.clone();
+        self.restore_debug_state(&execution_id, &exec_doc);
+        self.spawn_executor(flow, execution_id, start_step_id, checkpoint.into_scope());
+    }
 
-        let flow: Flow = bson::from_document(flow_doc_clean)
-            .map_err(|e| AppError::Internal(format!("Failed to deserialize flow: {}", e)))?;
+    /// Dispatch queued executions in the background: after each execution
+    /// finishes, and periodically
+    pub fn start_queue_dispatcher(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = service.dispatch_queued().await {
+                    tracing::error!("Failed to dispatch queued executions: {}", e);
+                }
+                let _ = tokio::time::timeout(QUEUE_POLL_INTERVAL, service.queue.wake.notified()).await;
+            }
+        });
+    }
 
-        // Create execution record
+    /// Insert the execution record for a run of `flow`, returning its ID.
+    /// `extra_fields` (links to a parent or rerun source) are added to the record.
+    pub(crate) async fn create_execution(
+        &self,
+        flow_id: &str,
+        flow: &Flow,
+        input_data: &HashMap<String, Value>,
+        variables: &HashMap<String, Value>,
+        extra_fields: bson::Document,
+    ) -> Result<String, AppError> {
         let now = bson::DateTime::from_chrono(Utc::now());
-        let execution_doc = doc! {
+        let mut execution_doc = doc! {
             "flow_id": flow_id,
-            "user_id": user_id,
+            "user_id": &flow.user_id,
             "status": "running",
-            "input_data": bson::to_bson(&input_data).unwrap_or(bson::Bson::Document(doc!{})),
-            "variables": bson::to_bson(&variables).unwrap_or(bson::Bson::Document(doc!{})),
+            "input_data": bson::to_bson(input_data).unwrap_or(bson::Bson::Document(doc!{})),
+            "variables": bson::to_bson(variables).unwrap_or(bson::Bson::Document(doc!{})),
             "current_step_id": &flow.start_step_id,
             "completed_steps": [],
             "failed_steps": [],
             "step_results": {},
+            "edge_states": bson::to_bson(&flow.edge_metadata).unwrap_or(bson::Bson::Document(doc!{})),
             "start_time": now,
             "created_at": now,
             "updated_at": now,
             "is_cancellation_requested": false,
         };
+        execution_doc.extend(extra_fields);
 
         let exec_collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
         let result = exec_collection.insert_one(execution_doc).await
             .map_err(|e| AppError::Database(e.to_string()))?;
 
-        let execution_id = result.inserted_id.as_object_id()
+        result.inserted_id.as_object_id()
             .map(|oid| oid.to_hex())
-            .ok_or_else(|| AppError::Internal("Failed to get execution ID".to_string()))?;
+            .ok_or_else(|| AppError::Internal("Failed to get execution ID".to_string()))
+    }
 
-        // Emit start event
-        self.emit_event(FlowExecutionEvent {
-            id: None,
-            execution_id: execution_id.clone(),
-            event_type: FlowEventType::ExecutionStarted,
-            step_id: None,
-            message: format!("Flow '{}' execution started", flow.name),
-            data: HashMap::new(),
-            timestamp: Utc::now(),
-        }).await;
+    pub(crate) async fn load_flow(&self, flow_id: &str, user_id: &str) -> Result<Flow, AppError> {
+        let flow_collection = self.db().collection::<bson::Document>(FLOWS);
+        let flow_doc = if let Ok(oid) = ObjectId::parse_str(flow_id) {
+            flow_collection.find_one(doc! { "_id": oid, "user_id": user_id }).await?
+        } else {
+            flow_collection.find_one(doc! { "_id": flow_id, "user_id": user_id }).await?
+        };
 
-        // Spawn execution in background
-        let exec_id = execution_id.clone();
-        let flow_service = FlowService::new(
-            self.mongo_client.clone(),
-            self.cipher.clone(),
-            Arc::clone(&self.mcp_manager),
-        );
+        let flow_doc = flow_doc.ok_or_else(|| AppError::NotFound("Flow not found".to_string()))?;
+        flow_from_document(flow_doc)
+    }
+
+    pub(crate) async fn load_flow_by_name(&self, name: &str, user_id: &str) -> Result<Flow, AppError> {
+        let flow_doc = self.db().collection::<bson::Document>(FLOWS)
+            .find_one(doc! { "name": name, "user_id": user_id })
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Flow '{}' not found", name)))?;
+        flow_from_document(flow_doc)
+    }
+
+    /// The flow an execution runs, with the execution's step parameter overrides
+    pub(crate) async fn execution_flow(&self, exec_doc: &bson::Document) -> Result<Flow, AppError> {
+        let flow_id = exec_doc.get_str("flow_id").unwrap_or_default();
+        let user_id = exec_doc.get_str("user_id").unwrap_or_default();
+        let mut flow = self.load_flow(flow_id, user_id).await?;
+
+        let overrides: HashMap<String, HashMap<String, Value>> = exec_doc.get_document("parameter_overrides").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default();
+        apply_parameter_overrides(&mut flow, &overrides).map_err(AppError::BadRequest)?;
+        Ok(flow)
+    }
+
+    /// Run an execution in the background, starting at `start_step_id` with `scope`
+    fn spawn_executor(&self, flow: Flow, execution_id: String, start_step_id: String, scope: ExecutionScope) {
+        self.controls.register(&execution_id, None);
+        let flow_service = self.clone();
         // Share event channels
         let channels = Arc::clone(&self.event_channels);
 
@@ -146,29 +452,205 @@ impl FlowService {
                 flow_service,
                 channels,
             );
-            executor.run(flow, &exec_id, input_data, variables).await;
+            executor.run(flow, &execution_id, &start_step_id, scope).await;
         });
+    }
+
+    /// Deal with executions left `running` by a previous backend process. Each is
+    /// resumed from its last checkpoint if its flow's `on_interrupt` policy is
+    /// `resume`, and marked failed as interrupted otherwise.
+    pub async fn recover_interrupted_executions(&self) -> Result<(), AppError> {
+        let exec_collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let mut cursor = exec_collection.find(doc! { "status": { "$in": ["running", "awaiting_input", "paused"] } }).await?;
+        let mut interrupted = Vec::new();
+        while cursor.advance().await? {
+            interrupted.push(cursor.deserialize_current()?);
+        }
+
+        for exec_doc in interrupted {
+            let Ok(oid) = exec_doc.get_object_id("_id") else { continue };
+            let execution_id = oid.to_hex();
+
+            // Reruns with edited step parameters resume with the same edits
+            let flow = match self.execution_flow(&exec_doc).await {
+                Ok(flow) => Some(flow),
+                Err(e) => {
+                    tracing::warn!(execution_id = %execution_id, error = %e, "Could not load the flow of an interrupted execution");
+                    None
+                }
+            };
+            let checkpoint = exec_doc.get_document("checkpoint").ok()
+                .and_then(|c| bson::from_document::<ExecutionCheckpoint>(c.clone()).ok());
+            // A sub-flow execution is not resumed on its own: its parent re-runs the
+            // sub-flow step when it resumes
+            let policy = match flow {
+                Some(ref flow) if !exec_doc.contains_key("parent_execution_id") => InterruptPolicy::from_flow(flow),
+                _ => InterruptPolicy::Fail,
+            };
+
+            match (flow, checkpoint, policy) {
+                (Some(flow), Some(checkpoint), InterruptPolicy::Resume) => {
+                    tracing::info!(execution_id = %execution_id, step_id = %checkpoint.step_id, "Resuming interrupted execution");
+                    self.emit_event(FlowExecutionEvent {
+                        id: None,
+                        execution_id: execution_id.clone(),
+                        event_type: FlowEventType::ExecutionResumed,
+                        step_id: Some(checkpoint.step_id.clone()),
+                        message: format!("Execution resumed at step '{}' after a restart", checkpoint.step_id),
+                        data: HashMap::from([("reason".to_string(), json!("interrupted"))]),
+                        timestamp: Utc::now(),
+                    }).await;
+
+                    let start_step_id = checkpoint.step_id.clone();
+                    self.restore_debug_state(&execution_id, &exec_doc);
+                    self.spawn_executor(flow, execution_id, start_step_id, checkpoint.into_scope());
+                }
+                (_, checkpoint, _) => {
+                    let error = "Execution interrupted: the backend stopped while it was running";
+                    tracing::warn!(execution_id = %execution_id, "Marking interrupted execution as failed");
+                    let now = bson::DateTime::from_chrono(Utc::now());
+                    exec_collection.update_one(
+                        doc! { "_id": oid },
+                        doc! { "$set": { "status": "failed", "error": error, "end_time": now, "updated_at": now } },
+                    ).await?;
+
+                    self.emit_event(FlowExecutionEvent {
+                        id: None,
+                        execution_id,
+                        event_type: FlowEventType::ExecutionFailed,
+                        step_id: checkpoint.map(|c| c.step_id),
+                        message: error.to_string(),
+                        data: HashMap::from([("reason".to_string(), json!("interrupted"))]),
+                        timestamp: Utc::now(),
+                    }).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queue a new execution of the same flow at `from_step` (the start step when
+    /// `None`) with the state the execution had when that step started. Returns
+    /// the new execution and the step it starts at.
+    pub async fn rerun_execution(
+        &self,
+        execution_id: &str,
+        user_id: &str,
+        from_step: Option<&str>,
+        edits: FlowExecutionRerun,
+    ) -> Result<(AdmittedExecution, String), AppError> {
+        let oid = ObjectId::parse_str(execution_id)
+            .map_err(|_| AppError::BadRequest("Invalid execution ID".to_string()))?;
+        let source = self.db().collection::<bson::Document>(FLOW_EXECUTIONS)
+            .find_one(doc! { "_id": oid, "user_id": user_id })
+            .await?
+            .ok_or_else(|| AppError::NotFound("Execution not found".to_string()))?;
+
+        let flow_id = source.get_str("flow_id").unwrap_or_default().to_string();
+        let mut flow = self.load_flow(&flow_id, user_id).await?;
+        if !edits.step_parameters.is_empty() {
+            apply_parameter_overrides(&mut flow, &edits.step_parameters).map_err(AppError::BadRequest)?;
+            flow_validator::validate_flow_in_db(&self.db(), &flow).await?.into_result()?;
+        }
 
-        Ok(execution_id)
+        let from_step = from_step.unwrap_or(&flow.start_step_id).to_string();
+        if !flow.steps.iter().any(|s| s.id == from_step) {
+            return Err(AppError::BadRequest(format!("Step '{}' is not in flow '{}'", from_step, flow.name)));
+        }
+        if let Some(ref debug) = edits.debug {
+            check_breakpoints(&flow, debug)?;
+        }
+
+        let field = |key: &str| -> HashMap<String, Value> {
+            source.get_document(key).ok()
+                .and_then(|d| bson::from_document(d.clone()).ok())
+                .unwrap_or_default()
+        };
+        let (mut variables, input_data) = (field("variables"), field("input_data"));
+        let step_results: HashMap<String, FlowStepResult> = source.get_document("step_results").ok()
+            .and_then(|d| bson::from_document(d.clone()).ok())
+            .unwrap_or_default();
+
+        let restored = restore_state(&variables, &input_data, &step_results, &from_step);
+        variables.extend(edits.variables.clone());
+        let mut scope_variables = restored.variables;
+        scope_variables.extend(edits.variables);
+
+        let scope = ExecutionScope {
+            variables: scope_variables,
+            completed_steps: restored.completed_steps.clone(),
+            edge_states: flow.edge_metadata.clone(),
+            ..Default::default()
+        };
+        let start = ExecutionCheckpoint::capture(&from_step, restored.previous_step_id.as_deref(), &scope);
+
+        let rerun_id = {
+            let _queue = self.queue.dispatch_lock.lock().await;
+            let mut fields = self.queue_fields(&flow_id, &flow, edits.priority, &start).await?;
+            fields.extend(doc! {
+                "rerun_of_execution_id": execution_id,
+                "rerun_from_step_id": &from_step,
+                "parameter_overrides": bson::to_bson(&edits.step_parameters).unwrap_or(bson::Bson::Document(doc!{})),
+                "current_step_id": &from_step,
+                "completed_steps": &restored.completed_steps,
+                "step_results": bson::to_bson(&restored.step_results).unwrap_or(bson::Bson::Document(doc!{})),
+            });
+            if let Some(ref debug) = edits.debug {
+                fields.insert("debug", bson::to_bson(debug).map_err(|e| AppError::Internal(e.to_string()))?);
+            }
+            self.create_execution(&flow_id, &flow, &input_data, &variables, fields).await?
+        };
+
+        self.emit_event(FlowExecutionEvent {
+            id: None,
+            execution_id: rerun_id.clone(),
+            event_type: FlowEventType::ExecutionQueued,
+            step_id: Some(from_step.clone()),
+            message: format!("Flow '{}' rerun of execution {} queued at step '{}'", flow.name, execution_id, from_step),
+            data: HashMap::from([
+                ("rerun_of_execution_id".to_string(), json!(execution_id)),
+                ("restored_steps".to_string(), json!(restored.completed_steps)),
+                ("priority".to_string(), json!(execution_priority(&flow, edits.priority))),
+            ]),
+            timestamp: Utc::now(),
+        }).await;
+
+        Ok((self.admit(&rerun_id).await?, from_step))
     }
 
-    /// Cancel a running execution
+    /// Cancel a running execution along with any sub-flow executions it started.
+    /// A queued or waiting execution is cancelled at once and leaves the queue.
     pub async fn cancel_execution(&self, execution_id: &str, user_id: &str) -> Result<(), AppError> {
         let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
         let oid = ObjectId::parse_str(execution_id)
             .map_err(|_| AppError::BadRequest("Invalid execution ID".to_string()))?;
 
-        collection.update_one(
-            doc! { "_id": oid, "user_id": user_id },
+        let now = bson::DateTime::from_chrono(Utc::now());
+        let dequeued = collection.update_one(
+            doc! { "_id": oid, "user_id": user_id, "status": { "$in": ["queued", "waiting"] } },
+            doc! {
+                "$set": { "status": "cancelled", "is_cancellation_requested": true, "end_time": now, "updated_at": now },
+                "$unset": { "wake_at": "" },
+            },
+        ).await.map_err(|e| AppError::Database(e.to_string()))?.modified_count > 0;
+
+        collection.update_many(
+            doc! {
+                "user_id": user_id,
+                "$or": [{ "_id": oid }, { "ancestor_execution_ids": execution_id }],
+            },
             doc! { "$set": { "is_cancellation_requested": true, "updated_at": bson::DateTime::from_chrono(Utc::now()) } },
         ).await.map_err(|e| AppError::Database(e.to_string()))?;
+        // Interrupts the step in flight; sub-flows are cancelled with their parent
+        self.controls.cancel(execution_id);
 
         self.emit_event(FlowExecutionEvent {
             id: None,
             execution_id: execution_id.to_string(),
             event_type: FlowEventType::ExecutionCancelled,
             step_id: None,
-            message: "Execution cancellation requested".to_string(),
+            message: if dequeued { "Execution cancelled while queued or waiting".into() } else { "Execution cancellation requested".into() },
             data: HashMap::new(),
             timestamp: Utc::now(),
         }).await;
@@ -176,43 +658,208 @@ impl FlowService {
         Ok(())
     }
 
-    /// Submit approval decision for an execution
-    pub async fn submit_approval(
+    /// Wait up to `timeout` for an execution to finish, returning its record
+    /// as of then
+    pub async fn wait_for_execution(
         &self,
         execution_id: &str,
-        user_id: &str,
-        approved: bool,
-    ) -> Result<(), AppError> {
-        let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        timeout: std::time::Duration,
+    ) -> Result<bson::Document, AppError> {
         let oid = ObjectId::parse_str(execution_id)
             .map_err(|_| AppError::BadRequest("Invalid execution ID".to_string()))?;
+        let collection = self.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        // Subscribe before the first read so a finish in between isn't missed
+        let mut receiver = self.subscribe_to_execution(execution_id).await;
+        let deadline = tokio::time::Instant::now() + timeout;
 
-        collection.update_one(
-            doc! { "_id": oid, "user_id": user_id },
-            doc! { "$set": {
-                "approval_decision": approved,
-                "updated_at": bson::DateTime::from_chrono(Utc::now()),
-            }},
-        ).await.map_err(|e| AppError::Database(e.to_string()))?;
+        loop {
+            let exec_doc = collection.find_one(doc! { "_id": oid }).await?
+                .ok_or_else(|| AppError::NotFound("Execution not found".to_string()))?;
+            if matches!(exec_doc.get_str("status"), Ok("completed" | "failed" | "cancelled")) {
+                return Ok(exec_doc);
+            }
 
-        let event_type = if approved { FlowEventType::ApprovalGranted } else { FlowEventType::ApprovalRejected };
-        self.emit_event(FlowExecutionEvent {
-            id: None,
-            execution_id: execution_id.to_string(),
-            event_type,
-            step_id: None,
-            message: if approved { "Approval granted".into() } else { "Approval rejected".into() },
-            data: HashMap::new(),
-            timestamp: Utc::now(),
-        }).await;
+            // Re-read on terminal events, or when events were missed
+            loop {
+                match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                    Err(_) => return Ok(exec_doc),
+                    Ok(Ok(event)) if !matches!(
+                        event.event_type,
+                        FlowEventType::ExecutionCompleted | FlowEventType::ExecutionFailed | FlowEventType::ExecutionCancelled
+                    ) => continue,
+                    Ok(Err(broadcast::error::RecvError::Closed)) => {
+                        receiver = self.subscribe_to_execution(execution_id).await;
+                        break;
+                    }
+                    Ok(_) => break,
+                }
+            }
+        }
+    }
+}
 
-        Ok(())
+/// Variable scope and progress of a run through the flow graph.
+/// Parallel branches each get their own scope, forked from the parent.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ExecutionScope {
+    pub variables: HashMap<String, Value>,
+    pub completed_steps: Vec<String>,
+    /// Per-execution state of each edge (feedback loop iterations, history, scores)
+    pub edge_states: HashMap<String, EdgeMetadata>,
+    /// Branch this scope belongs to (its first step ID), `None` for the main flow
+    pub branch_id: Option<String>,
+    /// Step that ran before the first step of this run (set when resuming)
+    pub previous_step_id: Option<String>,
+    /// Item a map step is running this scope for
+    pub item_index: Option<usize>,
+}
+
+impl ExecutionScope {
+    pub fn new(variables: HashMap<String, Value>, edge_states: HashMap<String, EdgeMetadata>) -> Self {
+        Self { variables, edge_states, ..Default::default() }
+    }
+
+    /// Create an isolated copy of this scope for a parallel branch
+    pub fn fork(&self, branch_id: &str) -> Self {
+        Self {
+            variables: self.variables.clone(),
+            completed_steps: Vec::new(),
+            edge_states: self.edge_states.clone(),
+            branch_id: Some(branch_id.to_string()),
+            previous_step_id: None,
+            item_index: self.item_index,
+        }
+    }
+
+    /// Record a completed step. A step that runs again (loop, rework, error
+    /// branch) moves to the end, so the last entry is always the step that
+    /// completed most recently.
+    pub fn mark_completed(&mut self, step_id: &str) {
+        self.completed_steps.retain(|s| s != step_id);
+        self.completed_steps.push(step_id.to_string());
+    }
+
+    /// Attach branch information to event data when running inside a branch
+    pub(crate) fn tag_event_data(&self, data: &mut HashMap<String, Value>) {
+        if let Some(ref branch_id) = self.branch_id {
+            data.insert("branch_id".to_string(), json!(branch_id));
+        }
+        if let Some(item_index) = self.item_index {
+            data.insert("item_index".to_string(), json!(item_index));
+        }
+    }
+}
+
+/// What a flow does with its executions that were running when the backend
+/// stopped, read from the flow's `metadata.on_interrupt` (`"resume"` or `"fail"`)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum InterruptPolicy {
+    #[default]
+    Fail,
+    Resume,
+}
+
+impl InterruptPolicy {
+    pub fn from_flow(flow: &Flow) -> Self {
+        match flow.metadata.get("on_interrupt").and_then(|v| v.as_str()) {
+            Some("resume") => InterruptPolicy::Resume,
+            _ => InterruptPolicy::Fail,
+        }
+    }
+}
+
+/// Loop state saved on the execution document before each step of the main run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ExecutionCheckpoint {
+    /// Step to run next when resuming
+    pub step_id: String,
+    #[serde(default)]
+    pub previous_step_id: Option<String>,
+    #[serde(default)]
+    pub variables: HashMap<String, Value>,
+    #[serde(default)]
+    pub completed_steps: Vec<String>,
+    /// Feedback loop counters, history and scores
+    #[serde(default)]
+    pub edge_states: HashMap<String, EdgeMetadata>,
+    pub checkpointed_at: String,
+}
+
+impl ExecutionCheckpoint {
+    pub(crate) fn capture(step_id: &str, previous_step_id: Option<&str>, scope: &ExecutionScope) -> Self {
+        Self {
+            step_id: step_id.to_string(),
+            previous_step_id: previous_step_id.map(String::from),
+            variables: scope.variables.clone(),
+            completed_steps: scope.completed_steps.clone(),
+            edge_states: scope.edge_states.clone(),
+            checkpointed_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    fn into_scope(self) -> ExecutionScope {
+        ExecutionScope {
+            variables: self.variables,
+            completed_steps: self.completed_steps,
+            edge_states: self.edge_states,
+            branch_id: None,
+            previous_step_id: self.previous_step_id,
+            item_index: None,
+        }
     }
 }
 
+/// Reason a run through the flow graph stopped early
+#[derive(Debug)]
+pub(crate) enum StepRunError {
+    Cancelled { step_id: String },
+    /// Waiting on a delay step; the queue dispatcher starts it again later
+    Parked { step_id: String },
+    StepNotFound { step_id: String },
+    StepFailed { step_id: String, step_name: String, error: String },
+}
+
+impl std::fmt::Display for StepRunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepRunError::Cancelled { .. } => write!(f, "Execution cancelled"),
+            StepRunError::Parked { step_id } => write!(f, "Execution waiting at step '{}'", step_id),
+            StepRunError::StepNotFound { step_id } => write!(f, "Step '{}' not found", step_id),
+            StepRunError::StepFailed { step_name, error, .. } => write!(f, "Step '{}' failed: {}", step_name, error),
+        }
+    }
+}
+
+/// Final outcome of a step after any retries
+struct StepOutcome {
+    result: Result<Value, String>,
+    /// Failed attempts that preceded the final one
+    attempts: Vec<Value>,
+    /// Whether the final attempt hit the step's time limit
+    timed_out: bool,
+}
+
+/// Time limit for a single attempt of a step; a missing or non-positive
+/// `timeout_seconds` means no limit. Human input, approval and wait-for-signal
+/// steps apply their own, so they can withdraw the request when it expires;
+/// delay steps have none. Parallel, map and sub-flow steps have none either:
+/// the steps they run are limited one by one.
+fn step_time_limit(step: &FlowStep) -> Option<std::time::Duration> {
+    if matches!(
+        step.step_type,
+        FlowStepType::HumanInput | FlowStepType::Approval | FlowStepType::WaitForSignal | FlowStepType::Delay
+            | FlowStepType::Parallel | FlowStepType::Map | FlowStepType::SubFlow
+    ) {
+        return None;
+    }
+    step.timeout_seconds
+        .filter(|secs| *secs > 0)
+        .map(|secs| std::time::Duration::from_secs(secs as u64))
+}
+
 /// Internal flow executor that runs a flow to completion
-struct FlowExecutor {
-    service: FlowService,
+pub(crate) struct FlowExecutor {
+    pub(crate) service: FlowService,
     event_channels: Arc<tokio::sync::RwLock<HashMap<String, EventSender>>>,
 }
 
@@ -224,155 +871,111 @@ impl FlowExecutor {
         Self { service, event_channels }
     }
 
-    async fn emit(&self, event: FlowExecutionEvent) {
+    /// An executor sharing this one's database, MCP sessions and event channels
+    pub(crate) fn fork(&self) -> FlowExecutor {
+        FlowExecutor::new(self.service.clone(), Arc::clone(&self.event_channels))
+    }
+
+    /// An executor for tests whose database is unreachable: events and step
+    /// results aren't stored, so it can only run steps that don't read them
+    #[cfg(test)]
+    pub(crate) async fn offline(mcp_manager: Arc<McpSessionManager>) -> FlowExecutor {
+        let mongo_client = mongodb::Client::with_uri_str("mongodb://127.0.0.1:9/?serverSelectionTimeoutMS=20")
+            .await
+            .expect("valid MongoDB URI");
+        let cipher = FernetCipher::new("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=").expect("valid Fernet key");
+        let service = FlowService::new(mongo_client, cipher, mcp_manager, QueueLimits::default());
+        let event_channels = Arc::clone(&service.event_channels);
+        FlowExecutor::new(service, event_channels)
+    }
+
+    pub(crate) async fn emit(&self, event: FlowExecutionEvent) {
         // Store in DB
         let collection = self.service.db().collection::<bson::Document>(FLOW_EVENTS);
         if let Ok(doc) = bson::to_document(&event) {
             let _ = collection.insert_one(doc).await;
         }
-        // Broadcast
+        self.broadcast(event).await;
+    }
+
+    /// Send an event to live subscribers only, without storing it
+    pub(crate) async fn broadcast(&self, event: FlowExecutionEvent) {
         let channels = self.event_channels.read().await;
         if let Some(sender) = channels.get(&event.execution_id) {
             let _ = sender.send(event);
         }
     }
 
+    pub(crate) async fn subscribe(&self, execution_id: &str) -> EventReceiver {
+        let mut channels = self.event_channels.write().await;
+        let sender = channels.entry(execution_id.to_string())
+            .or_insert_with(|| broadcast::channel(256).0);
+        sender.subscribe()
+    }
+
     async fn run(
         &mut self,
         flow: Flow,
         execution_id: &str,
-        _input_data: HashMap<String, Value>,
-        mut variables: HashMap<String, Value>,
+        start_step_id: &str,
+        mut scope: ExecutionScope,
     ) {
-        let mut current_step_id = flow.start_step_id.clone();
-        let mut completed_steps: Vec<String> = Vec::new();
-        let exec_collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
-
-        // Build step lookup
-        let step_map: HashMap<String, &FlowStep> = flow.steps.iter()
-            .map(|s| (s.id.clone(), s))
-            .collect();
+        let outcome = self.run_steps(&flow, execution_id, start_step_id, None, &mut scope).await;
+        self.finish_execution(execution_id, &outcome, &scope).await;
+        self.service.controls.remove(execution_id);
+        // The slot is free for the next queued execution
+        self.service.queue.wake.notify_one();
+    }
 
-        loop {
-            // Check cancellation
-            if let Ok(oid) = ObjectId::parse_str(execution_id) {
-                if let Ok(Some(exec_doc)) = exec_collection.find_one(doc! { "_id": oid }).await {
-                    if exec_doc.get_bool("is_cancellation_requested").unwrap_or(false) {
-                        self.update_execution_status(execution_id, "cancelled").await;
-                        self.emit(FlowExecutionEvent {
-                            id: None,
-                            execution_id: execution_id.to_string(),
-                            event_type: FlowEventType::ExecutionCancelled,
-                            step_id: Some(current_step_id.clone()),
-                            message: "Execution cancelled by user".to_string(),
-                            data: HashMap::new(),
-                            timestamp: Utc::now(),
-                        }).await;
-                        return;
-                    }
-                }
+    /// Record how a run ended and emit the matching execution event
+    pub(crate) async fn finish_execution(
+        &self,
+        execution_id: &str,
+        outcome: &Result<(), StepRunError>,
+        scope: &ExecutionScope,
+    ) {
+        match outcome {
+            Ok(()) => {}
+            // Not finished: it continues once its delay is over
+            Err(StepRunError::Parked { .. }) => return,
+            Err(StepRunError::Cancelled { step_id }) => {
+                self.cancel_running_steps(execution_id, None).await;
+                self.update_execution_status(execution_id, "cancelled", None).await;
+                self.emit(FlowExecutionEvent {
+                    id: None,
+                    execution_id: execution_id.to_string(),
+                    event_type: FlowEventType::ExecutionCancelled,
+                    step_id: Some(step_id.clone()),
+                    message: "Execution cancelled by user".to_string(),
+                    data: HashMap::new(),
+                    timestamp: Utc::now(),
+                }).await;
+                return;
             }
-
-            let step = match step_map.get(&current_step_id) {
-                Some(s) => *s,
-                None => {
-                    tracing::error!(execution_id = %execution_id, step_id = %current_step_id, "Step not found");
-                    self.update_execution_status(execution_id, "failed").await;
-                    return;
-                }
-            };
-
-            // Emit step started
-            self.emit(FlowExecutionEvent {
-                id: None,
-                execution_id: execution_id.to_string(),
-                event_type: FlowEventType::StepStarted,
-                step_id: Some(step.id.clone()),
-                message: format!("Step '{}' started", step.name),
-                data: HashMap::from([("step_type".to_string(), json!(step.step_type))]),
-                timestamp: Utc::now(),
-            }).await;
-
-            // Update current step in execution
-            if let Ok(oid) = ObjectId::parse_str(execution_id) {
-                let _ = exec_collection.update_one(
-                    doc! { "_id": oid },
-                    doc! { "$set": { "current_step_id": &step.id, "updated_at": bson::DateTime::from_chrono(Utc::now()) } },
-                ).await;
+            Err(error @ StepRunError::StepNotFound { step_id }) => {
+                tracing::error!(execution_id = %execution_id, step_id = %step_id, "Step not found");
+                let error = error.to_string();
+                self.update_execution_status(execution_id, "failed", Some(&error)).await;
+                return;
             }
-
-            // Execute step based on type
-            let step_result = self.execute_step(step, execution_id, &variables).await;
-
-            match step_result {
-                Ok(result) => {
-                    // Store step result
-                    if let Some(output) = result.get("output") {
-                        variables.insert(format!("step_{}_output", step.id), output.clone());
-                    }
-
-                    completed_steps.push(step.id.clone());
-
-                    self.emit(FlowExecutionEvent {
-                        id: None,
-                        execution_id: execution_id.to_string(),
-                        event_type: FlowEventType::StepCompleted,
-                        step_id: Some(step.id.clone()),
-                        message: format!("Step '{}' completed", step.name),
-                        data: HashMap::from([("result".to_string(), result.clone())]),
-                        timestamp: Utc::now(),
-                    }).await;
-
-                    // Determine next step
-                    let next_step_id = result.get("next_step_id")
-                        .and_then(|v| v.as_str())
-                        .map(String::from);
-
-                    match next_step_id {
-                        Some(next) if !next.is_empty() => {
-                            current_step_id = next;
-                        }
-                        _ => {
-                            // Use first next_step from step definition, or finish
-                            if let Some(next) = step.next_steps.first() {
-                                current_step_id = next.clone();
-                            } else {
-                                // Flow completed
-                                break;
-                            }
-                        }
-                    }
-                }
-                Err(error) => {
-                    tracing::error!(execution_id = %execution_id, step_id = %step.id, error = %error, "Step failed");
-
-                    self.emit(FlowExecutionEvent {
-                        id: None,
-                        execution_id: execution_id.to_string(),
-                        event_type: FlowEventType::StepFailed,
-                        step_id: Some(step.id.clone()),
-                        message: format!("Step '{}' failed: {}", step.name, error),
-                        data: HashMap::new(),
-                        timestamp: Utc::now(),
-                    }).await;
-
-                    self.update_execution_status(execution_id, "failed").await;
-                    self.emit(FlowExecutionEvent {
-                        id: None,
-                        execution_id: execution_id.to_string(),
-                        event_type: FlowEventType::ExecutionFailed,
-                        step_id: Some(step.id.clone()),
-                        message: format!("Flow execution failed at step '{}'", step.name),
-                        data: HashMap::from([("error".to_string(), json!(error.to_string()))]),
-                        timestamp: Utc::now(),
-                    }).await;
-                    return;
-                }
+            Err(StepRunError::StepFailed { step_id, step_name, error }) => {
+                let execution_error = format!("Step '{}' failed: {}", step_name, error);
+                self.update_execution_status(execution_id, "failed", Some(&execution_error)).await;
+                self.emit(FlowExecutionEvent {
+                    id: None,
+                    execution_id: execution_id.to_string(),
+                    event_type: FlowEventType::ExecutionFailed,
+                    step_id: Some(step_id.clone()),
+                    message: format!("Flow execution failed at step '{}'", step_name),
+                    data: HashMap::from([("error".to_string(), json!(error))]),
+                    timestamp: Utc::now(),
+                }).await;
+                return;
             }
         }
 
         // Flow completed successfully
-        self.update_execution_status(execution_id, "completed").await;
+        self.update_execution_status(execution_id, "completed", None).await;
         self.emit(FlowExecutionEvent {
             id: None,
             execution_id: execution_id.to_string(),
@@ -380,7 +983,7 @@ impl FlowExecutor {
             step_id: None,
             message: "Flow execution completed successfully".to_string(),
             data: HashMap::from([
-                ("completed_steps".to_string(), json!(completed_steps)),
+                ("completed_steps".to_string(), json!(scope.completed_steps)),
             ]),
             timestamp: Utc::now(),
         }).await;
@@ -394,53 +997,460 @@ impl FlowExecutor {
         });
     }
 
+    /// Walk the flow graph from `start_step_id` until a step has no successor
+    /// or `stop_at` is reached. Used for the main flow and for parallel branches.
+    pub(crate) fn run_steps<'a>(
+        &'a self,
+        flow: &'a Flow,
+        execution_id: &'a str,
+        start_step_id: &str,
+        stop_at: Option<&'a str>,
+        scope: &'a mut ExecutionScope,
+    ) -> BoxFuture<'a, Result<(), StepRunError>> {
+        let mut current_step_id = start_step_id.to_string();
+        let mut previous_step_id: Option<String> = scope.previous_step_id.take();
+
+        Box::pin(async move {
+            let exec_collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+
+            // Build step lookup
+            let step_map: HashMap<&str, &FlowStep> = flow.steps.iter()
+                .map(|s| (s.id.as_str(), s))
+                .collect();
+
+            loop {
+                if stop_at == Some(current_step_id.as_str()) {
+                    return Ok(());
+                }
+
+                if self.is_cancelled(execution_id) {
+                    return Err(StepRunError::Cancelled { step_id: current_step_id });
+                }
+
+                let step = match step_map.get(current_step_id.as_str()) {
+                    Some(s) => *s,
+                    None => return Err(StepRunError::StepNotFound { step_id: current_step_id }),
+                };
+
+                // Stop first when a breakpoint or pause request says so
+                self.pause_point(step, execution_id, previous_step_id.as_deref(), scope).await?;
+
+                // A long delay parks the execution instead of holding its slot
+                if self.park_at_delay(step, execution_id, previous_step_id.as_deref(), scope).await {
+                    return Err(StepRunError::Parked { step_id: step.id.clone() });
+                }
+
+                // Emit step started
+                let mut started_data = HashMap::from([("step_type".to_string(), json!(step.step_type))]);
+                scope.tag_event_data(&mut started_data);
+                self.emit(FlowExecutionEvent {
+                    id: None,
+                    execution_id: execution_id.to_string(),
+                    event_type: FlowEventType::StepStarted,
+                    step_id: Some(step.id.clone()),
+                    message: format!("Step '{}' started", step.name),
+                    data: started_data,
+                    timestamp: Utc::now(),
+                }).await;
+
+                // Update current step in execution and mark the step running
+                let step_started = Utc::now();
+                if let Ok(oid) = ObjectId::parse_str(execution_id) {
+                    let running = FlowStepResult {
+                        step_id: step.id.clone(),
+                        status: FlowStepStatus::Running,
+                        result: None,
+                        error: None,
+                        start_time: Some(step_started),
+                        end_time: None,
+                        execution_time_ms: None,
+                        retry_attempt: 0,
+                        agent_output: None,
+                    };
+                    let mut set = doc! {
+                        "current_step_id": &step.id,
+                        format!("step_results.{}", step.id): bson::to_bson(&running).unwrap_or(bson::Bson::Null),
+                        "updated_at": bson::DateTime::from_chrono(Utc::now()),
+                    };
+                    // Checkpoint the main run so it can resume here after a restart
+                    if scope.branch_id.is_none() {
+                        let checkpoint = ExecutionCheckpoint::capture(&step.id, previous_step_id.as_deref(), scope);
+                        match bson::to_bson(&checkpoint) {
+                            Ok(checkpoint) => { set.insert("checkpoint", checkpoint); }
+                            Err(e) => tracing::warn!(execution_id = %execution_id, error = %e, "Failed to serialize checkpoint"),
+                        }
+                    }
+                    let _ = exec_collection.update_one(doc! { "_id": oid }, doc! { "$set": set }).await;
+                }
+
+                // Feedback loop entered through the edge from the previous step, if any
+                let feedback_edge_id = feedback_edge_into(step, previous_step_id.as_deref(), scope);
+                let task_context = feedback_task_context(step, feedback_edge_id.as_deref(), scope);
+
+                // Execute step based on type, retrying per the step's retry policy
+                let StepOutcome { result: step_result, attempts, timed_out } = self.execute_step_with_retry(
+                    flow, step, execution_id, scope, task_context.as_deref(),
+                ).await;
+
+                let step_ended = Utc::now();
+                let mut step_record = FlowStepResult {
+                    step_id: step.id.clone(),
+                    status: FlowStepStatus::Completed,
+                    result: None,
+                    error: None,
+                    start_time: Some(step_started),
+                    end_time: Some(step_ended),
+                    execution_time_ms: Some((step_ended - step_started).num_milliseconds()),
+                    retry_attempt: attempts.len() as i32,
+                    agent_output: None,
+                };
+
+                match step_result {
+                    Ok(mut result) => {
+                        if !attempts.is_empty() {
+                            if let Some(obj) = result.as_object_mut() {
+                                obj.insert("retry_attempt".to_string(), json!(attempts.len()));
+                                obj.insert("failed_attempts".to_string(), json!(attempts));
+                            }
+                        }
+
+                        step_record.agent_output = agent_output_text(&result);
+                        step_record.result = Some(match result {
+                            // The agent's full reply is kept in agent_output instead
+                            Value::Object(ref map) => Value::Object(
+                                map.iter().filter(|(k, _)| k.as_str() != "agent_result")
+                                    .map(|(k, v)| (k.clone(), v.clone())).collect(),
+                            ),
+                            ref other => other.clone(),
+                        });
+                        self.record_step_result(execution_id, &step_record).await;
+
+                        // Store step result
+                        if let Some(output) = result.get("output") {
+                            scope.variables.insert(format!("step_{}_output", step.id), output.clone());
+                        }
+                        // Fields a step extracted into named variables (webhook, structured output)
+                        if let Some(mapped) = result.get("mapped_variables").and_then(|v| v.as_object()) {
+                            for (name, value) in mapped {
+                                scope.variables.insert(name.clone(), value.clone());
+                            }
+                        }
+
+                        scope.mark_completed(&step.id);
+
+                        let mut completed_data = HashMap::from([("result".to_string(), result.clone())]);
+                        scope.tag_event_data(&mut completed_data);
+                        self.emit(FlowExecutionEvent {
+                            id: None,
+                            execution_id: execution_id.to_string(),
+                            event_type: FlowEventType::StepCompleted,
+                            step_id: Some(step.id.clone()),
+                            message: format!("Step '{}' completed", step.name),
+                            data: completed_data,
+                            timestamp: Utc::now(),
+                        }).await;
+
+                        previous_step_id = Some(step.id.clone());
+
+                        // Loop back to the source step while the feedback loop is still iterating
+                        if let Some(ref edge_id) = feedback_edge_id {
+                            if let Some(source_step_id) = self.advance_feedback_loop(execution_id, edge_id, step, &result, scope).await {
+                                current_step_id = source_step_id;
+                                continue;
+                            }
+                        }
+
+                        // Determine next step
+                        let next_step_id = result.get("next_step_id")
+                            .and_then(|v| v.as_str())
+                            .map(String::from);
+
+                        match next_step_id {
+                            Some(next) if !next.is_empty() => {
+                                current_step_id = next;
+                            }
+                            // A parallel step's next_steps are its branches, which already ran
+                            _ if step.step_type == FlowStepType::Parallel => break,
+                            _ => {
+                                // Use first next_step from step definition, or finish
+                                if let Some(next) = step.next_steps.first() {
+                                    current_step_id = next.clone();
+                                } else {
+                                    // Flow completed
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(error) => {
+                        tracing::error!(execution_id = %execution_id, step_id = %step.id, error = %error, "Step failed");
+
+                        let status = if timed_out { FlowStepStatus::TimedOut } else { FlowStepStatus::Failed };
+                        step_record.status = status.clone();
+                        step_record.error = Some(error.clone());
+                        if !attempts.is_empty() {
+                            step_record.result = Some(json!({"failed_attempts": attempts}));
+                        }
+                        self.record_step_result(execution_id, &step_record).await;
+
+                        let error_step_id = step.parameters.get("on_error_step_id")
+                            .and_then(|v| v.as_str())
+                            .filter(|id| !id.is_empty() && error != "Execution cancelled")
+                            .map(String::from);
+
+                        let mut failed_data = HashMap::from([
+                            ("status".to_string(), json!(status)),
+                            ("error".to_string(), json!(error)),
+                            ("error_step_id".to_string(), json!(error_step_id)),
+                            ("retry_attempt".to_string(), json!(attempts.len())),
+                            ("failed_attempts".to_string(), json!(attempts)),
+                        ]);
+                        scope.tag_event_data(&mut failed_data);
+                        self.emit(FlowExecutionEvent {
+                            id: None,
+                            execution_id: execution_id.to_string(),
+                            event_type: FlowEventType::StepFailed,
+                            step_id: Some(step.id.clone()),
+                            message: if timed_out {
+                                format!("Step '{}' timed out: {}", step.name, error)
+                            } else {
+                                format!("Step '{}' failed: {}", step.name, error)
+                            },
+                            data: failed_data,
+                            timestamp: Utc::now(),
+                        }).await;
+
+                        // A step stopped by a cancellation (approval wait, sub-flow) ends the run as cancelled
+                        if self.is_cancelled(execution_id) {
+                            return Err(StepRunError::Cancelled { step_id: step.id.clone() });
+                        }
+
+                        // Error branch: expose the failure to the handler step and carry on there
+                        if let Some(error_step_id) = error_step_id {
+                            scope.variables.insert(format!("step_{}_error", step.id), json!(error));
+                            scope.variables.insert(format!("step_{}_status", step.id), json!(status));
+                            scope.variables.insert("last_error".to_string(), json!(error));
+                            previous_step_id = Some(step.id.clone());
+                            current_step_id = error_step_id;
+                            continue;
+                        }
+
+                        return Err(StepRunError::StepFailed {
+                            step_id: step.id.clone(),
+                            step_name: step.name.clone(),
+                            error,
+                        });
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+
     async fn execute_step(
         &self,
+        flow: &Flow,
         step: &FlowStep,
         execution_id: &str,
-        variables: &HashMap<String, Value>,
+        scope: &mut ExecutionScope,
+        task_context: Option<&str>,
     ) -> Result<Value, String> {
+        let variables = &scope.variables;
         match step.step_type {
-            FlowStepType::Llm => self.execute_llm_step(step, execution_id, variables).await,
+            FlowStepType::Llm => self.execute_llm_step(flow, step, execution_id, variables, task_context).await,
             FlowStepType::Tool => self.execute_tool_step(step, variables).await,
             FlowStepType::Condition => self.execute_condition_step(step, variables).await,
-            FlowStepType::Approval => self.execute_approval_step(step, execution_id).await,
-            FlowStepType::Parallel => self.execute_parallel_step(step, execution_id, variables).await,
-            FlowStepType::FeedbackLoop => self.execute_feedback_loop_step(step, execution_id, variables).await,
-            _ => Ok(json!({"output": "Step type not yet implemented", "next_step_id": null})),
+            FlowStepType::Approval => self.execute_approval_step(step, execution_id, scope).await,
+            FlowStepType::Parallel => self.execute_parallel_step(flow, step, execution_id, scope).await,
+            FlowStepType::FeedbackLoop => self.execute_feedback_loop_step(flow, step, execution_id, variables, task_context).await,
+            FlowStepType::QualityCheck => self.execute_quality_check_step(flow, step, execution_id, scope).await,
+            FlowStepType::Webhook => self.execute_webhook_step(step, execution_id, scope).await,
+            FlowStepType::Switch => self.execute_switch_step(flow, step, execution_id, scope).await,
+            FlowStepType::SubFlow => self.execute_sub_flow_step(flow, step, execution_id, scope).await,
+            FlowStepType::Map => self.execute_map_step(flow, step, execution_id, scope).await,
+            FlowStepType::HumanInput => self.execute_human_input_step(step, execution_id, scope).await,
+            FlowStepType::Delay => self.execute_delay_step(step, execution_id, scope).await,
+            FlowStepType::WaitForSignal => self.execute_wait_for_signal_step(step, execution_id, scope).await,
+        }
+    }
+
+    /// Run a step, retrying failed attempts with backoff while the error is retryable.
+    /// Each attempt is bounded by the step's `timeout_seconds`; dropping the attempt
+    /// on expiry cancels any in-flight LLM or tool request.
+    async fn execute_step_with_retry(
+        &self,
+        flow: &Flow,
+        step: &FlowStep,
+        execution_id: &str,
+        scope: &mut ExecutionScope,
+        task_context: Option<&str>,
+    ) -> StepOutcome {
+        let policy = RetryPolicy::from_step(step);
+        let time_limit = step_time_limit(step);
+        let control = self.control(execution_id);
+        let mut attempts: Vec<Value> = Vec::new();
+
+        loop {
+            let attempt = attempts.len() as u32;
+            let run = async {
+                let run = self.execute_step(flow, step, execution_id, scope, task_context);
+                match time_limit {
+                    Some(limit) => match tokio::time::timeout(limit, run).await {
+                        Ok(result) => (result, false),
+                        Err(_) => (Err(format!("Step timed out after {}s", limit.as_secs())), true),
+                    },
+                    None => (run.await, false),
+                }
+            };
+            // Cancelling drops the attempt, stopping any LLM stream or tool call it was waiting on
+            let (result, timed_out) = tokio::select! {
+                biased;
+                _ = control.cancelled() => (Err("Execution cancelled".to_string()), false),
+                outcome = run => outcome,
+            };
+            if control.is_cancelled() {
+                return StepOutcome { result, attempts, timed_out };
+            }
+
+            let error = match result {
+                Err(error) if attempt < policy.max_retries && policy.is_retryable(&error) => error,
+                result => return StepOutcome { result, attempts, timed_out },
+            };
+
+            let delay = policy.delay_for(attempt + 1);
+            tracing::warn!(execution_id = %execution_id, step_id = %step.id, attempt, error = %error, "Step attempt failed, retrying");
+            attempts.push(json!({
+                "attempt": attempt,
+                "status": if timed_out { FlowStepStatus::TimedOut } else { FlowStepStatus::Failed },
+                "error": error,
+                "timestamp": Utc::now().to_rfc3339(),
+            }));
+
+            let mut retry_data = HashMap::from([
+                ("attempt".to_string(), json!(attempt + 1)),
+                ("max_retries".to_string(), json!(policy.max_retries)),
+                ("error".to_string(), json!(error)),
+                ("delay_ms".to_string(), json!(delay.as_millis() as u64)),
+            ]);
+            scope.tag_event_data(&mut retry_data);
+            self.emit(FlowExecutionEvent {
+                id: None,
+                execution_id: execution_id.to_string(),
+                event_type: FlowEventType::StepRetrying,
+                step_id: Some(step.id.clone()),
+                message: format!(
+                    "Step '{}' failed, retry {}/{} in {:.1}s: {}",
+                    step.name, attempt + 1, policy.max_retries, delay.as_secs_f64(), error
+                ),
+                data: retry_data,
+                timestamp: Utc::now(),
+            }).await;
+
+            tokio::select! {
+                _ = control.cancelled() => {
+                    return StepOutcome { result: Err("Execution cancelled".to_string()), attempts, timed_out: false };
+                }
+                _ = tokio::time::sleep(delay) => {}
+            }
         }
     }
 
-    async fn execute_llm_step(
+    /// Control handle of an execution running here
+    pub(crate) fn control(&self, execution_id: &str) -> ExecutionControl {
+        self.service.controls.register(execution_id, None)
+    }
+
+    fn is_cancelled(&self, execution_id: &str) -> bool {
+        self.control(execution_id).is_cancelled()
+    }
+
+    pub(crate) async fn execute_llm_step(
         &self,
+        flow: &Flow,
         step: &FlowStep,
         execution_id: &str,
         variables: &HashMap<String, Value>,
+        task_context: Option<&str>,
     ) -> Result<Value, String> {
         let agent_id = step.agent_id.as_deref()
             .ok_or("LLM step requires agent_id")?;
 
-        let task = resolve_variables(
+        let mut task = resolve_variables(
             step.parameters.get("task").and_then(|v| v.as_str())
                 .or(step.description.as_deref())
                 .unwrap_or(&step.name),
             variables,
         );
+        if let Some(context) = task_context {
+            task = format!("{}\n\n{}", task, context);
+        }
 
-        let params = json!({"task": task});
+        let structured = StructuredOutput::from_parameters(&step.parameters)?;
+        let params = match structured {
+            Some(ref structured) => json!({"task": structured.instructions(&task), "output_schema": structured.schema}),
+            None => json!({"task": task}),
+        };
 
-        let event_exec_id = execution_id.to_string();
-        let event_step_id = step.id.clone();
-        let channels = Arc::clone(&self.event_channels);
-        let db_client = self.service.mongo_client.clone();
+        let mut client = self.agent_client(flow, step);
+        let event_callback = self.agent_event_callback(execution_id, &step.id);
 
-        let mut client = AgentApiClient::new(
+        let result = client.execute_agent_step(
+            agent_id,
+            &step.name,
+            &params,
+            step.agent_overrides.as_ref(),
+            None,
+            Some(event_callback),
+        ).await;
+
+        if result.get("success").and_then(|v| v.as_bool()) == Some(true) {
+            let mut step_result = json!({
+                "output": result.get("content").cloned().unwrap_or(json!("")),
+                "agent_result": result,
+            });
+            if let Some(ref structured) = structured {
+                self.apply_structured_output(step, execution_id, structured, &task, &mut client, &mut step_result).await?;
+            }
+            if let Some(applied) = step.agent_overrides.as_ref().and_then(applied_overrides) {
+                let agent_result = &step_result["agent_result"];
+                let (llm_id, model_used) = (agent_result["llm_id"].clone(), agent_result["model_used"].clone());
+                step_result["agent_overrides"] = applied;
+                step_result["llm_id"] = llm_id;
+                step_result["model_used"] = model_used;
+            }
+            Ok(step_result)
+        } else {
+            Err(result.get("error").and_then(|v| v.as_str()).unwrap_or("LLM step failed").to_string())
+        }
+    }
+
+    /// Agent client for an attempt of `step` in `flow`, limited to the flow
+    /// owner's LLMs. Tool calls get whatever is left of the step's time limit.
+    pub(crate) fn agent_client(&self, flow: &Flow, step: &FlowStep) -> AgentApiClient {
+        let client = AgentApiClient::new(
             self.service.mongo_client.clone(),
             self.service.cipher.clone(),
             Arc::clone(&self.service.mcp_manager),
-        );
+        ).for_owner(&flow.user_id);
+        match step_time_limit(step) {
+            Some(limit) => client.with_deadline(tokio::time::Instant::now() + limit),
+            None => client,
+        }
+    }
+
+    /// Callback that forwards agent progress (LLM responses, tool calls) as flow events
+    pub(crate) fn agent_event_callback(
+        &self,
+        execution_id: &str,
+        step_id: &str,
+    ) -> Box<dyn FnMut(&str, Value) + Send> {
+        let event_exec_id = execution_id.to_string();
+        let event_step_id = step_id.to_string();
+        let channels = Arc::clone(&self.event_channels);
+        let db_client = self.service.mongo_client.clone();
 
-        let event_callback: Box<dyn FnMut(&str, Value) + Send> = Box::new(move |event_type: &str, data: Value| {
+        Box::new(move |event_type: &str, data: Value| {
             let event = FlowExecutionEvent {
                 id: None,
                 execution_id: event_exec_id.clone(),
@@ -480,24 +1490,7 @@ impl FlowExecutor {
                     let _ = sender.send(event_clone);
                 }
             });
-        });
-
-        let result = client.execute_agent_step(
-            agent_id,
-            &step.name,
-            &params,
-            None,
-            Some(event_callback),
-        ).await;
-
-        if result.get("success").and_then(|v| v.as_bool()) == Some(true) {
-            Ok(json!({
-                "output": result.get("content").cloned().unwrap_or(json!("")),
-                "agent_result": result,
-            }))
-        } else {
-            Err(result.get("error").and_then(|v| v.as_str()).unwrap_or("LLM step failed").to_string())
-        }
+        })
     }
 
     async fn execute_tool_step(
@@ -517,7 +1510,9 @@ impl FlowExecutor {
             .and_then(|v| v.as_object())
             .cloned();
 
-        let result = self.service.mcp_manager.call_tool(connection_id, tool_name, arguments).await
+        // The step's own time limit applies instead of the default MCP call timeout
+        let timeout = step_time_limit(step).unwrap_or(DEFAULT_TOOL_CALL_TIMEOUT);
+        let result = self.service.mcp_manager.call_tool_with_timeout(connection_id, tool_name, arguments, timeout).await
             .map_err(|e| e.to_string())?;
 
         Ok(json!({"output": result}))
@@ -528,15 +1523,11 @@ impl FlowExecutor {
         step: &FlowStep,
         variables: &HashMap<String, Value>,
     ) -> Result<Value, String> {
-        let condition = step.condition.as_deref().unwrap_or("true");
-        let resolved = resolve_variables(condition, variables);
-
-        // Simple condition evaluation
-        let condition_met = match resolved.trim().to_lowercase().as_str() {
-            "true" | "1" | "yes" => true,
-            "false" | "0" | "no" | "" => false,
-            _ => !resolved.trim().is_empty(),
-        };
+        let condition = step.condition.as_deref()
+            .filter(|c| !c.trim().is_empty())
+            .unwrap_or("true");
+        let condition_met = expression::evaluate_condition(condition, variables)
+            .map_err(|e| format!("Invalid condition '{}': {}", condition, e))?;
 
         let next_step = if condition_met {
             step.next_steps.first().cloned()
@@ -551,104 +1542,128 @@ impl FlowExecutor {
         }))
     }
 
-    async fn execute_approval_step(
-        &self,
-        step: &FlowStep,
-        execution_id: &str,
-    ) -> Result<Value, String> {
-        // Set pending approval
-        if let Ok(oid) = ObjectId::parse_str(execution_id) {
-            let exec_collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
-            let _ = exec_collection.update_one(
-                doc! { "_id": oid },
-                doc! { "$set": {
-                    "pending_approval_step_id": &step.id,
-                    "approval_decision": bson::Bson::Null,
-                    "updated_at": bson::DateTime::from_chrono(Utc::now()),
-                }},
-            ).await;
+    /// Write a step's result to `step_results` and track it in `completed_steps` / `failed_steps`
+    async fn record_step_result(&self, execution_id: &str, step_result: &FlowStepResult) {
+        let Ok(oid) = ObjectId::parse_str(execution_id) else { return };
+        let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let result_bson = match bson::to_bson(step_result) {
+            Ok(b) => b,
+            Err(e) => {
+                tracing::error!(execution_id = %execution_id, step_id = %step_result.step_id, error = %e, "Failed to serialize step result");
+                return;
+            }
+        };
+
+        let mut update = doc! {
+            "$set": {
+                format!("step_results.{}", step_result.step_id): result_bson,
+                "updated_at": bson::DateTime::from_chrono(Utc::now()),
+            },
+        };
+        if step_result.status == FlowStepStatus::Completed {
+            update.insert("$addToSet", doc! { "completed_steps": &step_result.step_id });
+            update.insert("$pull", doc! { "failed_steps": &step_result.step_id });
+        } else {
+            update.insert("$addToSet", doc! { "failed_steps": &step_result.step_id });
         }
 
-        self.emit(FlowExecutionEvent {
-            id: None,
-            execution_id: execution_id.to_string(),
-            event_type: FlowEventType::ApprovalRequired,
-            step_id: Some(step.id.clone()),
-            message: format!("Approval required for step '{}'", step.name),
-            data: HashMap::new(),
-            timestamp: Utc::now(),
-        }).await;
+        let _ = collection.update_one(doc! { "_id": oid }, update).await;
+    }
 
-        // Poll for approval decision
-        let timeout = std::time::Duration::from_secs(
-            step.timeout_seconds.unwrap_or(300) as u64,
-        );
-        let start = std::time::Instant::now();
+    /// Mark step results still `running` as cancelled once the runs they belong
+    /// to were dropped. `steps` limits this to those step IDs.
+    pub(crate) async fn cancel_running_steps(&self, execution_id: &str, steps: Option<&HashSet<String>>) {
+        let Ok(oid) = ObjectId::parse_str(execution_id) else { return };
+        let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+        let Ok(Some(exec_doc)) = collection.find_one(doc! { "_id": oid }).projection(doc! { "step_results": 1 }).await else {
+            return;
+        };
+        let Ok(step_results) = exec_doc.get_document("step_results") else { return };
 
-        loop {
-            if start.elapsed() > timeout {
-                return Err("Approval timed out".to_string());
+        let now = Utc::now();
+        for (step_id, record) in step_results {
+            if steps.is_some_and(|steps| !steps.contains(step_id)) {
+                continue;
             }
+            let Ok(mut record) = bson::from_bson::<FlowStepResult>(record.clone()) else { continue };
+            if record.status != FlowStepStatus::Running {
+                continue;
+            }
+            record.status = FlowStepStatus::Cancelled;
+            record.error = Some("Cancelled before it finished".to_string());
+            record.end_time = Some(now);
+            record.execution_time_ms = record.start_time.map(|start| (now - start).num_milliseconds());
+            let Ok(record_bson) = bson::to_bson(&record) else { continue };
+            // A step that finished meanwhile keeps its own result
+            let _ = collection.update_one(
+                doc! { "_id": oid, format!("step_results.{}.status", step_id): "running" },
+                doc! { "$set": { format!("step_results.{}", step_id): record_bson } },
+            ).await;
+        }
+    }
 
-            if let Ok(oid) = ObjectId::parse_str(execution_id) {
-                let exec_collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
-                if let Ok(Some(doc)) = exec_collection.find_one(doc! { "_id": oid }).await {
-                    if let Ok(decision) = doc.get_bool("approval_decision") {
-                        return if decision {
-                            Ok(json!({"output": "approved", "approved": true}))
-                        } else {
-                            Err("Approval rejected".to_string())
-                        };
-                    }
-                    if doc.get_bool("is_cancellation_requested").unwrap_or(false) {
-                        return Err("Execution cancelled".to_string());
-                    }
+    /// Set the final status, error and total duration of the execution
+    async fn update_execution_status(&self, execution_id: &str, status: &str, error: Option<&str>) {
+        if let Ok(oid) = ObjectId::parse_str(execution_id) {
+            let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
+            let now = Utc::now();
+            let mut set = doc! {
+                "status": status,
+                "end_time": bson::DateTime::from_chrono(now),
+                "updated_at": bson::DateTime::from_chrono(now),
+            };
+            if let Some(error) = error {
+                set.insert("error", error);
+            }
+            if let Ok(Some(exec_doc)) = collection.find_one(doc! { "_id": oid }).await {
+                if let Ok(start) = exec_doc.get_datetime("start_time") {
+                    set.insert("execution_time_ms", (now - start.to_chrono()).num_milliseconds());
                 }
             }
-
-            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+            // Requests of steps interrupted by the end of the run are withdrawn
+            let unset = doc! { "pending_approvals": "", "pending_approval_step_id": "", "pending_inputs": "", "paused": "", "signal_waits": "" };
+            let _ = collection.update_one(doc! { "_id": oid }, doc! { "$set": set, "$unset": unset }).await;
         }
     }
+}
 
-    async fn execute_parallel_step(
-        &self,
-        step: &FlowStep,
-        _execution_id: &str,
-        _variables: &HashMap<String, Value>,
-    ) -> Result<Value, String> {
-        // Execute all next_steps in parallel
-        // For now, just proceed to first next step
-        Ok(json!({
-            "output": "Parallel execution completed",
-            "next_step_id": step.next_steps.first(),
-        }))
-    }
+fn flow_from_document(flow_doc: bson::Document) -> Result<Flow, AppError> {
+    let flow_id = flow_doc.get_object_id("_id").map(|oid| oid.to_hex())
+        .or_else(|_| flow_doc.get_str("_id").map(String::from))
+        .ok();
 
-    async fn execute_feedback_loop_step(
-        &self,
-        step: &FlowStep,
-        execution_id: &str,
-        variables: &HashMap<String, Value>,
-    ) -> Result<Value, String> {
-        // Feedback loops are handled via edge metadata
-        // For now, execute as LLM step
-        self.execute_llm_step(step, execution_id, variables).await
-    }
+    // Remove date fields before deserializing — bson::DateTime can't map to chrono directly
+    let mut flow_doc_clean = flow_doc;
+    flow_doc_clean.remove("created_at");
+    flow_doc_clean.remove("updated_at");
 
-    async fn update_execution_status(&self, execution_id: &str, status: &str) {
-        if let Ok(oid) = ObjectId::parse_str(execution_id) {
-            let collection = self.service.db().collection::<bson::Document>(FLOW_EXECUTIONS);
-            let now = bson::DateTime::from_chrono(Utc::now());
-            let _ = collection.update_one(
-                doc! { "_id": oid },
-                doc! { "$set": { "status": status, "end_time": now, "updated_at": now } },
-            ).await;
-        }
+    let mut flow: Flow = bson::from_document(flow_doc_clean)
+        .map_err(|e| AppError::Internal(format!("Failed to deserialize flow: {}", e)))?;
+    if flow.id.is_none() {
+        flow.id = flow_id;
     }
+    Ok(flow)
+}
+
+/// The agent's reply text from an LLM-backed step result, if any
+fn agent_output_text(result: &Value) -> Option<String> {
+    result.get("agent_result")
+        .and_then(|r| r.get("content"))
+        .and_then(|c| c.as_str())
+        .map(String::from)
+}
+
+/// The override fields a step actually set, or `None` when it sets nothing
+fn applied_overrides(overrides: &AgentOverride) -> Option<Value> {
+    let Ok(Value::Object(fields)) = serde_json::to_value(overrides) else {
+        return None;
+    };
+    let applied: serde_json::Map<String, Value> = fields.into_iter().filter(|(_, v)| !v.is_null()).collect();
+    (!applied.is_empty()).then_some(Value::Object(applied))
 }
 
 /// Resolve ${var} and {{var}} placeholders in a string
-fn resolve_variables(template: &str, variables: &HashMap<String, Value>) -> String {
+pub(crate) fn resolve_variables(template: &str, variables: &HashMap<String, Value>) -> String {
     let mut result = template.to_string();
     for (key, value) in variables {
         let val_str = match value {
@@ -660,3 +1675,103 @@ fn resolve_variables(template: &str, variables: &HashMap<String, Value>) -> Stri
     }
     result
 }
+
+/// Resolve placeholders in every string of a JSON value. A string that is exactly
+/// one placeholder (e.g. `"{{issue}}"`) is replaced by the variable's JSON value.
+pub(crate) fn resolve_value_variables(value: &Value, variables: &HashMap<String, Value>) -> Value {
+    match value {
+        Value::String(s) => {
+            let trimmed = s.trim();
+            let whole_var = trimmed.strip_prefix("{{").and_then(|v| v.strip_suffix("}}"))
+                .or_else(|| trimmed.strip_prefix("${").and_then(|v| v.strip_suffix('}')));
+            match whole_var.and_then(|name| variables.get(name.trim())) {
+                Some(var) => var.clone(),
+                None => Value::String(resolve_variables(s, variables)),
+            }
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|v| resolve_value_variables(v, variables)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter().map(|(k, v)| (k.clone(), resolve_value_variables(v, variables))).collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_roundtrip_restores_scope() {
+        let mut edge: EdgeMetadata = serde_json::from_value(json!({
+            "edge_id": "dev-qa", "source_step_id": "dev", "target_step_id": "qa", "is_feedback_loop": true,
+        })).unwrap();
+        edge.current_iteration = 2;
+        edge.quality_scores = vec![0.4, 0.7];
+
+        let mut scope = ExecutionScope::new(
+            HashMap::from([("step_dev_output".to_string(), json!({"files": ["main.rs"]}))]),
+            HashMap::from([("dev-qa".to_string(), edge)]),
+        );
+        scope.completed_steps = vec!["plan".to_string(), "dev".to_string()];
+
+        let checkpoint = ExecutionCheckpoint::capture("qa", Some("dev"), &scope);
+        let stored = bson::to_bson(&checkpoint).unwrap();
+        let restored: ExecutionCheckpoint = bson::from_bson(stored).unwrap();
+        assert_eq!(restored.step_id, "qa");
+
+        let resumed = restored.into_scope();
+        assert_eq!(resumed.previous_step_id.as_deref(), Some("dev"));
+        assert_eq!(resumed.completed_steps, vec!["plan", "dev"]);
+        assert_eq!(resumed.variables["step_dev_output"], json!({"files": ["main.rs"]}));
+        assert_eq!(resumed.edge_states["dev-qa"].current_iteration, 2);
+        assert_eq!(resumed.edge_states["dev-qa"].quality_scores, vec![0.4, 0.7]);
+    }
+
+    #[test]
+    fn test_steps_that_run_again_move_to_the_end() {
+        let mut scope = ExecutionScope::default();
+        for step_id in ["plan", "dev", "qa", "dev", "qa"] {
+            scope.mark_completed(step_id);
+        }
+        assert_eq!(scope.completed_steps, vec!["plan", "dev", "qa"]);
+        scope.mark_completed("dev");
+        assert_eq!(scope.completed_steps.last().map(String::as_str), Some("dev"));
+    }
+
+    #[test]
+    fn test_container_steps_have_no_time_limit() {
+        let step = |step_type: &str| -> FlowStep {
+            serde_json::from_value(json!({"id": "s", "name": "S", "type": step_type})).unwrap()
+        };
+        assert_eq!(step_time_limit(&step("llm")), Some(std::time::Duration::from_secs(300)));
+        for step_type in ["parallel", "map", "sub_flow", "approval", "delay"] {
+            assert_eq!(step_time_limit(&step(step_type)), None, "{}", step_type);
+        }
+    }
+
+    #[test]
+    fn test_interrupt_policy_from_flow_metadata() {
+        let mut flow: Flow = serde_json::from_value(json!({
+            "user_id": "u1", "name": "Flow", "steps": [], "start_step_id": "a",
+        })).unwrap();
+        assert_eq!(InterruptPolicy::from_flow(&flow), InterruptPolicy::Fail);
+
+        flow.metadata.insert("on_interrupt".to_string(), json!("resume"));
+        assert_eq!(InterruptPolicy::from_flow(&flow), InterruptPolicy::Resume);
+    }
+
+    #[test]
+    fn test_applied_overrides_only_lists_set_fields() {
+        let overrides: AgentOverride = serde_json::from_value(json!({
+            "llm_id": "cheap-model", "mcp_connections": ["fs"], "temperature": 0.1,
+        })).unwrap();
+        assert_eq!(
+            applied_overrides(&overrides),
+            Some(json!({"llm_id": "cheap-model", "mcp_connections": ["fs"], "temperature": 0.1})),
+        );
+
+        let empty: AgentOverride = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(applied_overrides(&empty), None);
+    }
+}
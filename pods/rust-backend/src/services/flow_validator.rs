@@ -0,0 +1,908 @@
+//! Static validation of flow definitions.
+//!
+//! Runs when a flow is created or updated (errors block the save) and backs
+//! `POST /api/flows/validate`, which returns the full report for drafts.
+//!
+//! Errors are problems that would make an execution fail or hang: dangling step
+//! references, LLM steps without an agent, agents or LLMs that no longer exist,
+//! invalid expressions or input schemas, and cycles with no way out. Warnings
+//! flag likely mistakes: unreachable steps, undefined `{{vars}}`, cycles not
+//! marked as feedback edges and parameters the step type doesn't read.
+
+use bson::{oid::ObjectId, Bson};
+use mongodb::bson::doc;
+use mongodb::Database;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::db::collections::{AGENTS, FLOWS, LLMS};
+use crate::error::AppError;
+use crate::models::flow::{Flow, FlowStep, FlowStepType};
+use crate::services::flow_executor::expression;
+use crate::services::flow_executor::step_handlers::approval_step::ApprovalConfig;
+use crate::services::flow_executor::step_handlers::delay_step;
+use crate::services::flow_executor::step_handlers::human_input_step::HumanInputConfig;
+use crate::services::flow_executor::step_handlers::map_step::MapConfig;
+use crate::services::flow_executor::step_handlers::structured_output::StructuredOutput;
+use crate::services::flow_executor::step_handlers::sub_flow_step::{FlowRef, SubFlowConfig, MAX_SUB_FLOW_DEPTH};
+use crate::services::flow_executor::step_handlers::switch_step::{SwitchConfig, SwitchMode};
+use crate::services::flow_executor::step_handlers::wait_for_signal_step::SignalWaitConfig;
+use crate::services::flow_inputs;
+
+/// Parameters every step type understands (retry policy and error branch)
+const COMMON_PARAMETERS: &[&str] = &[
+    "retry_backoff", "retry_delay_seconds", "retry_max_delay_seconds", "retry_jitter",
+    "retryable_errors", "fatal_errors", "on_error_step_id",
+];
+
+/// Parameters holding the ID of another step in the flow
+const STEP_REFERENCE_PARAMETERS: &[&str] = &[
+    "join_step_id", "target_step_id", "on_error_step_id", "default_step_id", "body_step_id",
+    "timeout_step_id", "rework_step_id",
+];
+
+/// Parameters read by each step type, on top of `COMMON_PARAMETERS`
+fn known_parameters(step_type: &FlowStepType) -> &'static [&'static str] {
+    match step_type {
+        FlowStepType::Llm => &["task", "output_schema", "output_mapping", "output_retries"],
+        FlowStepType::Tool => &["connection_id", "tool_name", "arguments"],
+        FlowStepType::Condition => &[],
+        FlowStepType::Parallel => &["join_step_id", "join_mode", "join_count"],
+        FlowStepType::Webhook => &[
+            "url", "method", "headers", "body", "expected_status", "timeout_seconds", "response_mapping",
+        ],
+        FlowStepType::FeedbackLoop => &[
+            "task", "max_iterations", "quality_threshold", "convergence_criteria",
+            "output_schema", "output_mapping", "output_retries",
+        ],
+        FlowStepType::QualityCheck => &["rubric", "target_step_id", "judge_agent_id", "judge_llm_id", "threshold"],
+        FlowStepType::Approval => &["message", "approvers", "required_approvals", "target_step_id", "rework_step_id"],
+        FlowStepType::Switch => &[
+            "cases", "default_step_id", "mode", "value", "input", "instructions", "router_llm_id",
+        ],
+        FlowStepType::SubFlow => &["flow_id", "flow_name", "inputs", "output_mapping"],
+        FlowStepType::Map => &["items", "body_step_id", "item_variable", "index_variable", "concurrency", "on_error"],
+        FlowStepType::HumanInput => &["prompt", "fields"],
+        FlowStepType::Delay => &["seconds", "until"],
+        FlowStepType::WaitForSignal => &["signal", "correlation_id", "timeout_step_id"],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    pub severity: IssueSeverity,
+    /// Stable machine-readable identifier, e.g. `dangling_next_step`
+    pub code: String,
+    pub message: String,
+    pub step_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    fn push(&mut self, severity: IssueSeverity, code: &str, step_id: Option<&str>, message: String) {
+        let issue = ValidationIssue {
+            severity,
+            code: code.to_string(),
+            message,
+            step_id: step_id.map(String::from),
+        };
+        match severity {
+            IssueSeverity::Error => self.errors.push(issue),
+            IssueSeverity::Warning => self.warnings.push(issue),
+        }
+    }
+
+    fn error(&mut self, code: &str, step_id: Option<&str>, message: String) {
+        self.push(IssueSeverity::Error, code, step_id, message);
+    }
+
+    fn warning(&mut self, code: &str, step_id: Option<&str>, message: String) {
+        self.push(IssueSeverity::Warning, code, step_id, message);
+    }
+
+    /// Turn a report with errors into the error returned when saving a flow
+    pub fn into_result(self) -> Result<Self, AppError> {
+        if self.errors.is_empty() {
+            return Ok(self);
+        }
+        let messages: Vec<&str> = self.errors.iter().map(|e| e.message.as_str()).collect();
+        Err(AppError::BadRequest(format!("Flow validation failed: {}", messages.join("; "))))
+    }
+}
+
+/// Agents, LLMs and sub-flows referenced by a flow that exist in the database
+/// and that the flow's owner may use
+#[derive(Debug, Clone, Default)]
+pub struct KnownResources {
+    /// Agent ID to the LLM it is configured with: the owner's agents and default ones
+    pub agents: HashMap<String, Option<String>>,
+    /// MCP connections each of those agents is set up with
+    pub agent_connections: HashMap<String, HashSet<String>>,
+    /// The owner's LLMs
+    pub llms: HashSet<String>,
+    /// LLMs of default agents, usable only through those agents
+    pub shared_llms: HashSet<String>,
+    /// IDs and names of the owner's flows that sub-flow steps refer to
+    pub flows: HashSet<String>,
+}
+
+impl KnownResources {
+    /// Look up every agent and LLM the flow references, plus the LLMs of those agents
+    pub async fn load(db: &Database, flow: &Flow) -> Result<Self, AppError> {
+        let mut agent_ids = HashSet::new();
+        let mut llm_ids = HashSet::new();
+        let mut flow_ids = HashSet::new();
+        let mut flow_names = Vec::new();
+        for step in &flow.steps {
+            flow_ids.extend(string_param(step, "flow_id").map(String::from));
+            flow_names.extend(string_param(step, "flow_name").map(String::from));
+            agent_ids.extend(step.agent_id.clone());
+            agent_ids.extend(string_param(step, "judge_agent_id").map(String::from));
+            llm_ids.extend(step.agent_overrides.as_ref().and_then(|o| o.llm_id.clone()));
+            for key in ["judge_llm_id", "router_llm_id"] {
+                llm_ids.extend(string_param(step, key).map(String::from));
+            }
+        }
+
+        let mut resources = Self::default();
+        let mut shared_llm_ids = HashSet::new();
+        if !agent_ids.is_empty() {
+            let mut cursor = db.collection::<bson::Document>(AGENTS)
+                .find(doc! {
+                    "_id": { "$in": id_filter(&agent_ids) },
+                    "$or": [{ "user_id": &flow.user_id }, { "is_default": true }],
+                })
+                .await?;
+            while cursor.advance().await? {
+                let agent = cursor.deserialize_current()?;
+                let Some(id) = document_id(&agent) else { continue };
+                let llm_id = agent.get_str("llm_id").ok().filter(|s| !s.is_empty()).map(String::from);
+                if agent.get_str("user_id").ok() == Some(flow.user_id.as_str()) {
+                    llm_ids.extend(llm_id.clone());
+                } else {
+                    shared_llm_ids.extend(llm_id.clone());
+                }
+                let connections = agent.get_array("mcp_connections").ok()
+                    .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+                    .unwrap_or_default();
+                resources.agent_connections.insert(id.clone(), connections);
+                resources.agents.insert(id, llm_id);
+            }
+        }
+
+        if !llm_ids.is_empty() {
+            let mut cursor = db.collection::<bson::Document>(LLMS)
+                .find(doc! { "_id": { "$in": id_filter(&llm_ids) }, "user_id": &flow.user_id })
+                .await?;
+            while cursor.advance().await? {
+                let llm = cursor.deserialize_current()?;
+                resources.llms.extend(document_id(&llm));
+            }
+        }
+
+        if !shared_llm_ids.is_empty() {
+            let mut cursor = db.collection::<bson::Document>(LLMS)
+                .find(doc! { "_id": { "$in": id_filter(&shared_llm_ids) } })
+                .await?;
+            while cursor.advance().await? {
+                let llm = cursor.deserialize_current()?;
+                resources.shared_llms.extend(document_id(&llm));
+            }
+        }
+
+        if !flow_ids.is_empty() || !flow_names.is_empty() {
+            let mut cursor = db.collection::<bson::Document>(FLOWS)
+                .find(doc! {
+                    "user_id": &flow.user_id,
+                    "$or": [{ "_id": { "$in": id_filter(&flow_ids) } }, { "name": { "$in": &flow_names } }],
+                })
+                .await?;
+            while cursor.advance().await? {
+                let found = cursor.deserialize_current()?;
+                resources.flows.extend(document_id(&found));
+                resources.flows.extend(found.get_str("name").ok().map(String::from));
+            }
+        }
+
+        Ok(resources)
+    }
+}
+
+/// IDs are stored as ObjectIds, but older documents may use plain strings
+fn id_filter(ids: &HashSet<String>) -> Vec<Bson> {
+    ids.iter()
+        .map(|id| match ObjectId::parse_str(id) {
+            Ok(oid) => Bson::ObjectId(oid),
+            Err(_) => Bson::String(id.clone()),
+        })
+        .collect()
+}
+
+fn document_id(doc: &bson::Document) -> Option<String> {
+    match doc.get("_id")? {
+        Bson::ObjectId(oid) => Some(oid.to_hex()),
+        Bson::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn string_param<'a>(step: &'a FlowStep, key: &str) -> Option<&'a str> {
+    step.parameters.get(key).and_then(|v| v.as_str()).filter(|s| !s.trim().is_empty())
+}
+
+/// Validate a flow against the agents and LLMs that exist
+pub async fn validate_flow_in_db(db: &Database, flow: &Flow) -> Result<ValidationReport, AppError> {
+    let resources = KnownResources::load(db, flow).await?;
+    Ok(validate_flow(flow, &resources))
+}
+
+pub fn validate_flow(flow: &Flow, resources: &KnownResources) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    if flow.steps.is_empty() {
+        report.error("empty_flow", None, "Flow has no steps".to_string());
+        report.valid = false;
+        return report;
+    }
+
+    let mut step_ids = HashSet::new();
+    for step in &flow.steps {
+        if step.id.trim().is_empty() {
+            report.error("missing_step_id", None, format!("Step '{}' has no ID", step.name));
+        } else if !step_ids.insert(step.id.as_str()) {
+            report.error("duplicate_step_id", Some(&step.id), format!("Duplicate step ID '{}'", step.id));
+        }
+    }
+
+    if !step_ids.contains(flow.start_step_id.as_str()) {
+        report.error("missing_start_step", None, format!(
+            "Start step '{}' does not exist", flow.start_step_id
+        ));
+    }
+
+    for step in &flow.steps {
+        check_references(step, &step_ids, &mut report);
+        check_step_config(step, &mut report);
+        check_resources(step, resources, &mut report);
+        check_sub_flow(flow, step, resources, &mut report);
+        check_parameters(step, &mut report);
+    }
+
+    for error in flow_inputs::validate_schema(&flow.inputs) {
+        report.error("invalid_input", None, format!("Input '{}': {}", error.field, error.message));
+    }
+
+    for (edge_id, edge) in &flow.edge_metadata {
+        for endpoint in [&edge.source_step_id, &edge.target_step_id] {
+            if !step_ids.contains(endpoint.as_str()) {
+                report.error("dangling_edge", None, format!(
+                    "Edge '{}' refers to step '{}' which does not exist", edge_id, endpoint
+                ));
+            }
+        }
+    }
+
+    let graph = FlowGraph::new(flow);
+    check_reachability(flow, &graph, &mut report);
+    check_cycles(flow, &graph, &mut report);
+    check_variables(flow, &mut report);
+
+    report.valid = report.errors.is_empty();
+    report
+}
+
+/// Every `next_steps` entry and step-ID parameter must name a step in the flow
+fn check_references(step: &FlowStep, step_ids: &HashSet<&str>, report: &mut ValidationReport) {
+    for next in &step.next_steps {
+        if !step_ids.contains(next.as_str()) {
+            report.error("dangling_next_step", Some(&step.id), format!(
+                "Step '{}' continues to '{}' which does not exist", step.id, next
+            ));
+        }
+    }
+
+    for (key, target) in parameter_targets(step) {
+        if !step_ids.contains(target.as_str()) {
+            report.error("dangling_step_reference", Some(&step.id), format!(
+                "Step '{}' parameter '{}' refers to step '{}' which does not exist", step.id, key, target
+            ));
+        }
+    }
+}
+
+/// Step IDs named in a step's parameters, with the parameter they came from
+fn parameter_targets(step: &FlowStep) -> Vec<(String, String)> {
+    let mut targets: Vec<(String, String)> = STEP_REFERENCE_PARAMETERS.iter()
+        .filter_map(|key| string_param(step, key).map(|id| (key.to_string(), id.to_string())))
+        .collect();
+
+    if step.step_type == FlowStepType::Switch {
+        if let Some(cases) = step.parameters.get("cases").and_then(|v| v.as_array()) {
+            for case in cases {
+                if let Some(target) = case.get("target_step_id").or_else(|| case.get("target")).and_then(|v| v.as_str()) {
+                    targets.push(("cases".to_string(), target.to_string()));
+                }
+            }
+        }
+    }
+
+    targets
+}
+
+/// Required parameters and step-type specific configuration
+fn check_step_config(step: &FlowStep, report: &mut ValidationReport) {
+    let id = step.id.as_str();
+    let missing = |report: &mut ValidationReport, what: &str| {
+        report.error("missing_parameter", Some(id), format!("Step '{}' requires {}", id, what));
+    };
+
+    match step.step_type {
+        FlowStepType::Llm | FlowStepType::FeedbackLoop => {
+            if step.agent_id.as_deref().is_none_or(|a| a.trim().is_empty()) {
+                report.error("missing_agent", Some(id), format!("Step '{}' has no agent_id", id));
+            }
+            if let Err(e) = StructuredOutput::from_parameters(&step.parameters) {
+                report.error("invalid_output_schema", Some(id), format!("Step '{}': {}", id, e));
+            }
+        }
+        FlowStepType::Tool => {
+            for key in ["connection_id", "tool_name"] {
+                if string_param(step, key).is_none() {
+                    missing(report, &format!("a {} parameter", key));
+                }
+            }
+        }
+        FlowStepType::Webhook => {
+            if string_param(step, "url").is_none() {
+                missing(report, "a url parameter");
+            }
+        }
+        FlowStepType::QualityCheck => {
+            if string_param(step, "rubric").is_none() {
+                missing(report, "a rubric parameter");
+            }
+            if step.agent_id.is_none()
+                && string_param(step, "judge_agent_id").is_none()
+                && string_param(step, "judge_llm_id").is_none()
+            {
+                missing(report, "judge_agent_id, judge_llm_id or agent_id");
+            }
+        }
+        FlowStepType::Condition => {
+            if let Some(condition) = step.condition.as_deref().filter(|c| !c.trim().is_empty()) {
+                if let Err(e) = expression::validate(condition) {
+                    report.error("invalid_condition", Some(id), format!("Invalid condition in step '{}': {}", id, e));
+                }
+            }
+        }
+        FlowStepType::Switch => match SwitchConfig::from_parameters(&step.parameters) {
+            Ok(config) => {
+                if config.mode == SwitchMode::Llm
+                    && step.agent_id.is_none()
+                    && string_param(step, "router_llm_id").is_none()
+                {
+                    missing(report, "agent_id or router_llm_id for LLM routing");
+                }
+            }
+            Err(e) => report.error("invalid_switch", Some(id), format!("Invalid switch step '{}': {}", id, e)),
+        },
+        FlowStepType::SubFlow => {
+            if let Err(e) = SubFlowConfig::from_parameters(&step.parameters) {
+                report.error("invalid_sub_flow", Some(id), format!("Invalid sub-flow step '{}': {}", id, e));
+            }
+        }
+        FlowStepType::Map => {
+            if let Err(e) = MapConfig::from_parameters(&step.parameters) {
+                report.error("invalid_map", Some(id), format!("Invalid map step '{}': {}", id, e));
+            }
+        }
+        FlowStepType::HumanInput => {
+            if let Err(e) = HumanInputConfig::validate_parameters(&step.parameters) {
+                report.error("invalid_human_input", Some(id), format!("Invalid human input step '{}': {}", id, e));
+            }
+        }
+        FlowStepType::Approval => {
+            if let Err(e) = ApprovalConfig::from_parameters(&step.parameters) {
+                report.error("invalid_approval", Some(id), format!("Invalid approval step '{}': {}", id, e));
+            }
+        }
+        FlowStepType::Delay => {
+            if let Err(e) = delay_step::validate_parameters(&step.parameters) {
+                report.error("invalid_delay", Some(id), format!("Invalid delay step '{}': {}", id, e));
+            }
+        }
+        FlowStepType::WaitForSignal => {
+            if let Err(e) = SignalWaitConfig::validate_parameters(&step.parameters) {
+                report.error("invalid_signal_wait", Some(id), format!("Invalid wait-for-signal step '{}': {}", id, e));
+            }
+        }
+        FlowStepType::Parallel => {}
+    }
+
+    if step.retry_count < 0 {
+        report.error("invalid_retry_count", Some(id), format!("Step '{}' has a negative retry_count", id));
+    }
+    if step.timeout_seconds.is_some_and(|t| t <= 0) {
+        report.warning("no_timeout", Some(id), format!(
+            "Step '{}' has timeout_seconds <= 0 and will run without a time limit", id
+        ));
+    }
+}
+
+/// Agents and LLMs the step uses must still exist and be the owner's to use;
+/// overridden MCP connections must be ones the agent already has
+fn check_resources(step: &FlowStep, resources: &KnownResources, report: &mut ValidationReport) {
+    let id = step.id.as_str();
+
+    let agents = step.agent_id.as_deref().filter(|a| !a.trim().is_empty()).into_iter()
+        .chain(string_param(step, "judge_agent_id"));
+    for agent_id in agents {
+        match resources.agents.get(agent_id) {
+            None => report.error("unknown_agent", Some(id), format!(
+                "Step '{}' uses agent '{}' which does not exist or is not accessible", id, agent_id
+            )),
+            Some(None) => report.error("agent_without_llm", Some(id), format!(
+                "Agent '{}' used by step '{}' has no LLM configured", agent_id, id
+            )),
+            Some(Some(llm_id)) if !resources.llms.contains(llm_id) && !resources.shared_llms.contains(llm_id) => report.error("agent_llm_missing", Some(id), format!(
+                "Agent '{}' used by step '{}' uses LLM '{}' which no longer exists", agent_id, id, llm_id
+            )),
+            Some(Some(_)) => {}
+        }
+    }
+
+    let overridden_connections = step.agent_overrides.as_ref().and_then(|o| o.mcp_connections.as_ref());
+    if let (Some(agent_id), Some(connections)) = (step.agent_id.as_deref(), overridden_connections) {
+        if let Some(own) = resources.agent_connections.get(agent_id) {
+            for connection in connections.iter().filter(|c| !own.contains(*c)) {
+                report.error("unknown_mcp_connection", Some(id), format!(
+                    "Step '{}' agent_overrides.mcp_connections names '{}', which agent '{}' is not connected to",
+                    id, connection, agent_id
+                ));
+            }
+        }
+    }
+
+    let llms = step.agent_overrides.as_ref().and_then(|o| o.llm_id.as_deref()).map(|l| ("agent_overrides.llm_id", l))
+        .into_iter()
+        .chain(string_param(step, "judge_llm_id").map(|l| ("judge_llm_id", l)))
+        .chain(string_param(step, "router_llm_id").map(|l| ("router_llm_id", l)));
+    for (field, llm_id) in llms {
+        if !resources.llms.contains(llm_id) {
+            report.error("unknown_llm", Some(id), format!(
+                "Step '{}' {} refers to LLM '{}' which does not exist or is not accessible", id, field, llm_id
+            ));
+        }
+    }
+}
+
+/// The flow a sub-flow step runs must exist; calling the flow itself is allowed
+/// but only stops at the nesting limit
+fn check_sub_flow(flow: &Flow, step: &FlowStep, resources: &KnownResources, report: &mut ValidationReport) {
+    if step.step_type != FlowStepType::SubFlow {
+        return;
+    }
+    let Ok(config) = SubFlowConfig::from_parameters(&step.parameters) else { return };
+
+    let is_self = match &config.flow {
+        FlowRef::Id(id) => flow.id.as_ref() == Some(id),
+        FlowRef::Name(name) => *name == flow.name,
+    };
+    let target = match &config.flow {
+        FlowRef::Id(id) | FlowRef::Name(id) => id,
+    };
+    if is_self {
+        report.warning("recursive_sub_flow", Some(&step.id), format!(
+            "Step '{}' runs this flow again; recursion stops at {} levels of nesting", step.id, MAX_SUB_FLOW_DEPTH
+        ));
+    } else if !resources.flows.contains(target) {
+        report.error("unknown_flow", Some(&step.id), format!(
+            "Step '{}' runs {} which does not exist", step.id, config.flow
+        ));
+    }
+}
+
+fn check_parameters(step: &FlowStep, report: &mut ValidationReport) {
+    let known = known_parameters(&step.step_type);
+    let mut unknown: Vec<&String> = step.parameters.keys()
+        .filter(|k| !known.contains(&k.as_str()) && !COMMON_PARAMETERS.contains(&k.as_str()))
+        .collect();
+    unknown.sort();
+    for key in unknown {
+        report.warning("unknown_parameter", Some(&step.id), format!(
+            "Step '{}' has parameter '{}' which {:?} steps don't use", step.id, key, step.step_type
+        ));
+    }
+}
+
+/// Successor lists for every step, following `next_steps`, step-ID parameters
+/// and feedback edges
+struct FlowGraph<'a> {
+    successors: HashMap<&'a str, Vec<String>>,
+    feedback_edges: HashSet<(String, String)>,
+}
+
+impl<'a> FlowGraph<'a> {
+    fn new(flow: &'a Flow) -> Self {
+        let successors = flow.steps.iter()
+            .map(|step| {
+                let mut next = step.next_steps.clone();
+                next.extend(parameter_targets(step).into_iter()
+                    .filter(|(key, _)| key != "target_step_id")
+                    .map(|(_, target)| target));
+                (step.id.as_str(), next)
+            })
+            .collect();
+
+        let feedback_edges = flow.edge_metadata.values()
+            .filter(|e| e.is_feedback_loop)
+            .flat_map(|e| [
+                (e.source_step_id.clone(), e.target_step_id.clone()),
+                (e.target_step_id.clone(), e.source_step_id.clone()),
+            ])
+            .collect();
+
+        Self { successors, feedback_edges }
+    }
+
+    fn successors(&self, step_id: &str) -> &[String] {
+        self.successors.get(step_id).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+fn check_reachability(flow: &Flow, graph: &FlowGraph, report: &mut ValidationReport) {
+    let mut reachable = HashSet::new();
+    let mut queue = VecDeque::from([flow.start_step_id.as_str()]);
+    while let Some(step_id) = queue.pop_front() {
+        if !reachable.insert(step_id) {
+            continue;
+        }
+        queue.extend(graph.successors(step_id).iter().map(String::as_str));
+        queue.extend(graph.feedback_edges.iter()
+            .filter(|(from, _)| from == step_id)
+            .map(|(_, to)| to.as_str()));
+    }
+
+    for step in &flow.steps {
+        if !reachable.contains(step.id.as_str()) {
+            report.warning("unreachable_step", Some(&step.id), format!(
+                "Step '{}' is not reachable from the start step", step.id
+            ));
+        }
+    }
+}
+
+/// Cycles outside feedback edges loop forever unless a step on them can branch out
+fn check_cycles(flow: &Flow, graph: &FlowGraph, report: &mut ValidationReport) {
+    let steps: HashMap<&str, &FlowStep> = flow.steps.iter().map(|s| (s.id.as_str(), s)).collect();
+    let mut visited = HashSet::new();
+    let mut reported = HashSet::new();
+
+    for step in &flow.steps {
+        let mut path = Vec::new();
+        find_cycles(step.id.as_str(), graph, &steps, &mut visited, &mut path, &mut |cycle| {
+            let mut key = cycle.to_vec();
+            key.sort();
+            if !reported.insert(key) {
+                return;
+            }
+            let description = cycle.iter().chain(cycle.first()).cloned().collect::<Vec<_>>().join(" -> ");
+            let can_exit = cycle.iter().any(|id| steps.get(id).is_some_and(|s| {
+                matches!(s.step_type, FlowStepType::Condition | FlowStepType::Switch | FlowStepType::QualityCheck)
+                    || s.parameters.contains_key("on_error_step_id")
+            }));
+            if can_exit {
+                report.warning("unmarked_cycle", Some(cycle[0]), format!(
+                    "Cycle {} is not marked as a feedback loop", description
+                ));
+            } else {
+                report.error("infinite_cycle", Some(cycle[0]), format!(
+                    "Cycle {} has no condition, switch or quality check to exit it", description
+                ));
+            }
+        });
+    }
+}
+
+fn find_cycles<'a>(
+    step_id: &'a str,
+    graph: &'a FlowGraph,
+    steps: &HashMap<&'a str, &'a FlowStep>,
+    visited: &mut HashSet<&'a str>,
+    path: &mut Vec<&'a str>,
+    on_cycle: &mut dyn FnMut(&[&'a str]),
+) {
+    if let Some(pos) = path.iter().position(|id| *id == step_id) {
+        on_cycle(&path[pos..]);
+        return;
+    }
+    if !visited.insert(step_id) || !steps.contains_key(step_id) {
+        return;
+    }
+
+    path.push(step_id);
+    for next in graph.successors(step_id) {
+        if graph.feedback_edges.contains(&(step_id.to_string(), next.clone())) {
+            continue;
+        }
+        // Borrow the ID from `steps` so it lives as long as the flow
+        if let Some(next) = steps.get_key_value(next.as_str()).map(|(k, _)| *k) {
+            find_cycles(next, graph, steps, visited, path, on_cycle);
+        }
+    }
+    path.pop();
+}
+
+/// Placeholders that name no flow variable or step output
+fn check_variables(flow: &Flow, report: &mut ValidationReport) {
+    let placeholder = Regex::new(r"\{\{\s*([^{}]+?)\s*\}\}|\$\{\s*([^{}]+?)\s*\}").expect("valid regex");
+
+    let mut defined: HashSet<String> = flow.variables.keys().chain(flow.inputs.keys()).cloned().collect();
+    defined.extend(["feedback", "feedback_iteration", "last_error"].map(String::from));
+    for step in &flow.steps {
+        for suffix in ["output", "error", "status", "feedback"] {
+            defined.insert(format!("step_{}_{}", step.id, suffix));
+        }
+        for key in ["response_mapping", "output_mapping"] {
+            if let Some(mapping) = step.parameters.get(key).and_then(|v| v.as_object()) {
+                defined.extend(mapping.keys().cloned());
+            }
+        }
+        if step.step_type == FlowStepType::Map {
+            if let Ok(map) = MapConfig::from_parameters(&step.parameters) {
+                defined.extend([map.item_variable, map.index_variable]);
+            }
+        }
+    }
+
+    for step in &flow.steps {
+        let mut texts: Vec<&str> = step.description.iter().map(String::as_str).collect();
+        texts.extend(step.agent_overrides.as_ref().and_then(|o| o.system_prompt.as_deref()));
+        for value in step.parameters.values() {
+            collect_strings(value, &mut texts);
+        }
+
+        let mut undefined = Vec::new();
+        for text in texts {
+            for captures in placeholder.captures_iter(text) {
+                let name = captures.get(1).or_else(|| captures.get(2)).map(|m| m.as_str()).unwrap_or_default();
+                let root = name.split('.').next().unwrap_or(name);
+                let is_defined = defined.contains(name)
+                    || defined.contains(root)
+                    || root == "steps"
+                    || root == "variables";
+                if !is_defined && !undefined.contains(&name) {
+                    undefined.push(name);
+                }
+            }
+        }
+
+        for name in undefined {
+            report.warning("undefined_variable", Some(&step.id), format!(
+                "Step '{}' uses '{{{{{}}}}}' which is never defined", step.id, name
+            ));
+        }
+    }
+}
+
+fn collect_strings<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+    match value {
+        Value::String(s) => out.push(s),
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, out)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn flow(value: Value) -> Flow {
+        let mut value = value;
+        value["user_id"] = json!("u1");
+        value["name"] = json!("Test flow");
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn resources() -> KnownResources {
+        KnownResources {
+            agents: HashMap::from([
+                ("writer".to_string(), Some("gpt".to_string())),
+                ("orphan".to_string(), Some("deleted-llm".to_string())),
+                ("house-agent".to_string(), Some("house-llm".to_string())),
+            ]),
+            agent_connections: HashMap::from([
+                ("writer".to_string(), HashSet::from(["search".to_string()])),
+            ]),
+            llms: HashSet::from(["gpt".to_string()]),
+            shared_llms: HashSet::from(["house-llm".to_string()]),
+            flows: HashSet::from(["shared-qa".to_string()]),
+        }
+    }
+
+    fn codes(issues: &[ValidationIssue]) -> Vec<&str> {
+        issues.iter().map(|i| i.code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_valid_flow_has_no_issues() {
+        let flow = flow(json!({
+            "start_step_id": "draft",
+            "variables": {"topic": "rust"},
+            "steps": [
+                {"id": "draft", "name": "Draft", "agent_id": "writer", "parameters": {"task": "Write about {{topic}}"}, "next_steps": ["check"]},
+                {"id": "check", "name": "Check", "type": "condition", "condition": "len(step_draft_output) > 10", "next_steps": ["publish", "draft"]},
+                {"id": "publish", "name": "Publish", "type": "webhook", "parameters": {"url": "http://cms.local", "body": {"text": "{{step_draft_output}}"}}},
+            ],
+        }));
+
+        let report = validate_flow(&flow, &resources());
+        assert!(report.valid, "{:?}", report.errors);
+        assert_eq!(codes(&report.warnings), vec!["unmarked_cycle"]);
+    }
+
+    #[test]
+    fn test_reports_broken_references_and_resources() {
+        let flow = flow(json!({
+            "start_step_id": "missing",
+            "steps": [
+                {"id": "a", "name": "A", "next_steps": ["ghost"], "parameters": {"on_error_step_id": "nowhere"}},
+                {"id": "b", "name": "B", "agent_id": "orphan", "agent_overrides": {"llm_id": "nope"}},
+                {"id": "c", "name": "C", "agent_id": "unknown"},
+                {"id": "c", "name": "C again", "type": "tool", "parameters": {"tool_name": "search"}},
+            ],
+        }));
+
+        let report = validate_flow(&flow, &resources());
+        assert!(!report.valid);
+        let errors = codes(&report.errors);
+        for code in [
+            "duplicate_step_id", "missing_start_step", "dangling_next_step", "dangling_step_reference",
+            "missing_agent", "agent_llm_missing", "unknown_llm", "unknown_agent", "missing_parameter",
+        ] {
+            assert!(errors.contains(&code), "missing {} in {:?}", code, errors);
+        }
+        assert!(report.into_result().is_err());
+    }
+
+    #[test]
+    fn test_rework_step_must_exist() {
+        let flow = flow(json!({
+            "start_step_id": "a",
+            "steps": [
+                {"id": "a", "name": "A", "agent_id": "writer", "next_steps": ["review"]},
+                {"id": "review", "name": "Review", "type": "approval", "parameters": {"rework_step_id": "redraft"}},
+            ],
+        }));
+
+        let report = validate_flow(&flow, &resources());
+        assert_eq!(codes(&report.errors), vec!["dangling_step_reference"]);
+        assert!(report.errors[0].message.contains("rework_step_id"));
+    }
+
+    #[test]
+    fn test_default_agents_llms_are_not_the_owners() {
+        let flow = flow(json!({
+            "start_step_id": "a",
+            "steps": [
+                {"id": "a", "name": "A", "agent_id": "house-agent", "next_steps": ["b"]},
+                {"id": "b", "name": "B", "agent_id": "writer", "agent_overrides": {"llm_id": "house-llm"}},
+            ],
+        }));
+
+        // The default agent may run on its LLM, but the flow can't name that LLM itself
+        let report = validate_flow(&flow, &resources());
+        assert_eq!(codes(&report.errors), vec!["unknown_llm"]);
+        assert_eq!(report.errors[0].step_id.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_overrides_only_narrow_the_agents_connections() {
+        let flow = flow(json!({
+            "start_step_id": "a",
+            "steps": [
+                {"id": "a", "name": "A", "agent_id": "writer", "agent_overrides": {"mcp_connections": ["search"]}, "next_steps": ["b"]},
+                {"id": "b", "name": "B", "agent_id": "writer", "agent_overrides": {"mcp_connections": ["search", "someone-elses"]}},
+            ],
+        }));
+
+        let report = validate_flow(&flow, &resources());
+        assert_eq!(codes(&report.errors), vec!["unknown_mcp_connection"]);
+        assert_eq!(report.errors[0].step_id.as_deref(), Some("b"));
+        assert!(report.errors[0].message.contains("someone-elses"));
+    }
+
+    #[test]
+    fn test_warnings_for_unreachable_steps_variables_and_parameters() {
+        let flow = flow(json!({
+            "start_step_id": "a",
+            "steps": [
+                {"id": "a", "name": "A", "agent_id": "writer", "parameters": {"task": "Use {{undefined_thing}} and {{steps.a.output}}", "temprature": 0.2}},
+                {"id": "island", "name": "Island", "agent_id": "writer"},
+            ],
+        }));
+
+        let report = validate_flow(&flow, &resources());
+        assert!(report.valid);
+        let warnings = codes(&report.warnings);
+        assert_eq!(warnings, vec!["unknown_parameter", "unreachable_step", "undefined_variable"]);
+        assert!(report.warnings[2].message.contains("undefined_thing"));
+    }
+
+    #[test]
+    fn test_cycles() {
+        let endless = flow(json!({
+            "start_step_id": "a",
+            "steps": [
+                {"id": "a", "name": "A", "agent_id": "writer", "next_steps": ["b"]},
+                {"id": "b", "name": "B", "agent_id": "writer", "next_steps": ["a"]},
+            ],
+        }));
+        let report = validate_flow(&endless, &resources());
+        assert_eq!(codes(&report.errors), vec!["infinite_cycle"]);
+
+        let mut marked = endless.clone();
+        marked.edge_metadata = serde_json::from_value(json!({
+            "e1": {"edge_id": "e1", "source_step_id": "a", "target_step_id": "b", "is_feedback_loop": true},
+        })).unwrap();
+        let report = validate_flow(&marked, &resources());
+        assert!(report.valid, "{:?}", report.errors);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+    }
+
+    #[test]
+    fn test_sub_flow_steps() {
+        let flow = flow(json!({
+            "start_step_id": "analyze",
+            "steps": [
+                {"id": "analyze", "name": "Analyze", "type": "sub_flow", "next_steps": ["again"],
+                 "parameters": {"flow_name": "shared-qa", "output_mapping": {"verdict": "step_qa_output.verdict"}}},
+                {"id": "again", "name": "Again", "type": "sub_flow", "next_steps": ["ghost"],
+                 "parameters": {"flow_name": "Test flow"}},
+                {"id": "ghost", "name": "Ghost", "type": "sub_flow", "next_steps": ["broken"],
+                 "parameters": {"flow_id": "deleted-flow"}},
+                {"id": "broken", "name": "Broken", "type": "sub_flow", "next_steps": ["use"], "parameters": {"inputs": "x"}},
+                {"id": "use", "name": "Use", "agent_id": "writer", "parameters": {"task": "Verdict: {{verdict}}"}},
+            ],
+        }));
+
+        let report = validate_flow(&flow, &resources());
+        assert_eq!(codes(&report.errors), vec!["unknown_flow", "invalid_sub_flow"]);
+        assert_eq!(codes(&report.warnings), vec!["recursive_sub_flow"]);
+    }
+
+    #[test]
+    fn test_map_step() {
+        let flow = flow(json!({
+            "start_step_id": "list",
+            "steps": [
+                {"id": "list", "name": "List", "agent_id": "writer", "next_steps": ["each"]},
+                {"id": "each", "name": "Each", "type": "map", "next_steps": ["summary"],
+                 "parameters": {"items": "step_list_output.files", "body_step_id": "review", "item_variable": "file"}},
+                {"id": "review", "name": "Review", "agent_id": "writer", "parameters": {"task": "Review {{file}} ({{item_index}})"}},
+                {"id": "summary", "name": "Summary", "type": "map", "parameters": {"items": "x", "body_step_id": "gone", "concurrency": 0}},
+            ],
+        }));
+
+        let report = validate_flow(&flow, &resources());
+        assert_eq!(codes(&report.errors), vec!["dangling_step_reference", "invalid_map"]);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+    }
+}
@@ -16,6 +16,10 @@ use crate::utils::command_utils::{normalize_mcp_command, fix_stdio_args};
 
 type McpClient = rmcp::service::RunningService<rmcp::RoleClient, ()>;
 
+/// Handle for sending requests over a session; clones share the connection,
+/// so calls through them can run at the same time
+pub type McpPeer = rmcp::service::Peer<rmcp::RoleClient>;
+
 /// Individual MCP session wrapping an rmcp client
 #[allow(dead_code)]
 pub struct McpSession {
@@ -164,16 +168,27 @@ impl McpSession {
         Ok(())
     }
 
-    /// List tools from the MCP server
-    pub async fn list_tools(&mut self, use_cache: bool) -> Result<Vec<MCPToolInfo>, AppError> {
-        if use_cache && !self.tools_cache.is_empty() {
-            self.last_used = Utc::now();
-            return Ok(self.tools_cache.clone());
-        }
+    /// Connect over an in-memory stream to a fake server
+    #[cfg(test)]
+    pub(crate) async fn connect_io<T>(&mut self, io: T) -> Result<(), AppError>
+    where
+        T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+    {
+        let service = ().serve(io).await
+            .map_err(|e| AppError::Internal(format!("MCP handshake failed: {}", e)))?;
+        self.client = Some(service);
+        self.connected = true;
+        self.last_used = Utc::now();
+        Ok(())
+    }
 
-        let client = self.client.as_ref()
-            .ok_or_else(|| AppError::Internal("MCP session not connected".to_string()))?;
+    /// Request handle of a connected session
+    pub fn peer(&self) -> Option<McpPeer> {
+        self.client.as_ref().filter(|_| self.connected).map(|client| client.peer().clone())
+    }
 
+    /// List tools from the MCP server
+    pub async fn list_tools(client: &McpPeer) -> Result<Vec<MCPToolInfo>, AppError> {
         let result = tokio::time::timeout(
             std::time::Duration::from_secs(30),
             client.list_tools(Default::default()),
@@ -210,24 +225,20 @@ impl McpSession {
             }
         }).collect();
 
-        self.tools_cache = tools.clone();
-        self.last_used = Utc::now();
         Ok(tools)
     }
 
-    /// Execute a tool on the MCP server
+    /// Execute a tool on the MCP server, giving up after `timeout`
     pub async fn call_tool(
-        &mut self,
+        client: &McpPeer,
         tool_name: &str,
         arguments: Option<serde_json::Map<String, Value>>,
+        timeout: std::time::Duration,
     ) -> Result<Value, AppError> {
-        let client = self.client.as_ref()
-            .ok_or_else(|| AppError::Internal("MCP session not connected".to_string()))?;
-
         let start = Instant::now();
 
         let result = tokio::time::timeout(
-            std::time::Duration::from_secs(120),
+            timeout,
             client.call_tool(CallToolRequestParams {
                 meta: None,
                 name: Cow::Owned(tool_name.to_string()),
@@ -236,13 +247,11 @@ impl McpSession {
             }),
         )
         .await
-        .map_err(|_| AppError::Internal(format!("Tool execution timed out after 120s: {}", tool_name)))?
+        .map_err(|_| AppError::Internal(format!("Tool execution timed out after {}s: {}", timeout.as_secs(), tool_name)))?
         .map_err(|e| AppError::Internal(format!("Tool execution failed: {}", e)))?;
 
         let elapsed = start.elapsed().as_millis() as i64;
 
-        self.last_used = Utc::now();
-
         // Convert result content to JSON
         // Content in rmcp 0.15 is Annotated<RawContent>; serialize to Value
         let output_parts: Vec<Value> = result.content.iter().map(|content| {
@@ -343,3 +352,75 @@ fn get_node_paths() -> Vec<String> {
 
     paths
 }
+
+/// In-memory MCP server for tests. It answers the handshake and replies "ok" to
+/// every tool call after a delay, counting how many calls overlapped.
+#[cfg(test)]
+pub(crate) mod fake_server {
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+
+    #[derive(Debug, Default)]
+    pub(crate) struct CallStats {
+        pub calls: AtomicUsize,
+        in_flight: AtomicUsize,
+        pub max_in_flight: AtomicUsize,
+    }
+
+    /// Serve MCP on `io` until the client hangs up
+    pub(crate) fn spawn(io: DuplexStream, delay: std::time::Duration) -> Arc<CallStats> {
+        let stats = Arc::new(CallStats::default());
+        let (read, mut write) = tokio::io::split(io);
+        let (replies, mut outbox) = tokio::sync::mpsc::unbounded_channel::<Value>();
+
+        tokio::spawn(async move {
+            while let Some(reply) = outbox.recv().await {
+                if write.write_all(format!("{}\n", reply).as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let server_stats = Arc::clone(&stats);
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(request) = serde_json::from_str::<Value>(&line) else { continue };
+                // Notifications need no reply
+                let Some(id) = request.get("id").cloned() else { continue };
+                match request["method"].as_str() {
+                    Some("initialize") => {
+                        let _ = replies.send(json!({"jsonrpc": "2.0", "id": id, "result": {
+                            "protocolVersion": "2025-03-26",
+                            "capabilities": {"tools": {}},
+                            "serverInfo": {"name": "fake", "version": "1.0.0"},
+                        }}));
+                    }
+                    Some("tools/call") => {
+                        let (stats, replies) = (Arc::clone(&server_stats), replies.clone());
+                        tokio::spawn(async move {
+                            let running = stats.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                            stats.max_in_flight.fetch_max(running, Ordering::SeqCst);
+                            tokio::time::sleep(delay).await;
+                            stats.in_flight.fetch_sub(1, Ordering::SeqCst);
+                            stats.calls.fetch_add(1, Ordering::SeqCst);
+                            let _ = replies.send(json!({"jsonrpc": "2.0", "id": id, "result": {
+                                "content": [{"type": "text", "text": "ok"}],
+                                "isError": false,
+                            }}));
+                        });
+                    }
+                    _ => {
+                        let _ = replies.send(json!({"jsonrpc": "2.0", "id": id, "error": {
+                            "code": -32601, "message": "Method not found",
+                        }}));
+                    }
+                }
+            }
+        });
+
+        stats
+    }
+}
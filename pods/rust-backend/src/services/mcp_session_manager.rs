@@ -1,14 +1,21 @@
 use chrono::Utc;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 
-use super::mcp_session::McpSession;
+use super::mcp_session::{McpPeer, McpSession};
 use crate::error::AppError;
 
-/// Manages a pool of MCP sessions with automatic cleanup
+/// How long a tool call may take when the caller sets no limit of its own
+pub const DEFAULT_TOOL_CALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// Manages a pool of MCP sessions with automatic cleanup. Sessions stay in the
+/// pool while requests run over them, so concurrent steps can share one.
 pub struct McpSessionManager {
     sessions: Arc<RwLock<HashMap<String, McpSession>>>,
+    /// Held while a connection's session is being created, so concurrent
+    /// callers wait for it instead of starting a second server
+    connecting: Mutex<HashMap<String, Arc<Mutex<()>>>>,
     running: Arc<std::sync::atomic::AtomicBool>,
 }
 
@@ -16,6 +23,7 @@ impl McpSessionManager {
     pub fn new() -> Self {
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            connecting: Mutex::new(HashMap::new()),
             running: Arc::new(std::sync::atomic::AtomicBool::new(false)),
         }
     }
@@ -76,23 +84,20 @@ impl McpSessionManager {
         env_vars: Option<&HashMap<String, String>>,
     ) -> Result<(), AppError> {
         // Check if we already have a connected session
-        {
-            let sessions = self.sessions.read().await;
-            if let Some(session) = sessions.get(connection_id) {
-                if session.connected {
-                    return Ok(());
-                }
-            }
+        if self.is_connected(connection_id).await {
+            return Ok(());
         }
 
-        // Remove old disconnected session if exists
-        {
-            let mut sessions = self.sessions.write().await;
-            if let Some(mut old) = sessions.remove(connection_id) {
-                old.cleanup().await;
-            }
+        let lock = self.connect_lock(connection_id).await;
+        let _connecting = lock.lock().await;
+        // Another caller may have connected while we waited
+        if self.is_connected(connection_id).await {
+            return Ok(());
         }
 
+        // Remove old disconnected session if exists
+        self.remove_session(connection_id).await;
+
         // Create new session
         let mut session = McpSession::new(connection_id, "stdio");
         session.connect_stdio(command, args, env_vars).await?;
@@ -109,22 +114,18 @@ impl McpSessionManager {
         base_url: &str,
         api_key: Option<&str>,
     ) -> Result<(), AppError> {
-        {
-            let sessions = self.sessions.read().await;
-            if let Some(session) = sessions.get(connection_id) {
-                if session.connected {
-                    return Ok(());
-                }
-            }
+        if self.is_connected(connection_id).await {
+            return Ok(());
         }
 
-        {
-            let mut sessions = self.sessions.write().await;
-            if let Some(mut old) = sessions.remove(connection_id) {
-                old.cleanup().await;
-            }
+        let lock = self.connect_lock(connection_id).await;
+        let _connecting = lock.lock().await;
+        if self.is_connected(connection_id).await {
+            return Ok(());
         }
 
+        self.remove_session(connection_id).await;
+
         let mut session = McpSession::new(connection_id, "http");
         session.connect_http(base_url, api_key).await?;
 
@@ -133,70 +134,79 @@ impl McpSessionManager {
         Ok(())
     }
 
+    async fn connect_lock(&self, connection_id: &str) -> Arc<Mutex<()>> {
+        let mut connecting = self.connecting.lock().await;
+        Arc::clone(connecting.entry(connection_id.to_string()).or_default())
+    }
+
+    /// Request handle of a connected session, marking the session as used.
+    /// The pool lock is released before the caller sends anything over it.
+    async fn peer(&self, connection_id: &str) -> Result<McpPeer, AppError> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.get_mut(connection_id)
+            .ok_or_else(|| AppError::NotFound(format!(
+                "No active MCP session for connection '{}'", connection_id
+            )))?;
+        let peer = session.peer()
+            .ok_or_else(|| AppError::Internal(format!(
+                "MCP session '{}' is not connected", connection_id
+            )))?;
+        session.last_used = Utc::now();
+        Ok(peer)
+    }
+
     /// List tools for a session
-    /// Takes session out of the map, releases the lock, operates, then puts it back.
     pub async fn list_tools(
         &self,
         connection_id: &str,
         use_cache: bool,
     ) -> Result<Vec<crate::models::mcp_tools::MCPToolInfo>, AppError> {
-        // Take the session out so we don't hold the write lock during the RPC call
-        let mut session = {
-            let mut sessions = self.sessions.write().await;
-            sessions.remove(connection_id)
-                .ok_or_else(|| AppError::NotFound(format!(
-                    "No active MCP session for connection '{}'", connection_id
-                )))?
-        }; // write lock released here
-
-        if !session.connected {
-            // Put it back before returning error
+        if use_cache {
             let mut sessions = self.sessions.write().await;
-            sessions.insert(connection_id.to_string(), session);
-            return Err(AppError::Internal(format!(
-                "MCP session '{}' is not connected", connection_id
-            )));
+            if let Some(session) = sessions.get_mut(connection_id).filter(|s| s.connected && !s.tools_cache.is_empty()) {
+                session.last_used = Utc::now();
+                return Ok(session.tools_cache.clone());
+            }
         }
 
-        let result = session.list_tools(use_cache).await;
+        let peer = self.peer(connection_id).await?;
+        let tools = McpSession::list_tools(&peer).await?;
 
-        // Put session back regardless of result
         let mut sessions = self.sessions.write().await;
-        sessions.insert(connection_id.to_string(), session);
+        if let Some(session) = sessions.get_mut(connection_id) {
+            session.tools_cache = tools.clone();
+            session.last_used = Utc::now();
+        }
 
-        result
+        Ok(tools)
     }
 
     /// Execute a tool on a session
-    /// Takes session out of the map, releases the lock, operates, then puts it back.
     pub async fn call_tool(
         &self,
         connection_id: &str,
         tool_name: &str,
         arguments: Option<serde_json::Map<String, serde_json::Value>>,
     ) -> Result<serde_json::Value, AppError> {
-        // Take the session out so we don't hold the write lock during the RPC call
-        let mut session = {
-            let mut sessions = self.sessions.write().await;
-            sessions.remove(connection_id)
-                .ok_or_else(|| AppError::NotFound(format!(
-                    "No active MCP session for connection '{}'", connection_id
-                )))?
-        }; // write lock released here
-
-        if !session.connected {
-            let mut sessions = self.sessions.write().await;
-            sessions.insert(connection_id.to_string(), session);
-            return Err(AppError::Internal(format!(
-                "MCP session '{}' is not connected", connection_id
-            )));
-        }
+        self.call_tool_with_timeout(connection_id, tool_name, arguments, DEFAULT_TOOL_CALL_TIMEOUT).await
+    }
 
-        let result = session.call_tool(tool_name, arguments).await;
+    /// Execute a tool on a session, giving up after `timeout`. Calls on the
+    /// same session run side by side; dropping one mid-call leaves the session in the pool.
+    pub async fn call_tool_with_timeout(
+        &self,
+        connection_id: &str,
+        tool_name: &str,
+        arguments: Option<serde_json::Map<String, serde_json::Value>>,
+        timeout: std::time::Duration,
+    ) -> Result<serde_json::Value, AppError> {
+        let peer = self.peer(connection_id).await?;
+        let result = McpSession::call_tool(&peer, tool_name, arguments, timeout).await;
 
-        // Put session back regardless of result
         let mut sessions = self.sessions.write().await;
-        sessions.insert(connection_id.to_string(), session);
+        if let Some(session) = sessions.get_mut(connection_id) {
+            session.last_used = Utc::now();
+        }
 
         result
     }
@@ -230,6 +240,21 @@ impl McpSessionManager {
         }
     }
 
+    /// Add a session connected to an in-memory fake server that takes `delay` per tool call
+    #[cfg(test)]
+    pub(crate) async fn connect_fake(
+        &self,
+        connection_id: &str,
+        delay: std::time::Duration,
+    ) -> Arc<super::mcp_session::fake_server::CallStats> {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let stats = super::mcp_session::fake_server::spawn(server, delay);
+        let mut session = McpSession::new(connection_id, "fake");
+        session.connect_io(client).await.expect("fake MCP server handshake");
+        self.sessions.write().await.insert(connection_id.to_string(), session);
+        stats
+    }
+
     /// List all active sessions info
     #[allow(dead_code)]
     pub async fn list_sessions(&self) -> Vec<SessionInfo> {
@@ -265,3 +290,35 @@ pub struct SessionInfo {
     pub last_used: chrono::DateTime<chrono::Utc>,
     pub tools_count: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_a_session() {
+        let manager = McpSessionManager::new();
+        let stats = manager.connect_fake("search", std::time::Duration::from_millis(100)).await;
+
+        let (first, second) = tokio::join!(
+            manager.call_tool("search", "lookup", None),
+            manager.call_tool("search", "lookup", None),
+        );
+        assert_eq!(first.unwrap()["content"][0]["text"], "ok");
+        assert_eq!(second.unwrap()["content"][0]["text"], "ok");
+        assert_eq!(stats.max_in_flight.load(Ordering::SeqCst), 2);
+        assert!(manager.is_connected("search").await);
+    }
+
+    #[tokio::test]
+    async fn test_a_dropped_call_leaves_the_session_in_the_pool() {
+        let manager = McpSessionManager::new();
+        manager.connect_fake("search", std::time::Duration::from_millis(200)).await;
+
+        let call = manager.call_tool("search", "lookup", None);
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(20), call).await.is_err());
+        assert!(manager.is_connected("search").await);
+        assert!(manager.call_tool("search", "lookup", None).await.is_ok());
+    }
+}
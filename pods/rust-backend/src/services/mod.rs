@@ -3,5 +3,12 @@ pub mod mcp_session_manager;
 pub mod agent_api_client;
 pub mod flow_service;
 pub mod flow_executor;
+pub mod execution_rerun;
+pub mod execution_queue;
+pub mod execution_control;
+pub mod flow_inputs;
+pub mod flow_validator;
+pub mod flow_scheduler;
+pub mod webhook_triggers;
 pub mod langsmith_service;
 pub mod license_service;
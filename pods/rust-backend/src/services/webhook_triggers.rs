@@ -0,0 +1,170 @@
+//! Inbound webhook triggers: request authentication and input mapping.
+//!
+//! Each trigger has a URL token (only its SHA-256 is stored). A `secret_url`
+//! trigger accepts any request to its URL; an `hmac` trigger also requires an
+//! HMAC-SHA256 of the raw body, as hex with or without a `sha256=` prefix, in
+//! its signature header (GitHub's `X-Hub-Signature-256` by default, which
+//! also fits Gitea and most issue trackers).
+//!
+//! The request is seen by the mapping as `{body, headers, query}`, with header
+//! names lowercased. Without a mapping, top-level body fields named like the
+//! flow's inputs are used.
+
+use axum::http::HeaderMap;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::models::flow::FlowInput;
+use crate::utils::json_path::get_path;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const DEFAULT_SIGNATURE_HEADER: &str = "x-hub-signature-256";
+/// Bodies above this size are left out of the delivery record
+pub const MAX_RECORDED_BODY_BYTES: usize = 64 * 1024;
+/// Headers never written to the delivery record
+const CREDENTIAL_HEADERS: [&str; 3] = ["authorization", "cookie", "proxy-authorization"];
+
+/// A random URL-safe token for a trigger URL or HMAC secret
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("Failed to generate random token");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hex SHA-256 of a URL token, as stored and looked up
+pub fn hash_token(token: &str) -> String {
+    hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// Check an HMAC-SHA256 signature of `body` in constant time
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let signature = signature.trim();
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+    let Some(expected) = decode_hex(signature) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Hex HMAC-SHA256 of `body`, as a sender computes it
+#[cfg(test)]
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    hex(&mac.finalize().into_bytes())
+}
+
+/// The request as the mapping sees it. `hidden_headers` (the signature) are
+/// left out along with credentials.
+pub fn request_value(body: Value, headers: &HeaderMap, query: &HashMap<String, String>, hidden_headers: &[&str]) -> Value {
+    let headers: serde_json::Map<String, Value> = headers.iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            !CREDENTIAL_HEADERS.contains(&name) && !hidden_headers.iter().any(|h| h.eq_ignore_ascii_case(name))
+        })
+        .filter_map(|(name, value)| Some((name.as_str().to_string(), json!(value.to_str().ok()?))))
+        .collect();
+    json!({ "body": body, "headers": headers, "query": query })
+}
+
+/// Values for the flow from the request: each mapped path that is present,
+/// or the body fields named like declared inputs when there is no mapping
+pub fn map_request(
+    mapping: &HashMap<String, String>,
+    request: &Value,
+    schema: &HashMap<String, FlowInput>,
+) -> HashMap<String, Value> {
+    if mapping.is_empty() {
+        return match request.get("body") {
+            Some(Value::Object(body)) => body.iter()
+                .filter(|(name, _)| schema.contains_key(name.as_str()))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            _ => HashMap::new(),
+        };
+    }
+    mapping.iter()
+        .filter_map(|(name, path)| Some((name.clone(), get_path(request, path)?.clone())))
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_verification() {
+        let body = br#"{"ref":"refs/heads/main"}"#;
+        let signature = sign("s3cret", body);
+        assert!(verify_signature("s3cret", body, &signature));
+        assert!(verify_signature("s3cret", body, &format!("sha256={}", signature)));
+        assert!(!verify_signature("other", body, &signature));
+        assert!(!verify_signature("s3cret", b"{}", &signature));
+        assert!(!verify_signature("s3cret", body, "not-hex"));
+    }
+
+    #[test]
+    fn test_tokens_are_random_and_hashed() {
+        let (a, b) = (generate_token(), generate_token());
+        assert_ne!(a, b);
+        assert_eq!(hash_token(&a), hash_token(&a));
+        assert_eq!(hash_token(&a).len(), 64);
+    }
+
+    #[test]
+    fn test_request_mapping() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-GitHub-Event", "push".parse().unwrap());
+        headers.insert("Authorization", "Bearer t".parse().unwrap());
+        headers.insert("X-Hub-Signature-256", "sha256=00".parse().unwrap());
+        let query = HashMap::from([("env".to_string(), "staging".to_string())]);
+        let request = request_value(
+            json!({"ref": "refs/heads/main", "commits": [{"id": "abc"}]}),
+            &headers,
+            &query,
+            &[DEFAULT_SIGNATURE_HEADER],
+        );
+        assert!(request["headers"].get("authorization").is_none());
+        assert!(request["headers"].get("x-hub-signature-256").is_none());
+
+        let schema: HashMap<String, FlowInput> = serde_json::from_value(json!({
+            "ref": {"type": "string"}, "event": {"type": "string"},
+        })).unwrap();
+        let mapping = HashMap::from([
+            ("event".to_string(), "headers.x-github-event".to_string()),
+            ("commit".to_string(), "body.commits.0.id".to_string()),
+            ("env".to_string(), "query.env".to_string()),
+            ("missing".to_string(), "body.nope".to_string()),
+        ]);
+        let values = map_request(&mapping, &request, &schema);
+        assert_eq!(values.len(), 3);
+        let (inputs, variables) = crate::services::flow_inputs::split_inputs(&schema, values);
+        assert_eq!(inputs, HashMap::from([("event".to_string(), json!("push"))]));
+        assert_eq!(variables["commit"], json!("abc"));
+        assert_eq!(variables["env"], json!("staging"));
+
+        // No mapping: body fields named like inputs
+        let values = map_request(&HashMap::new(), &request, &schema);
+        assert_eq!(values, HashMap::from([("ref".to_string(), json!("refs/heads/main"))]));
+    }
+}
@@ -0,0 +1,56 @@
+use serde_json::Value;
+
+/// Look up a dotted path such as `data.items.0.id` in a JSON value.
+/// Numeric segments index into arrays; a leading `$.` is ignored.
+pub fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim();
+    let path = path.strip_prefix("$.").or_else(|| path.strip_prefix('$')).unwrap_or(path);
+    if path.is_empty() {
+        return Some(value);
+    }
+
+    path.split('.').try_fold(value, |current, segment| match current {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+/// Find the last JSON object embedded in free text (e.g. an LLM reply) that
+/// contains at least one of `keys`.
+pub fn last_json_object_with(text: &str, keys: &[&str]) -> Option<serde_json::Map<String, Value>> {
+    text.char_indices()
+        .rev()
+        .filter(|(_, c)| *c == '{')
+        .find_map(|(i, _)| {
+            let mut stream = serde_json::Deserializer::from_str(&text[i..]).into_iter::<Value>();
+            match stream.next() {
+                Some(Ok(Value::Object(map))) if keys.iter().any(|k| map.contains_key(*k)) => Some(map),
+                _ => None,
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_get_path_objects_and_arrays() {
+        let value = json!({"data": {"items": [{"id": 7}, {"id": 8}]}, "ok": true});
+        assert_eq!(get_path(&value, "data.items.1.id"), Some(&json!(8)));
+        assert_eq!(get_path(&value, "$.ok"), Some(&json!(true)));
+        assert_eq!(get_path(&value, "$"), Some(&value));
+        assert_eq!(get_path(&value, "data.items.5.id"), None);
+        assert_eq!(get_path(&value, "ok.nested"), None);
+    }
+
+    #[test]
+    fn test_last_json_object_with() {
+        let text = "Thinking {\"draft\": 1}... final: {\"label\": \"bug\", \"reason\": \"stack trace\"} done";
+        let found = last_json_object_with(text, &["label"]).unwrap();
+        assert_eq!(found["label"], json!("bug"));
+        assert!(last_json_object_with(text, &["score"]).is_none());
+    }
+}
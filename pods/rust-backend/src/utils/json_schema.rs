@@ -0,0 +1,267 @@
+//! Validation against the subset of JSON Schema used for structured step outputs:
+//! `type` (a name or a list of names), `properties`, `required`,
+//! `additionalProperties: false`, `items`, `enum`, `const`, `minimum`/`maximum`,
+//! `minLength`/`maxLength`, `minItems`/`maxItems` and `pattern`.
+//! Other keywords are ignored.
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+/// Check `value` against `schema`, returning one message per violation with
+/// its location, e.g. `$.items.0.score: expected number`.
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(value, schema, "$", &mut errors);
+    errors
+}
+
+/// Problems with the schema itself, so bad schemas are reported when a flow is
+/// saved instead of on every run
+pub fn check_schema(schema: &Value) -> Result<(), String> {
+    let Value::Object(map) = schema else {
+        return Err("schema must be a JSON object".to_string());
+    };
+    if let Some(types) = map.get("type") {
+        let names: Vec<&Value> = match types {
+            Value::Array(names) => names.iter().collect(),
+            other => vec![other],
+        };
+        for name in names {
+            match name.as_str() {
+                Some("object" | "array" | "string" | "number" | "integer" | "boolean" | "null") => {}
+                _ => return Err(format!("unknown type {}", name)),
+            }
+        }
+    }
+    if let Some(pattern) = map.get("pattern").and_then(|p| p.as_str()) {
+        Regex::new(pattern).map_err(|e| format!("invalid pattern: {}", e))?;
+    }
+    if let Some(properties) = map.get("properties") {
+        let properties = properties.as_object().ok_or("properties must be an object")?;
+        for (name, property) in properties {
+            check_schema(property).map_err(|e| format!("property '{}': {}", name, e))?;
+        }
+    }
+    if let Some(items) = map.get("items") {
+        check_schema(items).map_err(|e| format!("items: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Convert values that are clearly meant as the schema's type (numbers or
+/// booleans sent as strings, a single item where an array is expected)
+pub fn coerce(value: Value, schema: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            Value::Object(map.into_iter().map(|(k, v)| {
+                let v = match properties.and_then(|p| p.get(&k)) {
+                    Some(property) => coerce(v, property),
+                    None => v,
+                };
+                (k, v)
+            }).collect())
+        }
+        Value::Array(items) => match schema.get("items") {
+            Some(item_schema) => Value::Array(items.into_iter().map(|v| coerce(v, item_schema)).collect()),
+            None => Value::Array(items),
+        },
+        Value::String(ref s) if !allows(schema, "string") => {
+            let trimmed = s.trim();
+            if allows(schema, "integer") {
+                if let Ok(i) = trimmed.parse::<i64>() {
+                    return Value::from(i);
+                }
+            }
+            if allows(schema, "number") {
+                if let Some(n) = trimmed.parse::<f64>().ok().and_then(serde_json::Number::from_f64) {
+                    return Value::Number(n);
+                }
+            }
+            if allows(schema, "boolean") && matches!(trimmed, "true" | "false") {
+                return Value::Bool(trimmed == "true");
+            }
+            if allows(schema, "array") {
+                return Value::Array(vec![coerce(value, schema.get("items").unwrap_or(&Value::Null))]);
+            }
+            value
+        }
+        other if allows(schema, "array") && !allows(schema, type_name(&other)) => {
+            Value::Array(vec![coerce(other, schema.get("items").unwrap_or(&Value::Null))])
+        }
+        other => other,
+    }
+}
+
+fn allows(schema: &Value, type_name: &str) -> bool {
+    match schema.get("type") {
+        Some(Value::String(t)) => t == type_name,
+        Some(Value::Array(types)) => types.iter().any(|t| t.as_str() == Some(type_name)),
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(value: &Value, expected: &str) -> bool {
+    match (expected, value) {
+        ("number", Value::Number(_)) => true,
+        ("integer", Value::Number(n)) => n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0),
+        _ => type_name(value) == expected,
+    }
+}
+
+fn check(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Value::Object(schema) = schema else { return };
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|t| matches_type(value, t)) {
+            errors.push(format!("{}: expected {}, got {}", path, names.join(" or "), type_name(value)));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !allowed.contains(value) {
+            let options: Vec<String> = allowed.iter().map(|v| v.to_string()).collect();
+            errors.push(format!("{}: must be one of {}", path, options.join(", ")));
+        }
+    }
+    if let Some(constant) = schema.get("const") {
+        if constant != value {
+            errors.push(format!("{}: must be {}", path, constant));
+        }
+    }
+
+    match value {
+        Value::Object(map) => check_object(map, schema, path, errors),
+        Value::Array(items) => {
+            check_bounds(items.len() as f64, schema, "minItems", "maxItems", "items", path, errors);
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item, item_schema, &format!("{}.{}", path, i), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            check_bounds(s.chars().count() as f64, schema, "minLength", "maxLength", "characters", path, errors);
+            if let Some(pattern) = schema.get("pattern").and_then(|p| p.as_str()) {
+                if Regex::new(pattern).is_ok_and(|re| !re.is_match(s)) {
+                    errors.push(format!("{}: must match pattern {}", path, pattern));
+                }
+            }
+        }
+        Value::Number(n) => {
+            if let Some(n) = n.as_f64() {
+                if schema.get("minimum").and_then(|m| m.as_f64()).is_some_and(|min| n < min) {
+                    errors.push(format!("{}: must be >= {}", path, schema["minimum"]));
+                }
+                if schema.get("maximum").and_then(|m| m.as_f64()).is_some_and(|max| n > max) {
+                    errors.push(format!("{}: must be <= {}", path, schema["maximum"]));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_object(map: &Map<String, Value>, schema: &Map<String, Value>, path: &str, errors: &mut Vec<String>) {
+    for name in schema.get("required").and_then(|r| r.as_array()).into_iter().flatten().filter_map(|n| n.as_str()) {
+        if !map.contains_key(name) {
+            errors.push(format!("{}: missing required field '{}'", path, name));
+        }
+    }
+
+    let properties = schema.get("properties").and_then(|p| p.as_object());
+    for (name, field) in map {
+        match properties.and_then(|p| p.get(name)) {
+            Some(property) => check(field, property, &format!("{}.{}", path, name), errors),
+            None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                errors.push(format!("{}: unexpected field '{}'", path, name));
+            }
+            None => {}
+        }
+    }
+}
+
+fn check_bounds(
+    len: f64,
+    schema: &Map<String, Value>,
+    min_key: &str,
+    max_key: &str,
+    unit: &str,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(min) = schema.get(min_key).and_then(|m| m.as_f64()).filter(|min| len < *min) {
+        errors.push(format!("{}: must have at least {} {}", path, min, unit));
+    }
+    if let Some(max) = schema.get(max_key).and_then(|m| m.as_f64()).filter(|max| len > *max) {
+        errors.push(format!("{}: must have at most {} {}", path, max, unit));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn review_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["verdict", "score"],
+            "additionalProperties": false,
+            "properties": {
+                "verdict": {"type": "string", "enum": ["approve", "reject"]},
+                "score": {"type": "number", "minimum": 0, "maximum": 1},
+                "issues": {"type": "array", "items": {"type": "string", "minLength": 1}},
+            },
+        })
+    }
+
+    #[test]
+    fn test_validate_reports_paths() {
+        assert!(validate(&json!({"verdict": "approve", "score": 0.9, "issues": []}), &review_schema()).is_empty());
+
+        let errors = validate(&json!({"verdict": "maybe", "score": 3, "issues": [""], "extra": 1}), &review_schema());
+        assert_eq!(errors, vec![
+            "$.verdict: must be one of \"approve\", \"reject\"",
+            "$.score: must be <= 1",
+            "$.issues.0: must have at least 1 characters",
+            "$: unexpected field 'extra'",
+        ]);
+
+        let errors = validate(&json!({"score": "high"}), &review_schema());
+        assert_eq!(errors, vec!["$: missing required field 'verdict'", "$.score: expected number, got string"]);
+    }
+
+    #[test]
+    fn test_coerce_repairs_scalar_types() {
+        let coerced = coerce(json!({"verdict": "approve", "score": "0.75", "issues": "typo"}), &review_schema());
+        assert_eq!(coerced, json!({"verdict": "approve", "score": 0.75, "issues": ["typo"]}));
+        assert!(validate(&coerced, &review_schema()).is_empty());
+    }
+
+    #[test]
+    fn test_check_schema() {
+        assert!(check_schema(&review_schema()).is_ok());
+        assert!(check_schema(&json!({"type": "obj"})).is_err());
+        assert!(check_schema(&json!({"properties": {"a": {"pattern": "("}}})).is_err());
+        assert!(check_schema(&json!("string")).is_err());
+    }
+}
@@ -1,2 +1,4 @@
 pub mod object_id;
 pub mod command_utils;
+pub mod json_path;
+pub mod json_schema;
@@ -56,6 +56,10 @@ fn test_flow_step_type_roundtrip() {
         (FlowStepType::FeedbackLoop, "feedback_loop"),
         (FlowStepType::QualityCheck, "quality_check"),
         (FlowStepType::Approval, "approval"),
+        (FlowStepType::Switch, "switch"),
+        (FlowStepType::SubFlow, "sub_flow"),
+        (FlowStepType::Map, "map"),
+        (FlowStepType::HumanInput, "human_input"),
     ];
 
     for (variant, expected_str) in types {
@@ -74,7 +78,9 @@ fn test_flow_execution_status_roundtrip() {
 
     let statuses = vec![
         (FlowExecutionStatus::Pending, "pending"),
+        (FlowExecutionStatus::Queued, "queued"),
         (FlowExecutionStatus::Running, "running"),
+        (FlowExecutionStatus::AwaitingInput, "awaiting_input"),
         (FlowExecutionStatus::Completed, "completed"),
         (FlowExecutionStatus::Failed, "failed"),
         (FlowExecutionStatus::Cancelled, "cancelled"),
@@ -86,21 +92,47 @@ fn test_flow_execution_status_roundtrip() {
     }
 }
 
+/// Test FlowStepStatus enum roundtrip
+#[test]
+fn test_flow_step_status_roundtrip() {
+    use pods_backend::models::flow::FlowStepStatus;
+
+    let statuses = vec![
+        (FlowStepStatus::Pending, "pending"),
+        (FlowStepStatus::Running, "running"),
+        (FlowStepStatus::Completed, "completed"),
+        (FlowStepStatus::Failed, "failed"),
+        (FlowStepStatus::Skipped, "skipped"),
+        (FlowStepStatus::TimedOut, "timed_out"),
+    ];
+
+    for (variant, expected_str) in statuses {
+        let json = serde_json::to_value(&variant).unwrap();
+        assert_eq!(json, json!(expected_str));
+        let back: FlowStepStatus = serde_json::from_value(json).unwrap();
+        assert_eq!(back, variant);
+    }
+}
+
 /// Test FlowEventType enum roundtrip
 #[test]
 fn test_flow_event_type_roundtrip() {
     use pods_backend::models::flow_events::FlowEventType;
 
     let events = vec![
+        (FlowEventType::ExecutionQueued, "execution_queued"),
         (FlowEventType::ExecutionStarted, "execution_started"),
         (FlowEventType::ExecutionCompleted, "execution_completed"),
         (FlowEventType::ExecutionFailed, "execution_failed"),
         (FlowEventType::ExecutionCancelled, "execution_cancelled"),
+        (FlowEventType::ExecutionResumed, "execution_resumed"),
         (FlowEventType::StepStarted, "step_started"),
         (FlowEventType::StepCompleted, "step_completed"),
         (FlowEventType::StepFailed, "step_failed"),
         (FlowEventType::StepSkipped, "step_skipped"),
         (FlowEventType::StepProgress, "step_progress"),
+        (FlowEventType::StepRetrying, "step_retrying"),
+        (FlowEventType::BranchSelected, "branch_selected"),
         (FlowEventType::Heartbeat, "heartbeat"),
         (FlowEventType::LlmResponse, "llm_response"),
         (FlowEventType::LlmStreamingChunk, "llm_streaming_chunk"),
@@ -112,6 +144,8 @@ fn test_flow_event_type_roundtrip() {
         (FlowEventType::ApprovalRequired, "approval_required"),
         (FlowEventType::ApprovalGranted, "approval_granted"),
         (FlowEventType::ApprovalRejected, "approval_rejected"),
+        (FlowEventType::InputRequired, "input_required"),
+        (FlowEventType::InputReceived, "input_received"),
     ];
 
     for (variant, expected_str) in events {
@@ -407,3 +441,133 @@ fn test_token_serialization() {
     assert_eq!(json["access_token"], "eyJhbGciOiJIUzI1NiJ9.xxx.yyy");
     assert_eq!(json["token_type"], "bearer");
 }
+
+/// Test FlowStepResult survives the BSON roundtrip used for execution documents
+#[test]
+fn test_flow_step_result_bson_roundtrip() {
+    use pods_backend::models::flow::{FlowStepResult, FlowStepStatus};
+
+    let start = chrono::Utc::now();
+    let result = FlowStepResult {
+        step_id: "review".to_string(),
+        status: FlowStepStatus::TimedOut,
+        result: Some(json!({"output": {"verdict": "approve"}})),
+        error: Some("Step timed out after 30s".to_string()),
+        start_time: Some(start),
+        end_time: Some(start + chrono::Duration::seconds(30)),
+        execution_time_ms: Some(30_000),
+        retry_attempt: 2,
+        agent_output: None,
+    };
+
+    let bson_value = bson::to_bson(&result).unwrap();
+    let doc = bson::doc! { "step_results": { "review": bson_value } };
+    let step_results: std::collections::HashMap<String, FlowStepResult> =
+        bson::from_document(doc.get_document("step_results").unwrap().clone()).unwrap();
+
+    let back = &step_results["review"];
+    assert_eq!(back.status, FlowStepStatus::TimedOut);
+    assert_eq!(back.start_time, Some(start));
+    assert_eq!(back.execution_time_ms, Some(30_000));
+    assert_eq!(back.retry_attempt, 2);
+    assert_eq!(back.result, Some(json!({"output": {"verdict": "approve"}})));
+}
+
+/// Test ExecutionTrigger is tagged by type and survives the BSON roundtrip,
+/// so executions can be filtered on `trigger.schedule_id`
+#[test]
+fn test_execution_trigger_bson_roundtrip() {
+    use pods_backend::models::flow::ExecutionTrigger;
+
+    let scheduled_for = chrono::Utc::now();
+    let trigger = ExecutionTrigger::Schedule { schedule_id: "s1".to_string(), scheduled_for };
+    let bson_value = bson::to_bson(&trigger).unwrap();
+    let doc = bson_value.as_document().unwrap();
+    assert_eq!(doc.get_str("type").unwrap(), "schedule");
+    assert_eq!(doc.get_str("schedule_id").unwrap(), "s1");
+
+    let back: ExecutionTrigger = bson::from_bson(bson_value).unwrap();
+    assert_eq!(back, trigger);
+    assert_eq!(serde_json::to_value(ExecutionTrigger::Manual).unwrap(), json!({"type": "manual"}));
+}
+
+/// Test the webhook trigger payload defaults and that secrets are only
+/// serialized when set
+#[test]
+fn test_flow_trigger_create_defaults() {
+    use pods_backend::models::trigger::{FlowTriggerCreate, WebhookAuth};
+
+    let create: FlowTriggerCreate = serde_json::from_value(json!({"flow_id": "f1"})).unwrap();
+    assert_eq!(create.auth, WebhookAuth::SecretUrl);
+    assert!(create.enabled);
+    assert!(!create.wait_for_completion);
+    assert!(create.input_mapping.is_empty());
+
+    let create: FlowTriggerCreate = serde_json::from_value(json!({
+        "flow_id": "f1",
+        "auth": "hmac",
+        "input_mapping": {"issue": "body.issue.number"},
+    })).unwrap();
+    assert_eq!(create.auth, WebhookAuth::Hmac);
+    assert_eq!(create.input_mapping["issue"], "body.issue.number");
+}
+
+/// Test an approval decision defaults to approving, with optional comment and
+/// edited output
+#[test]
+fn test_approval_submission_defaults() {
+    use pods_backend::models::flow::ApprovalSubmission;
+
+    let submission: ApprovalSubmission = serde_json::from_value(json!({})).unwrap();
+    assert!(submission.approved);
+    assert!(submission.comment.is_none());
+    assert!(submission.edited_output.is_none());
+
+    let submission: ApprovalSubmission = serde_json::from_value(json!({
+        "approved": false,
+        "comment": "Needs tests",
+    })).unwrap();
+    assert!(!submission.approved);
+    assert_eq!(submission.comment.as_deref(), Some("Needs tests"));
+}
+
+/// Test debug mode settings on an execution request and the pause record
+/// stored while paused
+#[test]
+fn test_execution_debug_bson_roundtrip() {
+    use pods_backend::models::flow::{FlowExecutionCreate, FlowExecutionStatus, PauseReason, PausedAt};
+
+    let create: FlowExecutionCreate = serde_json::from_value(json!({"input_data": {"issue": 42}})).unwrap();
+    assert!(create.debug.is_none());
+
+    let create: FlowExecutionCreate = serde_json::from_value(json!({
+        "debug": {"breakpoints": ["patch", "review"]},
+    })).unwrap();
+    assert_eq!(create.debug.unwrap().breakpoints, vec!["patch", "review"]);
+
+    let paused = PausedAt { step_id: "patch".to_string(), reason: PauseReason::Step, paused_at: chrono::Utc::now() };
+    let bson_doc = bson::to_document(&paused).unwrap();
+    assert_eq!(bson_doc.get_str("reason").unwrap(), "step");
+    let back: PausedAt = bson::from_document(bson_doc).unwrap();
+    assert_eq!(back, paused);
+
+    assert_eq!(json!(FlowExecutionStatus::Paused), json!("paused"));
+}
+
+/// Test the delay and wait-for-signal step types and that a signal needs a
+/// correlation ID
+#[test]
+fn test_delay_and_signal_models() {
+    use pods_backend::models::flow::{FlowExecutionStatus, FlowStep, FlowStepType, SignalDelivery};
+
+    let step: FlowStep = serde_json::from_value(json!({
+        "id": "wait", "name": "Wait", "type": "wait_for_signal", "parameters": {"signal": "ci_finished"},
+    })).unwrap();
+    assert_eq!(step.step_type, FlowStepType::WaitForSignal);
+    assert_eq!(json!(FlowStepType::Delay), json!("delay"));
+    assert_eq!(json!(FlowExecutionStatus::Waiting), json!("waiting"));
+
+    let delivery: SignalDelivery = serde_json::from_value(json!({"correlation_id": "build-881"})).unwrap();
+    assert_eq!(delivery.payload, Value::Null);
+    assert!(serde_json::from_value::<SignalDelivery>(json!({"payload": {"status": "green"}})).is_err());
+}